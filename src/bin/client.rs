@@ -26,6 +26,10 @@ async fn main() -> Result<()> {
             command: Some(Command {
                 command: Some(command::Command::AddQueue(AddQueue {
                     name: queue_name.clone(),
+                    retry_policy: None,
+                    dead_letter: None,
+                    priority_aging_ms: None,
+                    config: None,
                 })),
             }),
         }))
@@ -47,7 +51,18 @@ async fn main() -> Result<()> {
                                 value: "value_1".to_string(),
                             }],
                             status: None,
+                            time_to_run_ms: None,
+                            deadline: None,
+                            retry_policy: None,
+                            attempt: 0,
+                            not_before: None,
+                            failures: Vec::new(),
+                            priority: 0,
+                            ttl_ms: None,
+                            expires_at: None,
                         }),
+                        delay_ms: None,
+                        dedup_key: None,
                     })),
                 }),
             }))
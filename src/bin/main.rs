@@ -1,6 +1,9 @@
 use anyhow::Result;
 use tracing::{Level, info};
 
+use orkidea::exchange::exchange::ExchangeConfig;
+use orkidea::storage::snapshot::SnapshotConfig;
+use orkidea::storage::wal::WalConfig;
 use orkidea::worker::worker::Worker;
 
 fn main() -> Result<()> {
@@ -11,7 +14,13 @@ fn main() -> Result<()> {
 
     info!("--- Starting Orkidea ---");
 
-    Worker::new()?.run()?;
+    let exchange_config = ExchangeConfig {
+        wal: Some(WalConfig::new("./data/exchange.wal".into())),
+        snapshot: Some(SnapshotConfig::new("./data/snapshots".into())),
+        ..ExchangeConfig::default()
+    };
+
+    Worker::new(exchange_config)?.run()?;
 
     Ok(())
 }
@@ -0,0 +1,110 @@
+use anyhow::Result;
+
+use crate::rpc::proto;
+
+use super::event::Event;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeadLetterConfig {
+    // oldest dead-lettered events are dropped past this count
+    max_events: Option<u64>,
+}
+
+impl DeadLetterConfig {
+    pub fn new(max_events: Option<u64>) -> DeadLetterConfig {
+        DeadLetterConfig { max_events }
+    }
+
+    pub fn max_events(&self) -> Option<u64> {
+        self.max_events
+    }
+}
+
+// an event which ended in a failure status, kept with its failure
+// history until it is replayed or purged
+#[derive(Debug, Clone)]
+pub struct DeadLetter {
+    event: Event,
+    dead_lettered_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl DeadLetter {
+    pub fn new(event: Event, dead_lettered_at: chrono::DateTime<chrono::Utc>) -> DeadLetter {
+        DeadLetter {
+            event,
+            dead_lettered_at,
+        }
+    }
+
+    pub fn event(&self) -> &Event {
+        &self.event
+    }
+
+    pub fn into_event(self) -> Event {
+        self.event
+    }
+
+    pub fn dead_lettered_at(&self) -> chrono::DateTime<chrono::Utc> {
+        self.dead_lettered_at
+    }
+}
+
+impl TryFrom<proto::exchange::DeadLetterConfig> for DeadLetterConfig {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: proto::exchange::DeadLetterConfig) -> Result<DeadLetterConfig, Self::Error> {
+        Ok(DeadLetterConfig {
+            max_events: if obj.max_events > 0 {
+                Some(obj.max_events)
+            } else {
+                None
+            },
+        })
+    }
+}
+
+impl TryFrom<DeadLetterConfig> for proto::exchange::DeadLetterConfig {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: DeadLetterConfig) -> Result<proto::exchange::DeadLetterConfig, Self::Error> {
+        Ok(proto::exchange::DeadLetterConfig {
+            max_events: obj.max_events.unwrap_or(0),
+        })
+    }
+}
+
+impl TryFrom<DeadLetter> for proto::exchange::DeadLetter {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: DeadLetter) -> Result<proto::exchange::DeadLetter, Self::Error> {
+        Ok(proto::exchange::DeadLetter {
+            event: Some(proto::exchange::Event::try_from(obj.event)?),
+            dead_lettered_at: obj.dead_lettered_at.timestamp_millis(),
+        })
+    }
+}
+
+impl TryFrom<&DeadLetter> for proto::storage::DeadLetterState {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: &DeadLetter) -> Result<proto::storage::DeadLetterState, Self::Error> {
+        Ok(proto::storage::DeadLetterState {
+            event: Some(proto::storage::EventState::try_from(&obj.event)?),
+            dead_lettered_at: obj.dead_lettered_at.timestamp_millis(),
+        })
+    }
+}
+
+impl TryFrom<proto::storage::DeadLetterState> for DeadLetter {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: proto::storage::DeadLetterState) -> Result<DeadLetter, Self::Error> {
+        let event = Event::try_from(obj.event.ok_or(anyhow::anyhow!("event was None"))?)?;
+        Ok(DeadLetter {
+            event,
+            dead_lettered_at: chrono::DateTime::from_timestamp_millis(obj.dead_lettered_at).ok_or(
+                anyhow::anyhow!("invalid dead lettered at: {}", obj.dead_lettered_at),
+            )?,
+        })
+    }
+}
@@ -2,6 +2,23 @@ use anyhow::Result;
 
 use crate::rpc::proto;
 
+use super::queue::QueueError;
+use super::retry::RetryPolicy;
+
+// durations given in milliseconds are capped at a hundred years so the
+// deadlines computed from them stay within chrono's range
+pub const MAX_DURATION_MS: u64 = 100 * 365 * 24 * 60 * 60 * 1000;
+
+// latest timestamp accepted from clients, the end of year 9999
+pub const MAX_TIMESTAMP_MS: i64 = 253_402_300_799_999;
+
+pub fn bounded_duration(ms: u64) -> Option<chrono::Duration> {
+    if ms > MAX_DURATION_MS {
+        return None;
+    }
+    Some(chrono::Duration::milliseconds(i64::try_from(ms).ok()?))
+}
+
 #[derive(Debug, Clone)]
 pub struct Attribute {
     name: String,
@@ -12,6 +29,14 @@ impl Attribute {
     pub fn new(name: String, value: String) -> Attribute {
         Attribute { name, value }
     }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn value(&self) -> &String {
+        &self.value
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -22,6 +47,48 @@ pub enum EventStatus {
     Errored,
     MissedHeartbeat,
     Timedout,
+    Expired,
+}
+
+// a failed attempt of an event
+#[derive(Debug, Clone)]
+pub struct Failure {
+    attempt: u32,
+    status: EventStatus,
+    worker_id: Option<String>,
+    time: chrono::DateTime<chrono::Utc>,
+}
+
+impl Failure {
+    pub fn new(
+        attempt: u32,
+        status: EventStatus,
+        worker_id: Option<String>,
+        time: chrono::DateTime<chrono::Utc>,
+    ) -> Failure {
+        Failure {
+            attempt,
+            status,
+            worker_id,
+            time,
+        }
+    }
+
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    pub fn status(&self) -> &EventStatus {
+        &self.status
+    }
+
+    pub fn worker_id(&self) -> Option<&String> {
+        self.worker_id.as_ref()
+    }
+
+    pub fn time(&self) -> chrono::DateTime<chrono::Utc> {
+        self.time
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -30,6 +97,31 @@ pub struct Event {
     data: Vec<u8>,
     attributes: Vec<Attribute>,
     status: EventStatus,
+    // higher priorities are claimed first
+    priority: u32,
+    // when the event last became claimable
+    queued_at: Option<chrono::DateTime<chrono::Utc>>,
+
+    // worker which last claimed the event
+    worker_id: Option<String>,
+    // the running event was pushed to a subscriber rather than claimed
+    delivered: bool,
+
+    // maximum time the event may be running before it is timed out
+    time_to_run: Option<chrono::Duration>,
+    deadline: Option<chrono::DateTime<chrono::Utc>>,
+
+    // overrides the retry policy of the queue
+    retry_policy: Option<RetryPolicy>,
+    attempt: u32,
+    // the event is not claimable before this time
+    not_before: Option<chrono::DateTime<chrono::Utc>>,
+    // failed attempts, oldest first
+    failures: Vec<Failure>,
+
+    // maximum time the event may stay queued before it is expired
+    ttl: Option<chrono::Duration>,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 impl Event {
@@ -39,9 +131,135 @@ impl Event {
             data,
             attributes,
             status: EventStatus::Queued,
+            priority: 0,
+            queued_at: None,
+            worker_id: None,
+            delivered: false,
+            time_to_run: None,
+            deadline: None,
+            retry_policy: None,
+            attempt: 1,
+            not_before: None,
+            failures: Vec::new(),
+            ttl: None,
+            expires_at: None,
         }
     }
 
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn data(&self) -> &Vec<u8> {
+        &self.data
+    }
+
+    pub fn attributes(&self) -> &Vec<Attribute> {
+        &self.attributes
+    }
+
+    pub fn add_attribute(&mut self, attribute: Attribute) {
+        self.attributes.push(attribute);
+    }
+
+    pub fn status(&self) -> &EventStatus {
+        &self.status
+    }
+
+    pub fn priority(&self) -> u32 {
+        self.priority
+    }
+
+    pub fn queued_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.queued_at
+    }
+
+    pub fn set_queued_at(&mut self, queued_at: Option<chrono::DateTime<chrono::Utc>>) {
+        self.queued_at = queued_at;
+    }
+
+    pub fn worker_id(&self) -> Option<&String> {
+        self.worker_id.as_ref()
+    }
+
+    pub fn set_worker_id(&mut self, worker_id: Option<String>) {
+        self.worker_id = worker_id;
+    }
+
+    pub fn delivered(&self) -> bool {
+        self.delivered
+    }
+
+    pub fn set_delivered(&mut self, delivered: bool) {
+        self.delivered = delivered;
+    }
+
+    pub fn time_to_run(&self) -> Option<chrono::Duration> {
+        self.time_to_run
+    }
+
+    pub fn set_time_to_run(&mut self, time_to_run: Option<chrono::Duration>) {
+        self.time_to_run = time_to_run;
+    }
+
+    pub fn deadline(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.deadline
+    }
+
+    pub fn set_deadline(&mut self, deadline: Option<chrono::DateTime<chrono::Utc>>) {
+        self.deadline = deadline;
+    }
+
+    pub fn retry_policy(&self) -> Option<&RetryPolicy> {
+        self.retry_policy.as_ref()
+    }
+
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    pub fn set_attempt(&mut self, attempt: u32) {
+        self.attempt = attempt;
+    }
+
+    pub fn not_before(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.not_before
+    }
+
+    pub fn set_not_before(&mut self, not_before: Option<chrono::DateTime<chrono::Utc>>) {
+        self.not_before = not_before;
+    }
+
+    pub fn ttl(&self) -> Option<chrono::Duration> {
+        self.ttl
+    }
+
+    pub fn set_ttl(&mut self, ttl: Option<chrono::Duration>) {
+        self.ttl = ttl;
+    }
+
+    pub fn expires_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.expires_at
+    }
+
+    pub fn set_expires_at(&mut self, expires_at: Option<chrono::DateTime<chrono::Utc>>) {
+        self.expires_at = expires_at;
+    }
+
+    pub fn failures(&self) -> &Vec<Failure> {
+        &self.failures
+    }
+
+    // records the current attempt as failed with the status
+    pub fn add_failure(&mut self, status: EventStatus, time: chrono::DateTime<chrono::Utc>) {
+        self.failures.push(Failure::new(
+            self.attempt,
+            status,
+            self.worker_id.clone(),
+            time,
+        ));
+    }
+
     pub fn set_id(&mut self, id: u64) {
         self.id = id;
     }
@@ -51,6 +269,55 @@ impl Event {
     }
 }
 
+impl TryFrom<Event> for proto::exchange::Event {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: Event) -> Result<proto::exchange::Event, Self::Error> {
+        let mut attributes: Vec<proto::exchange::Attribute> = Vec::new();
+        for item in obj.attributes {
+            attributes.push(proto::exchange::Attribute::try_from(item)?);
+        }
+
+        let time_to_run_ms = match obj.time_to_run {
+            Some(time_to_run) => Some(u64::try_from(time_to_run.num_milliseconds())?),
+            None => None,
+        };
+
+        let ttl_ms = match obj.ttl {
+            Some(ttl) => Some(u64::try_from(ttl.num_milliseconds())?),
+            None => None,
+        };
+
+        let mut failures: Vec<proto::exchange::Failure> = Vec::new();
+        for failure in obj.failures {
+            failures.push(proto::exchange::Failure::try_from(failure)?);
+        }
+
+        Ok(proto::exchange::Event {
+            id: obj.id,
+            data: obj.data,
+            attributes,
+            status: Some(proto::exchange::Status::try_from(obj.status)?),
+            time_to_run_ms,
+            deadline: obj.deadline.map(|deadline| deadline.timestamp_millis()),
+            retry_policy: match obj.retry_policy {
+                Some(retry_policy) => Some(proto::exchange::RetryPolicy::try_from(retry_policy)?),
+                None => None,
+            },
+            attempt: obj.attempt,
+            not_before: obj
+                .not_before
+                .map(|not_before| not_before.timestamp_millis()),
+            failures,
+            priority: obj.priority,
+            ttl_ms,
+            expires_at: obj
+                .expires_at
+                .map(|expires_at| expires_at.timestamp_millis()),
+        })
+    }
+}
+
 impl TryFrom<proto::exchange::Event> for Event {
     type Error = anyhow::Error;
 
@@ -66,11 +333,68 @@ impl TryFrom<proto::exchange::Event> for Event {
             attributes.push(Attribute::try_from(item)?);
         }
 
+        let time_to_run = match obj.time_to_run_ms {
+            // the event would time out on the first sweep after its claim
+            Some(0) => {
+                return Err(
+                    QueueError::InvalidEvent("time to run has to be positive".to_string()).into(),
+                );
+            }
+            Some(time_to_run_ms) => Some(bounded_duration(time_to_run_ms).ok_or(
+                QueueError::InvalidEvent(format!(
+                    "time to run of {} ms exceeds {} ms",
+                    time_to_run_ms, MAX_DURATION_MS
+                )),
+            )?),
+            None => None,
+        };
+
+        let retry_policy = match obj.retry_policy {
+            Some(retry_policy) => Some(RetryPolicy::try_from(retry_policy)?),
+            None => None,
+        };
+
+        let ttl = match obj.ttl_ms {
+            Some(ttl_ms) => Some(bounded_duration(ttl_ms).ok_or(QueueError::InvalidEvent(
+                format!("ttl of {} ms exceeds {} ms", ttl_ms, MAX_DURATION_MS),
+            ))?),
+            None => None,
+        };
+
+        let not_before = match obj.not_before {
+            Some(not_before) if not_before > MAX_TIMESTAMP_MS => {
+                return Err(QueueError::InvalidEvent(format!(
+                    "not before {} is later than {}",
+                    not_before, MAX_TIMESTAMP_MS
+                ))
+                .into());
+            }
+            Some(not_before) => Some(
+                chrono::DateTime::from_timestamp_millis(not_before)
+                    .ok_or(anyhow::anyhow!("invalid not before: {}", not_before))?,
+            ),
+            None => None,
+        };
+
         let event = Event {
             id: obj.id,
             status,
+            priority: obj.priority,
+            queued_at: None,
             attributes,
             data: obj.data.clone(),
+            worker_id: None,
+            delivered: false,
+            time_to_run,
+            deadline: None,
+            retry_policy,
+            // the attempt is the exchange's to track, a client could skip
+            // retries by setting it, so only recovery restores it
+            attempt: 1,
+            not_before,
+            failures: Vec::new(),
+            ttl,
+            expires_at: None,
         };
 
         Ok(event)
@@ -90,11 +414,73 @@ impl TryFrom<proto::exchange::Status> for EventStatus {
                 Ok(EventStatus::MissedHeartbeat)
             }
             Some(proto::exchange::status::DataType::Timedout(_)) => Ok(EventStatus::Timedout),
+            Some(proto::exchange::status::DataType::Expired(_)) => Ok(EventStatus::Expired),
             None => Err(anyhow::anyhow!("received None")),
         }
     }
 }
 
+impl TryFrom<EventStatus> for proto::exchange::Status {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: EventStatus) -> Result<proto::exchange::Status, Self::Error> {
+        let data_type = match obj {
+            EventStatus::Queued => {
+                proto::exchange::status::DataType::Queued(proto::exchange::Queued {})
+            }
+            EventStatus::Running => {
+                proto::exchange::status::DataType::Running(proto::exchange::Running {})
+            }
+            EventStatus::Complete => {
+                proto::exchange::status::DataType::Complete(proto::exchange::Complete {})
+            }
+            EventStatus::Errored => {
+                proto::exchange::status::DataType::Errored(proto::exchange::Errored {})
+            }
+            EventStatus::MissedHeartbeat => proto::exchange::status::DataType::MissedHeartbeat(
+                proto::exchange::MissedHeartbeat {},
+            ),
+            EventStatus::Timedout => {
+                proto::exchange::status::DataType::Timedout(proto::exchange::Timedout {})
+            }
+            EventStatus::Expired => {
+                proto::exchange::status::DataType::Expired(proto::exchange::Expired {})
+            }
+        };
+        Ok(proto::exchange::Status {
+            data_type: Some(data_type),
+        })
+    }
+}
+
+impl TryFrom<Failure> for proto::exchange::Failure {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: Failure) -> Result<proto::exchange::Failure, Self::Error> {
+        Ok(proto::exchange::Failure {
+            attempt: obj.attempt,
+            status: Some(proto::exchange::Status::try_from(obj.status)?),
+            worker_id: obj.worker_id,
+            time: obj.time.timestamp_millis(),
+        })
+    }
+}
+
+impl TryFrom<proto::exchange::Failure> for Failure {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: proto::exchange::Failure) -> Result<Failure, Self::Error> {
+        let status = EventStatus::try_from(obj.status.ok_or(anyhow::anyhow!("status was None"))?)?;
+        Ok(Failure {
+            attempt: obj.attempt,
+            status,
+            worker_id: obj.worker_id,
+            time: chrono::DateTime::from_timestamp_millis(obj.time)
+                .ok_or(anyhow::anyhow!("invalid failure time: {}", obj.time))?,
+        })
+    }
+}
+
 impl TryFrom<proto::exchange::Attribute> for Attribute {
     type Error = anyhow::Error;
 
@@ -105,3 +491,138 @@ impl TryFrom<proto::exchange::Attribute> for Attribute {
         })
     }
 }
+
+impl TryFrom<Attribute> for proto::exchange::Attribute {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: Attribute) -> Result<proto::exchange::Attribute, Self::Error> {
+        Ok(proto::exchange::Attribute {
+            name: obj.name,
+            value: obj.value,
+        })
+    }
+}
+
+impl TryFrom<&Event> for proto::storage::EventState {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: &Event) -> Result<proto::storage::EventState, Self::Error> {
+        Ok(proto::storage::EventState {
+            event: Some(proto::exchange::Event::try_from(obj.clone())?),
+            worker_id: obj.worker_id.clone(),
+            queued_at: obj.queued_at.map(|queued_at| queued_at.timestamp_millis()),
+            delivered: obj.delivered,
+        })
+    }
+}
+
+impl TryFrom<proto::storage::EventState> for Event {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: proto::storage::EventState) -> Result<Event, Self::Error> {
+        let proto_event = obj.event.ok_or(anyhow::anyhow!("event was None"))?;
+        let deadline = match proto_event.deadline {
+            Some(deadline) => Some(
+                chrono::DateTime::from_timestamp_millis(deadline)
+                    .ok_or(anyhow::anyhow!("invalid deadline: {}", deadline))?,
+            ),
+            None => None,
+        };
+
+        let mut failures: Vec<Failure> = Vec::new();
+        for failure in proto_event.failures.clone() {
+            failures.push(Failure::try_from(failure)?);
+        }
+
+        let expires_at = match proto_event.expires_at {
+            Some(expires_at) => Some(
+                chrono::DateTime::from_timestamp_millis(expires_at)
+                    .ok_or(anyhow::anyhow!("invalid expires at: {}", expires_at))?,
+            ),
+            None => None,
+        };
+
+        let attempt = proto_event.attempt.max(1);
+        let mut event = Event::try_from(proto_event)?;
+        event.attempt = attempt;
+        event.worker_id = obj.worker_id;
+        event.delivered = obj.delivered;
+        event.failures = failures;
+        event.deadline = deadline;
+        event.expires_at = expires_at;
+        event.queued_at = match obj.queued_at {
+            Some(queued_at) => Some(
+                chrono::DateTime::from_timestamp_millis(queued_at)
+                    .ok_or(anyhow::anyhow!("invalid queued at: {}", queued_at))?,
+            ),
+            None => None,
+        };
+        Ok(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_past_the_bound_are_refused() {
+        assert!(bounded_duration(MAX_DURATION_MS).is_some());
+        assert!(bounded_duration(MAX_DURATION_MS + 1).is_none());
+        assert!(bounded_duration(u64::MAX).is_none());
+
+        for event in [
+            proto::exchange::Event {
+                time_to_run_ms: Some(u64::MAX),
+                ..Default::default()
+            },
+            proto::exchange::Event {
+                time_to_run_ms: Some(0),
+                ..Default::default()
+            },
+            proto::exchange::Event {
+                ttl_ms: Some(MAX_DURATION_MS + 1),
+                ..Default::default()
+            },
+            proto::exchange::Event {
+                not_before: Some(MAX_TIMESTAMP_MS + 1),
+                ..Default::default()
+            },
+        ] {
+            assert!(Event::try_from(event).is_err());
+        }
+    }
+
+    #[test]
+    fn durations_within_the_bound_are_kept() {
+        let event = Event::try_from(proto::exchange::Event {
+            time_to_run_ms: Some(MAX_DURATION_MS),
+            ttl_ms: Some(1000),
+            not_before: Some(MAX_TIMESTAMP_MS),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(event.time_to_run(), bounded_duration(MAX_DURATION_MS));
+        assert_eq!(event.ttl(), Some(chrono::Duration::seconds(1)));
+        assert_eq!(
+            event.not_before(),
+            chrono::DateTime::from_timestamp_millis(MAX_TIMESTAMP_MS)
+        );
+    }
+
+    #[test]
+    fn attempts_are_only_restored_from_storage() {
+        let proto_event = proto::exchange::Event {
+            attempt: 5,
+            ..Default::default()
+        };
+        assert_eq!(Event::try_from(proto_event.clone()).unwrap().attempt(), 1);
+
+        let event = Event::try_from(proto::storage::EventState {
+            event: Some(proto_event),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(event.attempt(), 5);
+    }
+}
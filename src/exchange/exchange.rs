@@ -2,17 +2,52 @@ use anyhow::Result;
 use std::collections;
 use thiserror::Error;
 use tokio::sync::{mpsc, oneshot};
-use tracing::{error, info};
+use tracing::{debug, error, info, warn};
 
 use crate::exchange::transaction::Command;
+use crate::storage::snapshot::{SnapshotConfig, SnapshotStore};
+use crate::storage::wal::{Wal, WalConfig};
 use crate::{exchange::queue::Queue, rpc::proto};
 
-use super::event::EventStatus;
-use super::transaction::CommandResp;
+use super::event::{Attribute, Event, EventStatus};
+use super::join::{JoinCondition, JoinError, JoinTrigger};
+use super::queue::QueueError;
+use super::routing::{Binding, RoutingError};
+use super::schedule::{Schedule, ScheduleError};
+use super::selector::{Selector, SelectorError};
+use super::subscriber::Subscriber;
+use super::transaction::{CommandResp, Trigger};
+use super::workflow::{Workflow, WorkflowEdge, WorkflowError, WorkflowNode};
 
-pub struct ExchangeReq {
-    command: proto::exchange::Command,
-    resp: oneshot::Sender<ExchangeResp>,
+// attribute set on events materialized by a schedule
+pub const SCHEDULED_AT_ATTRIBUTE: &str = "scheduled_at";
+// attribute set on events routed to a queue by a publish
+pub const TOPIC_ATTRIBUTE: &str = "topic";
+// attributes set on the events of workflow nodes
+pub const WORKFLOW_ID_ATTRIBUTE: &str = "workflow_id";
+pub const WORKFLOW_NODE_ATTRIBUTE: &str = "workflow_node";
+
+pub enum ExchangeReq {
+    Command {
+        command: Box<proto::exchange::Command>,
+        resp: oneshot::Sender<ExchangeResp>,
+    },
+    Subscribe {
+        queue_name: String,
+        prefetch: u64,
+        worker_id: String,
+        selector: Selector,
+        events: mpsc::Sender<proto::exchange::Event>,
+        resp: oneshot::Sender<Result<u64>>,
+    },
+    Unsubscribe {
+        queue_name: String,
+        subscriber_id: u64,
+    },
+    Batch {
+        commands: Vec<proto::exchange::Command>,
+        resp: oneshot::Sender<ExchangeBatchResp>,
+    },
 }
 
 impl ExchangeReq {
@@ -21,19 +56,93 @@ impl ExchangeReq {
     ) -> (ExchangeReq, oneshot::Receiver<ExchangeResp>) {
         let (sender, receiver) = oneshot::channel();
         (
-            ExchangeReq {
-                command,
+            ExchangeReq::Command {
+                command: Box::new(command),
+                resp: sender,
+            },
+            receiver,
+        )
+    }
+
+    pub fn new_subscribe(
+        queue_name: String,
+        prefetch: u64,
+        worker_id: String,
+        selector: Selector,
+    ) -> (
+        ExchangeReq,
+        oneshot::Receiver<Result<u64>>,
+        mpsc::Receiver<proto::exchange::Event>,
+    ) {
+        let (sender, receiver) = oneshot::channel();
+        // the exchange rejects prefetch values above its configured maximum,
+        // the clamp only keeps the channel from panicking before that
+        let capacity = usize::try_from(prefetch.max(1))
+            .unwrap_or(usize::MAX)
+            .min(tokio::sync::Semaphore::MAX_PERMITS);
+        let (events_sender, events_receiver) = mpsc::channel(capacity);
+        (
+            ExchangeReq::Subscribe {
+                queue_name,
+                prefetch,
+                worker_id,
+                selector,
+                events: events_sender,
+                resp: sender,
+            },
+            receiver,
+            events_receiver,
+        )
+    }
+
+    pub fn new_batch(
+        commands: Vec<proto::exchange::Command>,
+    ) -> (ExchangeReq, oneshot::Receiver<ExchangeBatchResp>) {
+        let (sender, receiver) = oneshot::channel();
+        (
+            ExchangeReq::Batch {
+                commands,
                 resp: sender,
             },
             receiver,
         )
     }
+
+    pub fn new_unsubscribe(queue_name: String, subscriber_id: u64) -> ExchangeReq {
+        ExchangeReq::Unsubscribe {
+            queue_name,
+            subscriber_id,
+        }
+    }
 }
 
 pub struct ExchangeResp {
     pub command_resp: proto::exchange::CommandResp,
 }
 
+pub struct ExchangeBatchResp {
+    pub command_resps: Vec<proto::exchange::CommandResp>,
+    pub applied: bool,
+}
+
+// state needed to roll back a batch which failed part way through,
+// queues are copied the first time the batch mutates them
+struct BatchBackup {
+    // None for queues which were created by the batch
+    queues: collections::HashMap<String, Option<Queue>>,
+    subscribers: collections::HashMap<String, Vec<Subscriber>>,
+    schedules: collections::BTreeMap<u64, Schedule>,
+    schedule_idx: u64,
+    bindings: collections::BTreeMap<u64, Binding>,
+    binding_idx: u64,
+    workflows: collections::BTreeMap<u64, Workflow>,
+    workflow_idx: u64,
+    join_triggers: collections::BTreeMap<u64, JoinTrigger>,
+    join_trigger_idx: u64,
+    // queues with events to dispatch once the batch is applied
+    dispatch: collections::HashSet<String>,
+}
+
 #[derive(Debug, Error)]
 pub enum ExchangeError {
     #[error("queue already exists for name {0}")]
@@ -42,6 +151,18 @@ pub enum ExchangeError {
     TransactionNotFound(u64),
     #[error("queue not found: {0}")]
     QueueNotFound(String),
+    #[error("queue has running events: {0}")]
+    QueueHasRunningEvents(String),
+    #[error("join triggers or workflows wait on events of queue: {0}")]
+    QueueHasDependentEvents(String),
+    #[error("schedule not found: {0}")]
+    ScheduleNotFound(u64),
+    #[error("binding not found: {0}")]
+    BindingNotFound(u64),
+    #[error("workflow not found: {0}")]
+    WorkflowNotFound(u64),
+    #[error("join trigger not found: {0}")]
+    JoinTriggerNotFound(u64),
     #[error("lock error")]
     LockError,
     #[error("add event request missing event")]
@@ -50,132 +171,3072 @@ pub enum ExchangeError {
     UpdateEventStatusMissingStatus,
     #[error("command not provided")]
     CommandNotProvided,
+    #[error("command is internal to the exchange")]
+    InternalCommand,
+    #[error("prefetch {0} exceeds the maximum of {1}")]
+    PrefetchTooLarge(u64, u64),
+}
+
+#[derive(Debug, Clone)]
+pub struct ExchangeConfig {
+    // how often the event loop sweeps the queues for expired work
+    pub sweep_interval: std::time::Duration,
+    // running events with a transaction are moved to MissedHeartbeat
+    // if no heartbeat was received within this interval
+    pub heartbeat_interval: std::time::Duration,
+    // subscriptions asking for more events in flight are rejected
+    pub max_prefetch: u64,
+    // how long finished workflows can still be fetched before they are
+    // removed
+    pub workflow_retention: std::time::Duration,
+    // how long dedup keys of added events are remembered, queues may
+    // override it
+    pub dedup_window: std::time::Duration,
+    // commands are only durable when a write-ahead log is configured
+    pub wal: Option<WalConfig>,
+    pub snapshot: Option<SnapshotConfig>,
+}
+
+impl Default for ExchangeConfig {
+    fn default() -> Self {
+        ExchangeConfig {
+            sweep_interval: std::time::Duration::from_secs(1),
+            heartbeat_interval: std::time::Duration::from_secs(30),
+            max_prefetch: 1000,
+            workflow_retention: std::time::Duration::from_secs(3600),
+            dedup_window: std::time::Duration::from_secs(300),
+            wal: None,
+            snapshot: None,
+        }
+    }
+}
+
+// structured error returned to clients in place of a command response
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorCode {
+    Internal,
+    InvalidCommand,
+    QueueNotFound,
+    QueueAlreadyExists,
+    TransactionNotFound,
+    EventNotFound,
+    EventNotRunning,
+    DeadLetterNotFound,
+    ScheduleNotFound,
+    InvalidSchedule,
+    BindingNotFound,
+    InvalidBinding,
+    WorkflowNotFound,
+    InvalidWorkflow,
+    JoinTriggerNotFound,
+    InvalidJoinTrigger,
+    TransactionAlreadyExists,
+    QueueHasRunningEvents,
+    QueueHasDependentEvents,
+    QueueFull,
+    PayloadTooLarge,
+    TooManyAttributes,
+    InvalidQueueConfig,
+}
+
+impl ErrorCode {
+    pub fn from_error(err: &anyhow::Error) -> ErrorCode {
+        if let Some(err) = err.downcast_ref::<ExchangeError>() {
+            return match err {
+                ExchangeError::QueueAlreadyExistsForName(_) => ErrorCode::QueueAlreadyExists,
+                ExchangeError::TransactionNotFound(_) => ErrorCode::TransactionNotFound,
+                ExchangeError::QueueNotFound(_) => ErrorCode::QueueNotFound,
+                ExchangeError::QueueHasRunningEvents(_) => ErrorCode::QueueHasRunningEvents,
+                ExchangeError::QueueHasDependentEvents(_) => ErrorCode::QueueHasDependentEvents,
+                ExchangeError::ScheduleNotFound(_) => ErrorCode::ScheduleNotFound,
+                ExchangeError::BindingNotFound(_) => ErrorCode::BindingNotFound,
+                ExchangeError::WorkflowNotFound(_) => ErrorCode::WorkflowNotFound,
+                ExchangeError::JoinTriggerNotFound(_) => ErrorCode::JoinTriggerNotFound,
+                ExchangeError::LockError => ErrorCode::Internal,
+                ExchangeError::AddEventRequestMissingEvent
+                | ExchangeError::UpdateEventStatusMissingStatus
+                | ExchangeError::CommandNotProvided
+                | ExchangeError::InternalCommand
+                | ExchangeError::PrefetchTooLarge(_, _) => ErrorCode::InvalidCommand,
+            };
+        }
+        if let Some(err) = err.downcast_ref::<QueueError>() {
+            return match err {
+                QueueError::EventNotFound(_) => ErrorCode::EventNotFound,
+                QueueError::EventNotRunning(_) => ErrorCode::EventNotRunning,
+                QueueError::InvalidEvent(_) => ErrorCode::InvalidCommand,
+                QueueError::DeadLetterNotFound(_) => ErrorCode::DeadLetterNotFound,
+                QueueError::TransactionAlreadyExists(_) => ErrorCode::TransactionAlreadyExists,
+                QueueError::QueueFull(_) => ErrorCode::QueueFull,
+                QueueError::PayloadTooLarge { .. } => ErrorCode::PayloadTooLarge,
+                QueueError::TooManyAttributes { .. } => ErrorCode::TooManyAttributes,
+                QueueError::InvalidConfig(_) => ErrorCode::InvalidQueueConfig,
+            };
+        }
+        if let Some(err) = err.downcast_ref::<ScheduleError>() {
+            return match err {
+                ScheduleError::InvalidCronExpr { .. } | ScheduleError::InvalidTimezone(_) => {
+                    ErrorCode::InvalidSchedule
+                }
+            };
+        }
+        if let Some(err) = err.downcast_ref::<RoutingError>() {
+            return match err {
+                RoutingError::InvalidTopicPattern(_) => ErrorCode::InvalidBinding,
+                RoutingError::InvalidTopic(_) => ErrorCode::InvalidCommand,
+            };
+        }
+        if let Some(err) = err.downcast_ref::<WorkflowError>() {
+            return match err {
+                WorkflowError::InvalidWorkflow(_) => ErrorCode::InvalidWorkflow,
+                WorkflowError::NodeNotFound(_) => ErrorCode::InvalidCommand,
+            };
+        }
+        if let Some(err) = err.downcast_ref::<JoinError>() {
+            return match err {
+                JoinError::NoEvents | JoinError::QuorumOutOfRange { .. } => {
+                    ErrorCode::InvalidJoinTrigger
+                }
+            };
+        }
+        if let Some(err) = err.downcast_ref::<SelectorError>() {
+            return match err {
+                SelectorError::MissingName | SelectorError::MissingMatchType(_) => {
+                    ErrorCode::InvalidCommand
+                }
+            };
+        }
+        ErrorCode::Internal
+    }
+}
+
+impl TryFrom<ErrorCode> for proto::exchange::ErrorCode {
+    type Error = anyhow::Error;
+
+    fn try_from(value: ErrorCode) -> Result<Self, Self::Error> {
+        match value {
+            ErrorCode::Internal => Ok(proto::exchange::ErrorCode::Internal),
+            ErrorCode::InvalidCommand => Ok(proto::exchange::ErrorCode::InvalidCommand),
+            ErrorCode::QueueNotFound => Ok(proto::exchange::ErrorCode::QueueNotFound),
+            ErrorCode::QueueAlreadyExists => Ok(proto::exchange::ErrorCode::QueueAlreadyExists),
+            ErrorCode::TransactionNotFound => Ok(proto::exchange::ErrorCode::TransactionNotFound),
+            ErrorCode::EventNotFound => Ok(proto::exchange::ErrorCode::EventNotFound),
+            ErrorCode::EventNotRunning => Ok(proto::exchange::ErrorCode::EventNotRunning),
+            ErrorCode::DeadLetterNotFound => Ok(proto::exchange::ErrorCode::DeadLetterNotFound),
+            ErrorCode::ScheduleNotFound => Ok(proto::exchange::ErrorCode::ScheduleNotFound),
+            ErrorCode::InvalidSchedule => Ok(proto::exchange::ErrorCode::InvalidSchedule),
+            ErrorCode::BindingNotFound => Ok(proto::exchange::ErrorCode::BindingNotFound),
+            ErrorCode::InvalidBinding => Ok(proto::exchange::ErrorCode::InvalidBinding),
+            ErrorCode::WorkflowNotFound => Ok(proto::exchange::ErrorCode::WorkflowNotFound),
+            ErrorCode::InvalidWorkflow => Ok(proto::exchange::ErrorCode::InvalidWorkflow),
+            ErrorCode::JoinTriggerNotFound => Ok(proto::exchange::ErrorCode::JoinTriggerNotFound),
+            ErrorCode::InvalidJoinTrigger => Ok(proto::exchange::ErrorCode::InvalidJoinTrigger),
+            ErrorCode::TransactionAlreadyExists => {
+                Ok(proto::exchange::ErrorCode::TransactionAlreadyExists)
+            }
+            ErrorCode::QueueHasRunningEvents => {
+                Ok(proto::exchange::ErrorCode::QueueHasRunningEvents)
+            }
+            ErrorCode::QueueHasDependentEvents => {
+                Ok(proto::exchange::ErrorCode::QueueHasDependentEvents)
+            }
+            ErrorCode::QueueFull => Ok(proto::exchange::ErrorCode::QueueFull),
+            ErrorCode::PayloadTooLarge => Ok(proto::exchange::ErrorCode::PayloadTooLarge),
+            ErrorCode::TooManyAttributes => Ok(proto::exchange::ErrorCode::TooManyAttributes),
+            ErrorCode::InvalidQueueConfig => Ok(proto::exchange::ErrorCode::InvalidQueueConfig),
+        }
+    }
 }
 
 pub struct Exchange {
+    config: ExchangeConfig,
     queues: collections::HashMap<String, Queue>,
+
+    // indexed by queue name
+    subscribers: collections::HashMap<String, Vec<Subscriber>>,
+    subscriber_idx: u64,
+
+    // indexed by schedule id
+    schedules: collections::BTreeMap<u64, Schedule>,
+    schedule_idx: u64,
+
+    // indexed by binding id
+    bindings: collections::BTreeMap<u64, Binding>,
+    binding_idx: u64,
+
+    // indexed by workflow id
+    workflows: collections::BTreeMap<u64, Workflow>,
+    workflow_idx: u64,
+
+    // indexed by join trigger id, join triggers are removed once decided
+    join_triggers: collections::BTreeMap<u64, JoinTrigger>,
+    join_trigger_idx: u64,
+
+    wal: Option<Wal>,
+    snapshot_store: Option<SnapshotStore>,
+    last_snapshot_sequence: Option<u64>,
+    last_snapshot_time: std::time::Instant,
+
+    batch: Option<BatchBackup>,
+
+    // time of the write-ahead log entry being replayed, commands are
+    // executed as of that time so time dependent state is rebuilt the
+    // same way it was first applied
+    replay_time: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl Default for Exchange {
+    fn default() -> Self {
+        Self::new(ExchangeConfig::default())
+    }
 }
 
 impl Exchange {
-    pub fn new() -> Exchange {
+    pub fn new(config: ExchangeConfig) -> Exchange {
         Exchange {
+            config,
             queues: collections::HashMap::new(),
+            subscribers: collections::HashMap::new(),
+            subscriber_idx: 0,
+            schedules: collections::BTreeMap::new(),
+            schedule_idx: 0,
+            bindings: collections::BTreeMap::new(),
+            binding_idx: 0,
+            workflows: collections::BTreeMap::new(),
+            workflow_idx: 0,
+            join_triggers: collections::BTreeMap::new(),
+            join_trigger_idx: 0,
+            wal: None,
+            snapshot_store: None,
+            last_snapshot_sequence: None,
+            last_snapshot_time: std::time::Instant::now(),
+            batch: None,
+            replay_time: None,
         }
     }
 
-    pub fn run(&mut self, mut receiver: mpsc::Receiver<ExchangeReq>) -> Result<()> {
-        while let Some(msg) = receiver.blocking_recv() {
-            let com = Command::try_from(msg.command)?;
-            let com_resp = self.execute_command(&com)?;
-            let com_resp = proto::exchange::CommandResp::try_from(com_resp)?;
-            let res = msg.resp.send(ExchangeResp {
-                command_resp: com_resp,
-            });
-            if let Err(_) = res {
-                error!("unabled to send response message from exchange");
+    // rebuilds the exchange state from the latest valid snapshot and the
+    // write-ahead log entries recorded after it, then keeps the log open
+    // so new commands are appended to it
+    pub fn recover(&mut self) -> Result<()> {
+        let mut covered_sequence: u64 = 0;
+        if let Some(snapshot_config) = &self.config.snapshot {
+            let snapshot_store = SnapshotStore::new(snapshot_config.clone())?;
+            if let Some(snapshot) = snapshot_store.load_latest()? {
+                info!(sequence = snapshot.sequence, "restoring snapshot");
+                covered_sequence = snapshot.sequence;
+                self.restore(snapshot)?;
             }
+            self.last_snapshot_sequence = Some(covered_sequence);
+            self.snapshot_store = Some(snapshot_store);
+        }
+
+        let wal_config = if let Some(wal_config) = &self.config.wal {
+            wal_config.clone()
+        } else {
+            return self.requeue_delivered_events();
+        };
+
+        let (mut wal, entries) = Wal::open(wal_config)?;
+        wal.advance_sequence(covered_sequence);
+        info!(entries = entries.len(), "replaying write-ahead log");
+
+        for entry in entries {
+            if entry.sequence < covered_sequence {
+                continue;
+            }
+            self.replay_time = chrono::DateTime::from_timestamp_millis(entry.timestamp);
+
+            // commands which failed when they were first applied fail the
+            // same way during replay, leaving the state untouched, entries
+            // which cannot be decoded are reported and skipped rather than
+            // stopping every restart
+            if !entry.batch.is_empty() {
+                let commands: Result<Vec<Command>> =
+                    entry.batch.into_iter().map(Command::try_from).collect();
+                let commands = match commands {
+                    Ok(commands) => commands,
+                    Err(err) => {
+                        error!(
+                            sequence = entry.sequence,
+                            error = format!("{:?}", err),
+                            "skipping invalid batch in write-ahead log"
+                        );
+                        continue;
+                    }
+                };
+                let (_, applied) = self.execute_batch(&commands);
+                if !applied {
+                    debug!(sequence = entry.sequence, "replayed batch was rolled back");
+                }
+                continue;
+            }
+
+            let command = match entry.command.map(Command::try_from) {
+                Some(Ok(command)) => command,
+                Some(Err(err)) => {
+                    error!(
+                        sequence = entry.sequence,
+                        error = format!("{:?}", err),
+                        "skipping invalid command in write-ahead log"
+                    );
+                    continue;
+                }
+                None => {
+                    error!(
+                        sequence = entry.sequence,
+                        "skipping write-ahead log entry without a command"
+                    );
+                    continue;
+                }
+            };
+
+            if let Err(err) = self.execute_command(&command) {
+                debug!(
+                    sequence = entry.sequence,
+                    error = format!("{:?}", err),
+                    "replayed command returned an error"
+                );
+            }
+        }
+
+        self.replay_time = None;
+        self.wal = Some(wal);
+        self.requeue_delivered_events()
+    }
+
+    // subscriptions do not survive a restart, so events pushed to the
+    // subscribers of the previous run are queued again
+    fn requeue_delivered_events(&mut self) -> Result<()> {
+        let mut delivered: Vec<(String, u64)> = Vec::new();
+        for queue in self.queues.values() {
+            for event_id in queue.delivered_events() {
+                delivered.push((queue.name(), event_id));
+            }
+        }
+        for (queue_name, event_id) in delivered {
+            self.apply_command(&Command::UpdateEventStatus {
+                queue_name,
+                event_id,
+                status: EventStatus::Queued,
+            })?;
         }
-        info!("exiting exchange event-loop");
         Ok(())
     }
 
-    fn add_queue(&mut self, queue: Queue) -> Result<()> {
-        if let Some(_) = self.queues.get(&queue.name()) {
-            return Err(ExchangeError::QueueAlreadyExistsForName(queue.name()).into());
-        } else {
+    fn now(&self) -> chrono::DateTime<chrono::Utc> {
+        self.replay_time.unwrap_or_else(chrono::Utc::now)
+    }
+
+    fn restore(&mut self, snapshot: proto::storage::Snapshot) -> Result<()> {
+        self.queues.clear();
+        for queue_state in snapshot.queues {
+            let queue = Queue::try_from(queue_state)?;
             self.queues.insert(queue.name(), queue);
         }
+
+        self.schedules.clear();
+        for schedule in snapshot.schedules {
+            let schedule = Schedule::try_from(schedule)?;
+            self.schedules.insert(schedule.id(), schedule);
+        }
+        self.schedule_idx = snapshot.schedule_idx;
+
+        self.bindings.clear();
+        for binding in snapshot.bindings {
+            let binding = Binding::try_from(binding)?;
+            self.bindings.insert(binding.id(), binding);
+        }
+        self.binding_idx = snapshot.binding_idx;
+
+        self.workflows.clear();
+        for workflow in snapshot.workflows {
+            let workflow = Workflow::try_from(workflow)?;
+            self.workflows.insert(workflow.id(), workflow);
+        }
+        self.workflow_idx = snapshot.workflow_idx;
+
+        self.join_triggers.clear();
+        for join_trigger in snapshot.join_triggers {
+            let join_trigger = JoinTrigger::try_from(join_trigger)?;
+            self.join_triggers.insert(join_trigger.id(), join_trigger);
+        }
+        self.join_trigger_idx = snapshot.join_trigger_idx;
         Ok(())
     }
 
-    fn update_event_status(
-        &mut self,
-        queue_name: &String,
-        event_id: &u64,
-        status: EventStatus,
-    ) -> Result<()> {
-        let queue = if let Some(queue) = self.queues.get_mut(queue_name) {
-            queue
-        } else {
-            return Err(ExchangeError::QueueNotFound(queue_name.clone()).into());
-        };
+    fn snapshot(&self, sequence: u64) -> Result<proto::storage::Snapshot> {
+        let mut queue_names: Vec<&String> = self.queues.keys().collect();
+        queue_names.sort();
 
-        let (event, event_exists) = queue.update_event_status(event_id, status.clone());
+        let mut queues: Vec<proto::storage::QueueState> = Vec::new();
+        for queue_name in queue_names {
+            queues.push(proto::storage::QueueState::try_from(
+                &self.queues[queue_name],
+            )?);
+        }
 
-        if !event_exists || event.is_none() {
-            return Ok(());
+        let mut schedules: Vec<proto::exchange::Schedule> = Vec::new();
+        for schedule in self.schedules.values() {
+            schedules.push(proto::exchange::Schedule::try_from(schedule.clone())?);
         }
 
-        let transaction = if let Some(transaction) = queue.remove_transaction(event_id) {
-            transaction
+        let mut bindings: Vec<proto::exchange::Binding> = Vec::new();
+        for binding in self.bindings.values() {
+            bindings.push(proto::exchange::Binding::try_from(binding.clone())?);
+        }
+
+        let mut workflows: Vec<proto::exchange::Workflow> = Vec::new();
+        for workflow in self.workflows.values() {
+            workflows.push(proto::exchange::Workflow::try_from(workflow.clone())?);
+        }
+
+        let mut join_triggers: Vec<proto::exchange::JoinTrigger> = Vec::new();
+        for join_trigger in self.join_triggers.values() {
+            join_triggers.push(proto::exchange::JoinTrigger::try_from(
+                join_trigger.clone(),
+            )?);
+        }
+
+        Ok(proto::storage::Snapshot {
+            sequence,
+            timestamp: chrono::Utc::now().timestamp_millis(),
+            queues,
+            schedules,
+            schedule_idx: self.schedule_idx,
+            bindings,
+            binding_idx: self.binding_idx,
+            workflows,
+            workflow_idx: self.workflow_idx,
+            join_triggers,
+            join_trigger_idx: self.join_trigger_idx,
+        })
+    }
+
+    // writes a snapshot once the configured interval has passed or the
+    // write-ahead log has grown too large, then truncates the log up to
+    // the oldest snapshot still retained
+    fn maybe_snapshot(&mut self) -> Result<()> {
+        let snapshot_store = if let Some(snapshot_store) = &self.snapshot_store {
+            snapshot_store
         } else {
             return Ok(());
         };
+        let (sequence, wal_bytes) = match &self.wal {
+            Some(wal) => (wal.next_sequence(), wal.size_bytes()),
+            None => (0, 0),
+        };
+        if self.last_snapshot_sequence == Some(sequence) && self.wal.is_some() {
+            return Ok(());
+        }
 
-        for command_trigger in transaction.get_command_triggers() {
-            if !command_trigger.triggered_by_event_status_change(status.clone()) {
-                return Ok(());
-            }
+        let snapshot_config = snapshot_store.config();
+        let interval_elapsed = snapshot_config
+            .interval
+            .is_some_and(|interval| self.last_snapshot_time.elapsed() >= interval);
+        let wal_too_large = snapshot_config
+            .max_wal_bytes
+            .is_some_and(|max_wal_bytes| wal_bytes >= max_wal_bytes);
+        if !interval_elapsed && !wal_too_large {
+            return Ok(());
+        }
 
-            for command in command_trigger.get_commands() {
-                self.execute_command(command)?;
-            }
+        let snapshot = self.snapshot(sequence)?;
+        let path = snapshot_store.write(&snapshot)?;
+        info!(
+            sequence = sequence,
+            path = format!("{:?}", path),
+            "wrote snapshot"
+        );
+        let oldest_sequence = snapshot_store.prune()?;
+
+        if let (Some(wal), Some(oldest_sequence)) = (&mut self.wal, oldest_sequence) {
+            wal.truncate_before(oldest_sequence)?;
         }
 
+        self.last_snapshot_sequence = Some(sequence);
+        self.last_snapshot_time = std::time::Instant::now();
         Ok(())
     }
 
-    fn execute_command(&mut self, command: &Command) -> Result<CommandResp> {
-        match command {
-            Command::AddQueue { name } => {
-                let queue = Queue::new(name.clone());
-                self.add_queue(queue)?;
-                Ok(CommandResp::AddQueue {})
+    pub fn run(&mut self, receiver: mpsc::Receiver<ExchangeReq>) -> Result<()> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()?;
+        runtime.block_on(self.event_loop(receiver))
+    }
+
+    async fn event_loop(&mut self, mut receiver: mpsc::Receiver<ExchangeReq>) -> Result<()> {
+        let mut sweep = tokio::time::interval(self.config.sweep_interval);
+        sweep.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            let wakeup = self.next_delayed_wakeup();
+            tokio::select! {
+                msg = receiver.recv() => match msg {
+                    Some(msg) => {
+                        if let Err(err) = self.handle_req(msg) {
+                            error!(error = format!("{:?}", err), "error handling exchange request");
+                        }
+                    }
+                    None => break,
+                },
+                _ = sweep.tick() => {
+                    if let Err(err) = self.sweep(chrono::Utc::now()) {
+                        error!(error = format!("{:?}", err), "error sweeping exchange");
+                    }
+                }
+                _ = tokio::time::sleep(wakeup.unwrap_or_default()), if wakeup.is_some() => {
+                    if let Err(err) = self.dispatch_delayed_events(chrono::Utc::now()) {
+                        error!(error = format!("{:?}", err), "error dispatching delayed events");
+                    }
+                }
             }
-            Command::AddEvent { queue_name, event } => {
-                let queue = if let Some(queue) = self.queues.get_mut(queue_name) {
-                    queue
-                } else {
-                    return Err(ExchangeError::QueueNotFound(queue_name.clone()).into());
-                };
-                let event_id = queue.add_event(event.clone());
-                Ok(CommandResp::AddEvent { id: event_id })
+        }
+        if let Some(wal) = &mut self.wal {
+            wal.sync()?;
+        }
+        info!("exiting exchange event-loop");
+        Ok(())
+    }
+
+    // time until the next delayed event of a subscribed queue becomes
+    // due, so it is pushed on time even when no requests arrive
+    fn next_delayed_wakeup(&self) -> Option<std::time::Duration> {
+        let next_not_before = self
+            .subscribers
+            .keys()
+            .filter_map(|queue_name| self.queues.get(queue_name))
+            .filter_map(|queue| queue.next_not_before())
+            .min()?;
+        Some(
+            (next_not_before - chrono::Utc::now())
+                .to_std()
+                .unwrap_or_default(),
+        )
+    }
+
+    fn dispatch_delayed_events(&mut self, now: chrono::DateTime<chrono::Utc>) -> Result<()> {
+        let mut due: Vec<String> = Vec::new();
+        for queue_name in self.subscribers.keys() {
+            if let Some(queue) = self.queues.get_mut(queue_name)
+                && queue
+                    .next_not_before()
+                    .is_some_and(|not_before| not_before <= now)
+            {
+                queue.promote_delayed_events(now);
+                due.push(queue_name.clone());
             }
-            Command::AddEvents { queue_name, events } => {
-                let queue = if let Some(queue) = self.queues.get_mut(queue_name) {
-                    queue
-                } else {
-                    return Err(ExchangeError::QueueNotFound(queue_name.clone()).into());
-                };
+        }
+        for queue_name in due {
+            self.dispatch_events(&queue_name)?;
+        }
+        Ok(())
+    }
 
-                let event_ids: Vec<u64> = Vec::new();
-                for event in events {
-                    queue.add_event(event.clone());
+    fn handle_req(&mut self, msg: ExchangeReq) -> Result<()> {
+        match msg {
+            ExchangeReq::Command { command, resp } => {
+                let com_resp = match Command::try_from(*command) {
+                    Ok(com) if com.is_internal() => CommandResp::Error {
+                        code: ErrorCode::InvalidCommand,
+                        error_msg: ExchangeError::InternalCommand.to_string(),
+                    },
+                    Ok(com) => match self.apply_command(&com) {
+                        Ok(com_resp) => com_resp,
+                        Err(err) => {
+                            debug!(error = format!("{:?}", err), "error executing command");
+                            CommandResp::from_error(&err)
+                        }
+                    },
+                    Err(err) => CommandResp::Error {
+                        code: ErrorCode::InvalidCommand,
+                        error_msg: err.to_string(),
+                    },
+                };
+                let com_resp = proto::exchange::CommandResp::try_from(com_resp)
+                    .unwrap_or_else(|err| Exchange::internal_error_resp(&err));
+                let res = resp.send(ExchangeResp {
+                    command_resp: com_resp,
+                });
+                if res.is_err() {
+                    error!("unabled to send response message from exchange");
+                }
+            }
+            ExchangeReq::Subscribe {
+                queue_name,
+                prefetch,
+                worker_id,
+                selector,
+                events,
+                resp,
+            } => {
+                let res = self.subscribe(queue_name, prefetch, worker_id, selector, events);
+                if resp.send(res).is_err() {
+                    error!("unabled to send subscribe response from exchange");
+                }
+            }
+            ExchangeReq::Unsubscribe {
+                queue_name,
+                subscriber_id,
+            } => {
+                self.unsubscribe(&queue_name, subscriber_id)?;
+            }
+            ExchangeReq::Batch { commands, resp } => {
+                let batch_resp = self.apply_batch(commands);
+                if resp.send(batch_resp).is_err() {
+                    error!("unabled to send batch response from exchange");
                 }
+            }
+        }
+        Ok(())
+    }
 
-                Ok(CommandResp::AddEvents { ids: event_ids })
+    // moves running events which missed their heartbeat or their deadline,
+    // expires queued events past their ttl, forgets old dedup keys, pushes
+    // delayed events which became due to subscribers and drops the
+    // subscribers whose streams have been closed
+    fn sweep(&mut self, now: chrono::DateTime<chrono::Utc>) -> Result<()> {
+        let heartbeat_interval = chrono::Duration::from_std(self.config.heartbeat_interval)?;
+
+        let mut missed: Vec<(String, u64)> = Vec::new();
+        for queue in self.queues.values() {
+            for event_id in queue.missed_heartbeats(now, heartbeat_interval) {
+                missed.push((queue.name(), event_id));
             }
-            Command::UpdateEventStatus {
+        }
+        for (queue_name, event_id) in missed {
+            info!(
+                queue = queue_name,
+                event_id = event_id,
+                "event missed heartbeat"
+            );
+            self.apply_command(&Command::UpdateEventStatus {
                 queue_name,
                 event_id,
-                status,
-            } => {
-                self.update_event_status(queue_name, event_id, status.clone())?;
+                status: EventStatus::MissedHeartbeat,
+            })?;
+        }
 
-                Ok(CommandResp::UpdateEventStatus {})
+        let mut timedout: Vec<(String, u64)> = Vec::new();
+        for queue in self.queues.values_mut() {
+            for event_id in queue.timedout_events(now) {
+                timedout.push((queue.name(), event_id));
             }
-            Command::CreateTransaction {
+        }
+        for (queue_name, event_id) in timedout {
+            info!(queue = queue_name, event_id = event_id, "event timed out");
+            self.apply_command(&Command::UpdateEventStatus {
                 queue_name,
                 event_id,
-            } => {
-                let queue = if let Some(queue) = self.queues.get_mut(queue_name) {
-                    queue
-                } else {
-                    return Err(ExchangeError::QueueNotFound(queue_name.clone()).into());
-                };
+                status: EventStatus::Timedout,
+            })?;
+        }
+
+        let dedup_window = chrono::Duration::from_std(self.config.dedup_window)?;
+        for queue in self.queues.values_mut() {
+            queue.prune_dedup_keys(now, dedup_window);
+        }
+
+        // finished workflows are never advanced again, replaying the log
+        // only brings them back until the next sweep
+        let workflow_retention = chrono::Duration::from_std(self.config.workflow_retention)?;
+        self.workflows.retain(|_, workflow| {
+            workflow.finished_at().is_none_or(|finished_at| {
+                finished_at
+                    .checked_add_signed(workflow_retention)
+                    .is_none_or(|retained_until| retained_until > now)
+            })
+        });
+
+        let mut expired: Vec<(String, u64)> = Vec::new();
+        for queue in self.queues.values_mut() {
+            for event_id in queue.expired_events(now) {
+                expired.push((queue.name(), event_id));
+            }
+        }
+        for (queue_name, event_id) in expired {
+            info!(queue = queue_name, event_id = event_id, "event expired");
+            self.apply_command(&Command::UpdateEventStatus {
+                queue_name,
+                event_id,
+                status: EventStatus::Expired,
+            })?;
+        }
+
+        let due: Vec<u64> = self
+            .schedules
+            .values()
+            .filter(|schedule| schedule.is_due(now))
+            .map(|schedule| schedule.id())
+            .collect();
+        for schedule_id in due {
+            if let Err(err) = self.apply_command(&Command::FireSchedule { id: schedule_id }) {
+                error!(
+                    schedule_id = schedule_id,
+                    error = format!("{:?}", err),
+                    "error firing schedule"
+                );
+            }
+        }
+
+        let queue_names: Vec<String> = self.subscribers.keys().cloned().collect();
+        for queue_name in queue_names {
+            self.dispatch_events(&queue_name)?;
+        }
+
+        if let Some(wal) = &mut self.wal {
+            wal.sync_if_due()?;
+        }
+
+        self.maybe_snapshot()
+    }
+
+    // logs the batch as a single write-ahead log entry so replaying it
+    // is all-or-nothing as well
+    fn apply_batch(&mut self, proto_commands: Vec<proto::exchange::Command>) -> ExchangeBatchResp {
+        let mut commands: Vec<Command> = Vec::new();
+        for (idx, command) in proto_commands.iter().enumerate() {
+            match Command::try_from(command.clone()) {
+                Ok(command) if command.is_internal() => {
+                    return Exchange::batch_error(CommandResp::Error {
+                        code: ErrorCode::InvalidCommand,
+                        error_msg: format!(
+                            "invalid command at index {}: {}",
+                            idx,
+                            ExchangeError::InternalCommand
+                        ),
+                    });
+                }
+                Ok(command) => commands.push(command),
+                Err(err) => {
+                    return Exchange::batch_error(CommandResp::Error {
+                        code: ErrorCode::InvalidCommand,
+                        error_msg: format!("invalid command at index {}: {}", idx, err),
+                    });
+                }
+            }
+        }
+
+        if let Some(wal) = &mut self.wal
+            && let Err(err) = wal.append_batch(proto_commands)
+        {
+            error!(error = format!("{:?}", err), "unable to log batch");
+            return Exchange::batch_error(CommandResp::from_error(&err));
+        }
+
+        let (results, applied) = self.execute_batch(&commands);
+
+        let mut command_resps: Vec<proto::exchange::CommandResp> = Vec::new();
+        for result in results {
+            let com_resp = match result {
+                Ok(com_resp) => com_resp,
+                Err(err) => CommandResp::from_error(&err),
+            };
+            match proto::exchange::CommandResp::try_from(com_resp) {
+                Ok(com_resp) => command_resps.push(com_resp),
+                Err(err) => return Exchange::batch_error(CommandResp::from_error(&err)),
+            }
+        }
+        ExchangeBatchResp {
+            command_resps,
+            applied,
+        }
+    }
+
+    fn batch_error(com_resp: CommandResp) -> ExchangeBatchResp {
+        let com_resp = proto::exchange::CommandResp::try_from(com_resp)
+            .unwrap_or_else(|err| Exchange::internal_error_resp(&err));
+        ExchangeBatchResp {
+            command_resps: vec![com_resp],
+            applied: false,
+        }
+    }
+
+    // used when a response cannot be converted, so the client still gets
+    // an error rather than a dropped request
+    fn internal_error_resp(err: &anyhow::Error) -> proto::exchange::CommandResp {
+        error!(
+            error = format!("{:?}", err),
+            "unable to convert command response"
+        );
+        proto::exchange::CommandResp {
+            command_resp: Some(proto::exchange::command_resp::CommandResp::ErrorResp(
+                proto::exchange::ErrorResp {
+                    error_msg: err.to_string(),
+                    code: proto::exchange::ErrorCode::Internal.into(),
+                },
+            )),
+        }
+    }
+
+    // executes the commands in order and stops at the first failure, in
+    // which case every queue touched by the batch is restored
+    fn execute_batch(&mut self, commands: &[Command]) -> (Vec<Result<CommandResp>>, bool) {
+        self.batch = Some(BatchBackup {
+            queues: collections::HashMap::new(),
+            subscribers: self.subscribers.clone(),
+            schedules: self.schedules.clone(),
+            schedule_idx: self.schedule_idx,
+            bindings: self.bindings.clone(),
+            binding_idx: self.binding_idx,
+            workflows: self.workflows.clone(),
+            workflow_idx: self.workflow_idx,
+            join_triggers: self.join_triggers.clone(),
+            join_trigger_idx: self.join_trigger_idx,
+            dispatch: collections::HashSet::new(),
+        });
+
+        let mut results: Vec<Result<CommandResp>> = Vec::new();
+        let mut applied = true;
+        for command in commands {
+            let result = self.execute_command(command);
+            let failed = result.is_err();
+            results.push(result);
+            if failed {
+                applied = false;
+                break;
+            }
+        }
 
-                Ok(CommandResp::CreateTransaction { id: 0 })
+        let batch = if let Some(batch) = self.batch.take() {
+            batch
+        } else {
+            return (results, applied);
+        };
+
+        if applied {
+            for queue_name in batch.dispatch {
+                if let Err(err) = self.dispatch_events(&queue_name) {
+                    error!(
+                        queue = queue_name,
+                        error = format!("{:?}", err),
+                        "error dispatching events after batch"
+                    );
+                }
             }
+        } else {
+            for (queue_name, queue) in batch.queues {
+                match queue {
+                    Some(queue) => {
+                        self.queues.insert(queue_name, queue);
+                    }
+                    None => {
+                        self.queues.remove(&queue_name);
+                    }
+                }
+            }
+            self.subscribers = batch.subscribers;
+            self.schedules = batch.schedules;
+            self.schedule_idx = batch.schedule_idx;
+            self.bindings = batch.bindings;
+            self.binding_idx = batch.binding_idx;
+            self.workflows = batch.workflows;
+            self.workflow_idx = batch.workflow_idx;
+            self.join_triggers = batch.join_triggers;
+            self.join_trigger_idx = batch.join_trigger_idx;
+        }
+
+        (results, applied)
+    }
+
+    fn queue(&self, queue_name: &String) -> Result<&Queue> {
+        match self.queues.get(queue_name) {
+            Some(queue) => Ok(queue),
+            None => Err(ExchangeError::QueueNotFound(queue_name.clone()).into()),
+        }
+    }
+
+    // every mutable access to a queue while a batch is executing goes
+    // through here so the queue can be restored if the batch fails
+    fn queue_mut(&mut self, queue_name: &String) -> Result<&mut Queue> {
+        if let Some(batch) = &mut self.batch
+            && !batch.queues.contains_key(queue_name)
+        {
+            batch
+                .queues
+                .insert(queue_name.clone(), self.queues.get(queue_name).cloned());
+        }
+
+        match self.queues.get_mut(queue_name) {
+            Some(queue) => Ok(queue),
+            None => Err(ExchangeError::QueueNotFound(queue_name.clone()).into()),
+        }
+    }
+
+    // appends the command to the write-ahead log before executing it so
+    // the state can be rebuilt after a restart, commands are fully
+    // validated when converted from their proto form so nothing that
+    // cannot be replayed is logged. Heartbeats are frequent and losing the
+    // last ones in a crash is harmless, so they are not synced right away.
+    fn apply_command(&mut self, command: &Command) -> Result<CommandResp> {
+        if let Some(wal) = &mut self.wal
+            && !command.is_read_only()
+        {
+            let proto_command = proto::exchange::Command::try_from(command.clone())?;
+            match command {
+                Command::Heartbeat { .. } => wal.append_unsynced(proto_command)?,
+                _ => wal.append(proto_command)?,
+            };
+        }
+        self.execute_command(command)
+    }
+
+    fn subscribe(
+        &mut self,
+        queue_name: String,
+        prefetch: u64,
+        worker_id: String,
+        selector: Selector,
+        events: mpsc::Sender<proto::exchange::Event>,
+    ) -> Result<u64> {
+        if !self.queues.contains_key(&queue_name) {
+            return Err(ExchangeError::QueueNotFound(queue_name).into());
+        }
+        if prefetch > self.config.max_prefetch {
+            return Err(ExchangeError::PrefetchTooLarge(prefetch, self.config.max_prefetch).into());
+        }
+
+        let subscriber_id = self.subscriber_idx;
+        self.subscriber_idx += 1;
+
+        self.subscribers
+            .entry(queue_name.clone())
+            .or_default()
+            .push(Subscriber::new(
+                subscriber_id,
+                worker_id,
+                prefetch,
+                selector,
+                events,
+            ));
+
+        self.dispatch_events(&queue_name)?;
+        Ok(subscriber_id)
+    }
+
+    fn unsubscribe(&mut self, queue_name: &String, subscriber_id: u64) -> Result<()> {
+        let subscribers = if let Some(subscribers) = self.subscribers.get_mut(queue_name) {
+            subscribers
+        } else {
+            return Ok(());
+        };
+
+        let mut in_flight: Vec<u64> = Vec::new();
+        subscribers.retain_mut(|subscriber| {
+            if subscriber.id() == subscriber_id {
+                in_flight.extend(subscriber.take_in_flight());
+                false
+            } else {
+                true
+            }
+        });
+
+        let now = self.now();
+        if let Some(queue) = self.queues.get_mut(queue_name) {
+            for event_id in in_flight {
+                if queue.requeue_event(&event_id, now) {
+                    log_requeue(&mut self.wal, queue_name, event_id)?;
+                }
+            }
+        }
+
+        self.dispatch_events(queue_name)
+    }
+
+    // pushes queued events to the subscribers of the queue round-robin
+    // until either the queue is drained or every subscriber is at its
+    // prefetch limit; events of dropped subscribers are requeued
+    fn dispatch_events(&mut self, queue_name: &String) -> Result<()> {
+        if let Some(batch) = &mut self.batch {
+            batch.dispatch.insert(queue_name.clone());
+            return Ok(());
+        }
+
+        let now = self.now();
+        let subscribers = if let Some(subscribers) = self.subscribers.get_mut(queue_name) {
+            subscribers
+        } else {
+            return Ok(());
+        };
+        let queue = if let Some(queue) = self.queues.get_mut(queue_name) {
+            queue
+        } else {
+            return Err(ExchangeError::QueueNotFound(queue_name.clone()).into());
+        };
+
+        let mut dropped: Vec<u64> = Vec::new();
+        for subscriber in subscribers.iter_mut() {
+            if subscriber.is_closed() {
+                dropped.extend(subscriber.take_in_flight());
+            }
+        }
+        subscribers.retain(|subscriber| !subscriber.is_closed());
+        for event_id in dropped {
+            if queue.requeue_event(&event_id, now) {
+                log_requeue(&mut self.wal, queue_name, event_id)?;
+            }
+        }
+
+        loop {
+            let mut pushed = false;
+            for subscriber in subscribers.iter_mut() {
+                if !subscriber.has_capacity() {
+                    continue;
+                }
+                // subscribers with a selector may have nothing to claim
+                // while others still do
+                let event = if let Some(event) = queue
+                    .claim_events(1, subscriber.worker_id(), subscriber.selector(), now)
+                    .pop()
+                {
+                    event
+                } else {
+                    continue;
+                };
+                let event_id = event.id();
+                // deliveries are logged so replay claims the same events,
+                // recovery then queues them again for new subscribers
+                log_command(
+                    &mut self.wal,
+                    Command::DeliverEvent {
+                        queue_name: queue_name.clone(),
+                        event_id,
+                        worker_id: subscriber.worker_id().to_string(),
+                    },
+                )?;
+                queue.set_delivered(&event_id);
+                if subscriber.push(proto::exchange::Event::try_from(event)?) {
+                    pushed = true;
+                } else if queue.requeue_event(&event_id, now) {
+                    log_requeue(&mut self.wal, queue_name, event_id)?;
+                }
+            }
+            if !pushed {
+                return Ok(());
+            }
+        }
+    }
+
+    fn release_event(&mut self, queue_name: &String, event_id: &u64) {
+        if let Some(subscribers) = self.subscribers.get_mut(queue_name) {
+            for subscriber in subscribers.iter_mut() {
+                if subscriber.release(event_id) {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn add_queue(&mut self, queue: Queue) -> Result<()> {
+        if let Some(batch) = &mut self.batch
+            && !self.queues.contains_key(&queue.name())
+        {
+            batch.queues.entry(queue.name()).or_insert(None);
+        }
+
+        match self.queues.entry(queue.name()) {
+            collections::hash_map::Entry::Occupied(_) => {
+                Err(ExchangeError::QueueAlreadyExistsForName(queue.name()).into())
+            }
+            collections::hash_map::Entry::Vacant(entry) => {
+                entry.insert(queue);
+                Ok(())
+            }
+        }
+    }
+
+    // drops the queue's subscribers, closing their streams, along with the
+    // bindings and schedules adding events to it and the join triggers
+    // still waiting on its events, as a queue added later under the same
+    // name reuses the event ids. Workflows cannot drop a started node, so
+    // the delete is refused while one waits on the queue's events.
+    fn delete_queue(&mut self, queue_name: &String, refuse_if_running: bool) -> Result<()> {
+        let queue = self.queue_mut(queue_name)?;
+        if refuse_if_running && queue.has_running_events() {
+            return Err(ExchangeError::QueueHasRunningEvents(queue_name.clone()).into());
+        }
+        if self
+            .workflows
+            .values()
+            .any(|workflow| workflow.waits_on_queue(queue_name))
+        {
+            return Err(ExchangeError::QueueHasDependentEvents(queue_name.clone()).into());
+        }
+
+        self.queues.remove(queue_name);
+        self.subscribers.remove(queue_name);
+        self.bindings
+            .retain(|_, binding| binding.queue_name() != queue_name);
+        self.schedules
+            .retain(|_, schedule| schedule.queue_name() != queue_name);
+        self.join_triggers
+            .retain(|_, join_trigger| !join_trigger.waits_on_queue(queue_name));
+        debug!(queue = queue_name, "deleted queue");
+        Ok(())
+    }
+
+    // expires the oldest queued events the drop-oldest overflow policy
+    // drops to make room for the events, through the same path as any
+    // other final status so their triggers, join triggers and workflows
+    // are resolved
+    fn make_room(&mut self, queue_name: &String, events: &[Event]) -> Result<()> {
+        self.queue(queue_name)?.check_events(events)?;
+        self.expire_overflow_victims(queue_name, events.len() as u64)
+    }
+
+    fn expire_overflow_victims(&mut self, queue_name: &String, count: u64) -> Result<()> {
+        for event_id in self.queue(queue_name)?.overflow_victims(count) {
+            // dispatching after an expiry may have claimed the next victim
+            let queued = self
+                .queue(queue_name)?
+                .event(&event_id)
+                .is_some_and(|event| *event.status() == EventStatus::Queued);
+            if !queued {
+                continue;
+            }
+            info!(
+                queue = queue_name,
+                event_id = event_id,
+                "expiring oldest queued event to make room"
+            );
+            self.update_event_status(queue_name, &event_id, EventStatus::Expired)?;
+        }
+        Ok(())
+    }
+
+    // purged events never reach a final status, so the purge is refused
+    // while join triggers or workflows wait on any of them
+    fn purge_queue(&mut self, queue_name: &String, status: Option<&EventStatus>) -> Result<u64> {
+        let dependants = self
+            .queue(queue_name)?
+            .purgeable_events(status)
+            .iter()
+            .any(|event_id| {
+                self.join_triggers
+                    .values()
+                    .any(|join_trigger| join_trigger.waits_on_event(queue_name, *event_id))
+                    || self
+                        .workflows
+                        .values()
+                        .any(|workflow| workflow.waits_on_event(queue_name, *event_id))
+            });
+        if dependants {
+            return Err(ExchangeError::QueueHasDependentEvents(queue_name.clone()).into());
+        }
+
+        let queue = self.queue_mut(queue_name)?;
+        let event_ids = queue.purge_events(status);
+        for event_id in event_ids.iter() {
+            self.release_event(queue_name, event_id);
+        }
+        self.dispatch_events(queue_name)?;
+        Ok(event_ids.len() as u64)
+    }
+
+    fn update_event_status(
+        &mut self,
+        queue_name: &String,
+        event_id: &u64,
+        status: EventStatus,
+    ) -> Result<()> {
+        let now = self.now();
+        let queue = self.queue_mut(queue_name)?;
+
+        let (event, event_exists) = queue.update_event_status(event_id, status.clone(), now);
+
+        if event_exists && status != EventStatus::Running {
+            self.release_event(queue_name, event_id);
+            self.dispatch_events(queue_name)?;
+        }
+
+        if !event_exists || event.is_none() {
+            return Ok(());
+        }
+
+        self.resolve_join_triggers(queue_name, *event_id, &status);
+
+        let queue = self.queue_mut(queue_name)?;
+
+        let transaction = if let Some(transaction) = queue.remove_transaction(event_id) {
+            transaction
+        } else {
+            return Ok(());
+        };
+
+        for command_trigger in transaction.get_command_triggers() {
+            if !command_trigger.triggered_by_event_status_change(status.clone()) {
+                continue;
+            }
+
+            for command in command_trigger.get_commands() {
+                if let Err(err) = self.execute_command(command) {
+                    error!(
+                        queue = queue_name,
+                        event_id = event_id,
+                        error = format!("{:?}", err),
+                        "error executing triggered command"
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // records the final status of the event on the join triggers waiting
+    // on it, running the commands of those which are satisfied and
+    // dropping those which can no longer be
+    fn resolve_join_triggers(&mut self, queue_name: &str, event_id: u64, status: &EventStatus) {
+        let mut decided: Vec<(u64, bool)> = Vec::new();
+        for join_trigger in self.join_triggers.values_mut() {
+            if !join_trigger.record(queue_name, event_id, status) {
+                continue;
+            }
+            if let Some(satisfied) = join_trigger.resolution() {
+                decided.push((join_trigger.id(), satisfied));
+            }
+        }
+
+        for (join_trigger_id, satisfied) in decided {
+            let join_trigger =
+                if let Some(join_trigger) = self.join_triggers.remove(&join_trigger_id) {
+                    join_trigger
+                } else {
+                    continue;
+                };
+            debug!(
+                join_trigger_id = join_trigger_id,
+                satisfied = satisfied,
+                "join trigger decided"
+            );
+            if !satisfied {
+                continue;
+            }
+            for command in join_trigger.commands() {
+                if let Err(err) = self.execute_command(command) {
+                    error!(
+                        join_trigger_id = join_trigger_id,
+                        error = format!("{:?}", err),
+                        "error executing join triggered command"
+                    );
+                }
+            }
+        }
+    }
+
+    // every event of the join trigger has to exist and not have finished
+    fn add_join_trigger(
+        &mut self,
+        events: &[(String, u64)],
+        condition: &JoinCondition,
+        commands: &[Command],
+    ) -> Result<u64> {
+        for (queue_name, event_id) in events {
+            if self.queue(queue_name)?.event(event_id).is_none() {
+                return Err(QueueError::EventNotFound(*event_id).into());
+            }
+        }
+
+        let join_trigger = JoinTrigger::new(
+            self.join_trigger_idx,
+            events.to_vec(),
+            condition.clone(),
+            commands.to_vec(),
+        )?;
+        self.join_trigger_idx += 1;
+
+        let id = join_trigger.id();
+        self.join_triggers.insert(id, join_trigger);
+        Ok(id)
+    }
+
+    // adds an event from the schedule's template for each due fire time,
+    // the fire time is recorded on the event as a scheduled_at attribute
+    fn fire_schedule(&mut self, schedule_id: &u64) -> Result<Vec<u64>> {
+        let now = self.now();
+        let schedule = if let Some(schedule) = self.schedules.get(schedule_id) {
+            schedule
+        } else {
+            return Err(ExchangeError::ScheduleNotFound(*schedule_id).into());
+        };
+
+        // fire times refused by their queue are dropped rather than
+        // retried, a full queue would otherwise be hit on every sweep
+        let due = schedule.due_fire_times(now);
+        let queue_name = schedule.queue_name().clone();
+        let event_template = schedule.event_template().clone();
+
+        let events: Vec<Event> = due
+            .fire_times()
+            .iter()
+            .map(|fire_time| {
+                let mut event = event_template.clone();
+                event.add_attribute(Attribute::new(
+                    SCHEDULED_AT_ATTRIBUTE.to_string(),
+                    fire_time.to_rfc3339(),
+                ));
+                event
+            })
+            .collect();
+        let event_ids = if events.is_empty() {
+            Vec::new()
+        } else {
+            let fire_times = events.len();
+            match self.make_room(&queue_name, &events).and_then(|_| {
+                let queue = self.queue_mut(&queue_name)?;
+                queue.add_events(events, now)
+            }) {
+                Ok(event_ids) => event_ids,
+                Err(err) => {
+                    warn!(
+                        schedule_id = schedule_id,
+                        queue = queue_name,
+                        fire_times = fire_times,
+                        error = format!("{:?}", err),
+                        "dropped schedule fire times refused by the queue"
+                    );
+                    Vec::new()
+                }
+            }
+        };
+        if let Some(schedule) = self.schedules.get_mut(schedule_id) {
+            schedule.advance(due);
+        }
+        if event_ids.is_empty() {
+            return Ok(event_ids);
+        }
+        debug!(
+            schedule_id = schedule_id,
+            queue = queue_name,
+            events = event_ids.len(),
+            "fired schedule"
+        );
+
+        self.dispatch_events(&queue_name)?;
+        Ok(event_ids)
+    }
+
+    // adds a copy of the event to every queue with a matching binding,
+    // the topic is recorded on each copy as a topic attribute
+    fn publish(&mut self, topic: &String, event: &Event) -> Result<Vec<(String, u64, bool)>> {
+        Binding::validate_topic(topic)?;
+
+        let now = self.now();
+        let mut event = event.clone();
+        event.add_attribute(Attribute::new(TOPIC_ATTRIBUTE.to_string(), topic.clone()));
+
+        // a queue receives a single copy however many of its bindings match
+        let queue_names: collections::BTreeSet<String> = self
+            .bindings
+            .values()
+            .filter(|binding| binding.matches(topic, &event))
+            .map(|binding| binding.queue_name().clone())
+            .collect();
+
+        // every queue is checked first so the copy is added to all of
+        // them or to none
+        for queue_name in queue_names.iter() {
+            self.queue(queue_name)?
+                .check_events(std::slice::from_ref(&event))?;
+        }
+
+        let mut routed: Vec<(String, u64, bool)> = Vec::new();
+        for queue_name in queue_names.iter() {
+            self.make_room(queue_name, std::slice::from_ref(&event))?;
+            let queue = self.queue_mut(queue_name)?;
+            let event_id = queue.add_event(event.clone(), now)?;
+            routed.push((
+                queue_name.clone(),
+                event_id,
+                queue.is_dead_letter(&event_id),
+            ));
+        }
+        for queue_name in queue_names.iter() {
+            self.dispatch_events(queue_name)?;
+        }
+        debug!(topic = topic, queues = routed.len(), "published event");
+        Ok(routed)
+    }
+
+    // starts a workflow instance with the nodes which have no incoming
+    // edges, every queue of the workflow has to exist up front
+    fn submit_workflow(
+        &mut self,
+        name: &str,
+        nodes: &[WorkflowNode],
+        edges: &[WorkflowEdge],
+    ) -> Result<u64> {
+        for node in nodes {
+            self.queue(node.queue_name())?;
+        }
+
+        let workflow = Workflow::new(
+            self.workflow_idx,
+            name.to_string(),
+            nodes.to_vec(),
+            edges.to_vec(),
+            self.now(),
+        )?;
+
+        // the root events are checked against their queues before anything
+        // is added so a refused event leaves no partial workflow behind
+        let workflow_id = workflow.id();
+        let root_nodes = workflow.root_nodes();
+        let mut root_events: collections::BTreeMap<String, Vec<Event>> =
+            collections::BTreeMap::new();
+        for node_name in root_nodes.iter() {
+            let node = workflow.node(node_name)?;
+            root_events
+                .entry(node.queue_name().clone())
+                .or_default()
+                .push(Exchange::workflow_node_event(workflow_id, node));
+        }
+        for (queue_name, events) in root_events.iter() {
+            self.queue(queue_name)?.check_events(events)?;
+        }
+
+        self.workflow_idx += 1;
+        self.workflows.insert(workflow_id, workflow);
+        self.start_workflow_nodes(workflow_id, root_nodes)?;
+        debug!(workflow_id = workflow_id, "submitted workflow");
+        Ok(workflow_id)
+    }
+
+    // starts the ready nodes in order, a node whose event cannot be added
+    // is finished as Errored so the nodes downstream of it are resolved
+    // and the workflow still finishes
+    fn start_workflow_nodes(&mut self, workflow_id: u64, nodes: Vec<String>) -> Result<Vec<u64>> {
+        let mut pending: collections::VecDeque<String> = nodes.into();
+        let mut event_ids: Vec<u64> = Vec::new();
+        while let Some(node_name) = pending.pop_front() {
+            // a node can be unblocked again while it is waiting here, or
+            // have been started by an advance nested in an earlier start
+            let workflow = if let Some(workflow) = self.workflows.get(&workflow_id) {
+                workflow
+            } else {
+                return Err(ExchangeError::WorkflowNotFound(workflow_id).into());
+            };
+            if !workflow.node_pending(&node_name) {
+                continue;
+            }
+
+            let err = match self.start_workflow_node(workflow_id, &node_name) {
+                Ok(event_id) => {
+                    event_ids.push(event_id);
+                    continue;
+                }
+                Err(err) => err,
+            };
+            error!(
+                workflow_id = workflow_id,
+                node = node_name,
+                error = format!("{:?}", err),
+                "unable to start workflow node"
+            );
+            let now = self.now();
+            if let Some(workflow) = self.workflows.get_mut(&workflow_id) {
+                pending.extend(workflow.fail_node(&node_name, EventStatus::Errored, now)?);
+            }
+        }
+        Ok(event_ids)
+    }
+
+    // adds the node's event with a trigger for every final status, each
+    // advancing the workflow once the event finishes
+    fn start_workflow_node(&mut self, workflow_id: u64, node_name: &String) -> Result<u64> {
+        let now = self.now();
+        let workflow = if let Some(workflow) = self.workflows.get(&workflow_id) {
+            workflow
+        } else {
+            return Err(ExchangeError::WorkflowNotFound(workflow_id).into());
+        };
+        let node = workflow.node(node_name)?;
+        let queue_name = node.queue_name().clone();
+        let event = Exchange::workflow_node_event(workflow_id, node);
+
+        self.make_room(&queue_name, std::slice::from_ref(&event))?;
+        let event_id = self.queue_mut(&queue_name)?.add_event(event, now)?;
+        if let Some(workflow) = self.workflows.get_mut(&workflow_id) {
+            workflow.start_node(node_name, event_id)?;
+        }
+        // an event dead-lettered by a full queue never runs, so the node
+        // finishes right away as if the event expired
+        let queue = self.queue_mut(&queue_name)?;
+        if queue.is_dead_letter(&event_id) {
+            self.advance_workflow(workflow_id, node_name, &EventStatus::Expired)?;
+            return Ok(event_id);
+        }
+        for status in [
+            EventStatus::Complete,
+            EventStatus::Errored,
+            EventStatus::MissedHeartbeat,
+            EventStatus::Timedout,
+            EventStatus::Expired,
+        ] {
+            let trigger = if let Some(trigger) = Trigger::on_status(event_id, &status) {
+                trigger
+            } else {
+                continue;
+            };
+            queue.add_command_trigger(
+                &event_id,
+                trigger,
+                Command::AdvanceWorkflow {
+                    workflow_id,
+                    node: node_name.clone(),
+                    status,
+                },
+            )?;
+        }
+
+        self.dispatch_events(&queue_name)?;
+        Ok(event_id)
+    }
+
+    // the node's event template tagged with the workflow and node
+    fn workflow_node_event(workflow_id: u64, node: &WorkflowNode) -> Event {
+        let mut event = node.event_template().clone();
+        event.add_attribute(Attribute::new(
+            WORKFLOW_ID_ATTRIBUTE.to_string(),
+            workflow_id.to_string(),
+        ));
+        event.add_attribute(Attribute::new(
+            WORKFLOW_NODE_ATTRIBUTE.to_string(),
+            node.name().clone(),
+        ));
+        event
+    }
+
+    fn advance_workflow(
+        &mut self,
+        workflow_id: u64,
+        node_name: &String,
+        status: &EventStatus,
+    ) -> Result<Vec<u64>> {
+        let now = self.now();
+        let workflow = if let Some(workflow) = self.workflows.get_mut(&workflow_id) {
+            workflow
+        } else {
+            return Err(ExchangeError::WorkflowNotFound(workflow_id).into());
+        };
+
+        let ready = workflow.finish_node(node_name, status.clone(), now)?;
+        let event_ids = self.start_workflow_nodes(workflow_id, ready)?;
+        debug!(
+            workflow_id = workflow_id,
+            node = node_name,
+            started = event_ids.len(),
+            "advanced workflow"
+        );
+        Ok(event_ids)
+    }
+
+    // a relative delay is resolved against the time the command is applied
+    fn delay_event(&self, event: &Event, delay: &Option<chrono::Duration>) -> Result<Event> {
+        let mut event = event.clone();
+        if let Some(delay) = delay {
+            let not_before =
+                self.now()
+                    .checked_add_signed(*delay)
+                    .ok_or(QueueError::InvalidEvent(format!(
+                        "delay of {} is out of range",
+                        delay
+                    )))?;
+            event.set_not_before(Some(not_before));
+        }
+        Ok(event)
+    }
+
+    fn execute_command(&mut self, command: &Command) -> Result<CommandResp> {
+        match command {
+            Command::AddQueue {
+                name,
+                config,
+                dead_letter,
+                priority_aging,
+            } => {
+                let mut queue = Queue::new(name.clone());
+                queue.set_dead_letter(dead_letter.clone());
+                queue.set_priority_aging(*priority_aging);
+                queue.set_config(config.clone())?;
+                self.add_queue(queue)?;
+                Ok(CommandResp::AddQueue {})
+            }
+            Command::AddEvent {
+                queue_name,
+                event,
+                delay,
+                dedup_key,
+            } => {
+                let now = self.now();
+                let dedup_window = chrono::Duration::from_std(self.config.dedup_window)?;
+                let event = self.delay_event(event, delay)?;
+                let queue = self.queue_mut(queue_name)?;
+                // a retried add returns the id of the event added the first time
+                if let Some(dedup_key) = dedup_key
+                    && let Some(event_id) = queue
+                        .dedup_event_ids(dedup_key, now, dedup_window)
+                        .and_then(|event_ids| event_ids.first())
+                {
+                    return Ok(CommandResp::AddEvent {
+                        id: *event_id,
+                        dead_lettered: queue.is_dead_letter(event_id),
+                    });
+                }
+
+                self.make_room(queue_name, std::slice::from_ref(&event))?;
+                let queue = self.queue_mut(queue_name)?;
+                let event_id = queue.add_event(event, now)?;
+                let dead_lettered = queue.is_dead_letter(&event_id);
+                if let Some(dedup_key) = dedup_key {
+                    queue.add_dedup_key(dedup_key.clone(), vec![event_id], now);
+                }
+                self.dispatch_events(queue_name)?;
+                Ok(CommandResp::AddEvent {
+                    id: event_id,
+                    dead_lettered,
+                })
+            }
+            Command::AddEvents {
+                queue_name,
+                events,
+                delay,
+                dedup_key,
+            } => {
+                let events: Vec<Event> = events
+                    .iter()
+                    .map(|event| self.delay_event(event, delay))
+                    .collect::<Result<Vec<Event>>>()?;
+                let now = self.now();
+                let dedup_window = chrono::Duration::from_std(self.config.dedup_window)?;
+                let queue = self.queue_mut(queue_name)?;
+                if let Some(dedup_key) = dedup_key
+                    && let Some(event_ids) = queue.dedup_event_ids(dedup_key, now, dedup_window)
+                {
+                    return Ok(CommandResp::AddEvents {
+                        ids: event_ids.clone(),
+                        dead_lettered_ids: queue.dead_letter_subset(event_ids),
+                    });
+                }
+
+                self.make_room(queue_name, &events)?;
+                let queue = self.queue_mut(queue_name)?;
+                let event_ids = queue.add_events(events, now)?;
+                let dead_lettered_ids = queue.dead_letter_subset(&event_ids);
+                if let Some(dedup_key) = dedup_key {
+                    queue.add_dedup_key(dedup_key.clone(), event_ids.clone(), now);
+                }
+                self.dispatch_events(queue_name)?;
+
+                Ok(CommandResp::AddEvents {
+                    ids: event_ids,
+                    dead_lettered_ids,
+                })
+            }
+            Command::UpdateEventStatus {
+                queue_name,
+                event_id,
+                status,
+            } => {
+                self.update_event_status(queue_name, event_id, status.clone())?;
+
+                Ok(CommandResp::UpdateEventStatus {})
+            }
+            Command::CreateTransaction {
+                queue_name,
+                event_id,
+            } => {
+                let queue = self.queue_mut(queue_name)?;
+
+                let id = queue.create_transaction(*event_id)?;
+                Ok(CommandResp::CreateTransaction { id })
+            }
+            Command::ClaimEvents {
+                queue_name,
+                max,
+                worker_id,
+                selector,
+            } => {
+                let now = self.now();
+                let queue = self.queue_mut(queue_name)?;
+
+                let events = queue.claim_events(*max, worker_id, selector, now);
+                Ok(CommandResp::ClaimEvents { events })
+            }
+            Command::Heartbeat {
+                queue_name,
+                event_id,
+            } => {
+                let queue = self.queue_mut(queue_name)?;
+
+                queue.heartbeat(event_id)?;
+                Ok(CommandResp::Heartbeat {})
+            }
+            Command::AddCommandTrigger {
+                queue_name,
+                event_id,
+                trigger,
+                command,
+            } => {
+                let queue = self.queue_mut(queue_name)?;
+
+                let transaction_id =
+                    queue.add_command_trigger(event_id, trigger.clone(), *command.clone())?;
+                Ok(CommandResp::AddCommandTrigger { transaction_id })
+            }
+            Command::DeliverEvent {
+                queue_name,
+                event_id,
+                worker_id,
+            } => {
+                let now = self.now();
+                let queue = self.queue_mut(queue_name)?;
+
+                queue.deliver_event(event_id, worker_id, now)?;
+                Ok(CommandResp::DeliverEvent {})
+            }
+            Command::ListDeadLetters {
+                queue_name,
+                after_event_id,
+                limit,
+            } => {
+                let queue = self.queue(queue_name)?;
+
+                let dead_letters = queue.list_dead_letters(*after_event_id, *limit);
+                Ok(CommandResp::ListDeadLetters { dead_letters })
+            }
+            Command::GetDeadLetter {
+                queue_name,
+                event_id,
+            } => {
+                let queue = self.queue(queue_name)?;
+
+                let dead_letter = Box::new(queue.get_dead_letter(event_id)?.clone());
+                Ok(CommandResp::GetDeadLetter { dead_letter })
+            }
+            Command::ReplayDeadLetters {
+                queue_name,
+                event_ids,
+            } => {
+                let now = self.now();
+                // the ids are resolved first so the events expired to make
+                // room are not replayed along with them
+                let queue = self.queue(queue_name)?;
+                let event_ids = queue.dead_letter_ids(event_ids)?;
+                queue.check_replay(&event_ids)?;
+                self.expire_overflow_victims(queue_name, event_ids.len() as u64)?;
+
+                let queue = self.queue_mut(queue_name)?;
+                let event_ids = queue.replay_dead_letters(&event_ids, now)?;
+                self.dispatch_events(queue_name)?;
+                Ok(CommandResp::ReplayDeadLetters { event_ids })
+            }
+            Command::PurgeDeadLetters {
+                queue_name,
+                event_ids,
+            } => {
+                let queue = self.queue_mut(queue_name)?;
+
+                let purged = queue.purge_dead_letters(event_ids)?;
+                Ok(CommandResp::PurgeDeadLetters { purged })
+            }
+            Command::AddSchedule {
+                queue_name,
+                cron_expr,
+                timezone,
+                event_template,
+                missed_fire_policy,
+            } => {
+                // the template is checked as it is fired, with the
+                // scheduled at attribute, so it is not refused on every fire
+                let mut event = event_template.clone();
+                event.add_attribute(Attribute::new(
+                    SCHEDULED_AT_ATTRIBUTE.to_string(),
+                    self.now().to_rfc3339(),
+                ));
+                self.queue(queue_name)?.validate_event(&event)?;
+
+                let schedule = Schedule::new(
+                    self.schedule_idx,
+                    queue_name.clone(),
+                    cron_expr.clone(),
+                    timezone.clone(),
+                    event_template.clone(),
+                    missed_fire_policy.clone(),
+                    self.now(),
+                )?;
+                self.schedule_idx += 1;
+
+                let (id, next_fire_time) = (schedule.id(), schedule.next_fire_time());
+                self.schedules.insert(id, schedule);
+                Ok(CommandResp::AddSchedule { id, next_fire_time })
+            }
+            Command::RemoveSchedule { id } => {
+                if self.schedules.remove(id).is_none() {
+                    return Err(ExchangeError::ScheduleNotFound(*id).into());
+                }
+                Ok(CommandResp::RemoveSchedule {})
+            }
+            Command::ListSchedules { queue_name } => {
+                let schedules: Vec<Schedule> = self
+                    .schedules
+                    .values()
+                    .filter(|schedule| queue_name.is_empty() || schedule.queue_name() == queue_name)
+                    .cloned()
+                    .collect();
+                Ok(CommandResp::ListSchedules { schedules })
+            }
+            Command::FireSchedule { id } => {
+                let event_ids = self.fire_schedule(id)?;
+                Ok(CommandResp::FireSchedule { event_ids })
+            }
+            Command::AddBinding {
+                topic_pattern,
+                queue_name,
+                selector,
+            } => {
+                self.queue(queue_name)?;
+                let binding = Binding::new(
+                    self.binding_idx,
+                    topic_pattern.clone(),
+                    queue_name.clone(),
+                    selector.clone(),
+                )?;
+                self.binding_idx += 1;
+
+                let id = binding.id();
+                self.bindings.insert(id, binding);
+                Ok(CommandResp::AddBinding { id })
+            }
+            Command::RemoveBinding { id } => {
+                if self.bindings.remove(id).is_none() {
+                    return Err(ExchangeError::BindingNotFound(*id).into());
+                }
+                Ok(CommandResp::RemoveBinding {})
+            }
+            Command::ListBindings { queue_name } => {
+                let bindings: Vec<Binding> = self
+                    .bindings
+                    .values()
+                    .filter(|binding| queue_name.is_empty() || binding.queue_name() == queue_name)
+                    .cloned()
+                    .collect();
+                Ok(CommandResp::ListBindings { bindings })
+            }
+            Command::Publish { topic, event } => {
+                let events = self.publish(topic, event)?;
+                Ok(CommandResp::Publish { events })
+            }
+            Command::SubmitWorkflow { name, nodes, edges } => {
+                let id = self.submit_workflow(name, nodes, edges)?;
+                Ok(CommandResp::SubmitWorkflow { id })
+            }
+            Command::GetWorkflow { id } => match self.workflows.get(id) {
+                Some(workflow) => Ok(CommandResp::GetWorkflow {
+                    workflow: Box::new(workflow.clone()),
+                }),
+                None => Err(ExchangeError::WorkflowNotFound(*id).into()),
+            },
+            Command::AdvanceWorkflow {
+                workflow_id,
+                node,
+                status,
+            } => {
+                let event_ids = self.advance_workflow(*workflow_id, node, status)?;
+                Ok(CommandResp::AdvanceWorkflow { event_ids })
+            }
+            Command::AddJoinTrigger {
+                events,
+                condition,
+                commands,
+            } => {
+                let id = self.add_join_trigger(events, condition, commands)?;
+                Ok(CommandResp::AddJoinTrigger { id })
+            }
+            Command::RemoveJoinTrigger { id } => {
+                if self.join_triggers.remove(id).is_none() {
+                    return Err(ExchangeError::JoinTriggerNotFound(*id).into());
+                }
+                Ok(CommandResp::RemoveJoinTrigger {})
+            }
+            Command::GetJoinTrigger { id } => match self.join_triggers.get(id) {
+                Some(join_trigger) => Ok(CommandResp::GetJoinTrigger {
+                    join_trigger: Box::new(join_trigger.clone()),
+                }),
+                None => Err(ExchangeError::JoinTriggerNotFound(*id).into()),
+            },
+            Command::GetTransaction {
+                queue_name,
+                transaction_id,
+            } => {
+                let queue = self.queue(queue_name)?;
+
+                match queue.transaction(*transaction_id) {
+                    Some(transaction) => Ok(CommandResp::GetTransaction {
+                        transaction: transaction.clone(),
+                    }),
+                    None => Err(ExchangeError::TransactionNotFound(*transaction_id).into()),
+                }
+            }
+            Command::ListTransactions { queue_name } => {
+                let queue = self.queue(queue_name)?;
+
+                let transactions = queue.transactions().into_iter().cloned().collect();
+                Ok(CommandResp::ListTransactions { transactions })
+            }
+            Command::AbortTransaction {
+                queue_name,
+                transaction_id,
+            } => {
+                let queue = self.queue_mut(queue_name)?;
+
+                if queue.abort_transaction(*transaction_id).is_none() {
+                    return Err(ExchangeError::TransactionNotFound(*transaction_id).into());
+                }
+                Ok(CommandResp::AbortTransaction {})
+            }
+            Command::GetEvent {
+                queue_name,
+                event_id,
+            } => {
+                let queue = self.queue(queue_name)?;
+
+                match queue.event(event_id) {
+                    Some(event) => Ok(CommandResp::GetEvent {
+                        event: Box::new(event.clone()),
+                    }),
+                    None => Err(QueueError::EventNotFound(*event_id).into()),
+                }
+            }
+            Command::ListEvents {
+                queue_name,
+                status,
+                after_event_id,
+                limit,
+            } => {
+                let queue = self.queue(queue_name)?;
+
+                let events = queue.list_events(status.as_ref(), *after_event_id, *limit);
+                Ok(CommandResp::ListEvents { events })
+            }
+            Command::ListQueues {} => {
+                let mut names: Vec<String> = self.queues.keys().cloned().collect();
+                names.sort();
+                Ok(CommandResp::ListQueues { names })
+            }
+            Command::DeleteQueue {
+                queue_name,
+                refuse_if_running,
+            } => {
+                self.delete_queue(queue_name, *refuse_if_running)?;
+                Ok(CommandResp::DeleteQueue {})
+            }
+            Command::PurgeQueue { queue_name, status } => {
+                let purged = self.purge_queue(queue_name, status.as_ref())?;
+                Ok(CommandResp::PurgeQueue { purged })
+            }
+            Command::PauseQueue { queue_name } => {
+                let queue = self.queue_mut(queue_name)?;
+
+                queue.set_paused(true);
+                Ok(CommandResp::PauseQueue {})
+            }
+            Command::ResumeQueue { queue_name } => {
+                let queue = self.queue_mut(queue_name)?;
+
+                queue.set_paused(false);
+                self.dispatch_events(queue_name)?;
+                Ok(CommandResp::ResumeQueue {})
+            }
+            Command::UpdateQueueConfig { queue_name, config } => {
+                let queue = self.queue_mut(queue_name)?;
+
+                queue.set_config(config.clone())?;
+                Ok(CommandResp::UpdateQueueConfig {})
+            }
+            Command::DescribeQueue { queue_name } => {
+                let now = self.now();
+                let queue = self.queue(queue_name)?;
+
+                Ok(CommandResp::DescribeQueue {
+                    description: Box::new(queue.describe(now)),
+                })
+            }
+        }
+    }
+}
+
+// records a command which was already applied to the state, used for
+// subscription deliveries made outside of apply_command
+fn log_command(wal: &mut Option<Wal>, command: Command) -> Result<()> {
+    if let Some(wal) = wal {
+        wal.append(proto::exchange::Command::try_from(command)?)?;
+    }
+    Ok(())
+}
+
+fn log_requeue(wal: &mut Option<Wal>, queue_name: &str, event_id: u64) -> Result<()> {
+    log_command(
+        wal,
+        Command::UpdateEventStatus {
+            queue_name: queue_name.to_string(),
+            event_id,
+            status: EventStatus::Queued,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange::dead_letter::DeadLetterConfig;
+    use crate::exchange::queue::QueueConfig;
+    use crate::exchange::schedule::MissedFirePolicy;
+    use crate::exchange::workflow::WorkflowStatus;
+
+    const QUEUE_NAME: &str = "test";
+
+    // a fresh directory per test so tests running in parallel do not
+    // share their logs
+    fn test_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("orkidea-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn durable_config(dir: &std::path::Path) -> ExchangeConfig {
+        ExchangeConfig {
+            wal: Some(WalConfig::new(dir.join("wal.log"))),
+            snapshot: Some(SnapshotConfig {
+                dir: dir.join("snapshots"),
+                interval: None,
+                // every check writes a snapshot
+                max_wal_bytes: Some(1),
+                retain: 2,
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn new_event(priority: u32) -> Event {
+        Event::try_from(proto::exchange::Event {
+            priority,
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    fn add_queue(exchange: &mut Exchange, name: &str, config: proto::exchange::QueueConfig) {
+        exchange
+            .apply_command(&Command::AddQueue {
+                name: name.to_string(),
+                config: QueueConfig::try_from(config).unwrap(),
+                dead_letter: Some(DeadLetterConfig::new(None)),
+                priority_aging: None,
+            })
+            .unwrap();
+    }
+
+    fn add_event(
+        exchange: &mut Exchange,
+        queue_name: &str,
+        dedup_key: Option<&str>,
+    ) -> Result<CommandResp> {
+        exchange.apply_command(&Command::AddEvent {
+            queue_name: queue_name.to_string(),
+            event: new_event(0),
+            delay: None,
+            dedup_key: dedup_key.map(|dedup_key| dedup_key.to_string()),
+        })
+    }
+
+    fn claim(exchange: &mut Exchange, max: u64) -> Vec<u64> {
+        match exchange.apply_command(&Command::ClaimEvents {
+            queue_name: QUEUE_NAME.to_string(),
+            max,
+            worker_id: "worker".to_string(),
+            selector: Selector::default(),
+        }) {
+            Ok(CommandResp::ClaimEvents { events }) => {
+                events.iter().map(|event| event.id()).collect()
+            }
+            resp => panic!("unexpected claim response: {:?}", resp),
+        }
+    }
+
+    fn update_status(
+        exchange: &mut Exchange,
+        queue_name: &str,
+        event_id: u64,
+        status: EventStatus,
+    ) {
+        exchange
+            .apply_command(&Command::UpdateEventStatus {
+                queue_name: queue_name.to_string(),
+                event_id,
+                status,
+            })
+            .unwrap();
+    }
+
+    fn status(exchange: &Exchange, queue_name: &str, event_id: u64) -> Option<EventStatus> {
+        exchange
+            .queue(&queue_name.to_string())
+            .unwrap()
+            .event(&event_id)
+            .map(|event| event.status().clone())
+    }
+
+    #[test]
+    fn recovers_state_from_the_snapshot_and_the_log() {
+        let dir = test_dir("recover");
+        let mut exchange = Exchange::new(durable_config(&dir));
+        exchange.recover().unwrap();
+
+        add_queue(&mut exchange, QUEUE_NAME, Default::default());
+        exchange
+            .apply_command(&Command::AddEvents {
+                queue_name: QUEUE_NAME.to_string(),
+                events: vec![new_event(0), new_event(0), new_event(0)],
+                delay: None,
+                dedup_key: None,
+            })
+            .unwrap();
+        assert_eq!(claim(&mut exchange, 1), vec![0]);
+        exchange
+            .apply_command(&Command::UpdateEventStatus {
+                queue_name: QUEUE_NAME.to_string(),
+                event_id: 0,
+                status: EventStatus::Errored,
+            })
+            .unwrap();
+        exchange.maybe_snapshot().unwrap();
+        assert!(
+            exchange
+                .last_snapshot_sequence
+                .is_some_and(|sequence| sequence > 0)
+        );
+
+        // recorded in the log after the snapshot
+        assert_eq!(claim(&mut exchange, 1), vec![1]);
+        exchange
+            .apply_command(&Command::DeliverEvent {
+                queue_name: QUEUE_NAME.to_string(),
+                event_id: 2,
+                worker_id: "subscriber".to_string(),
+            })
+            .unwrap();
+        add_event(&mut exchange, QUEUE_NAME, Some("key")).unwrap();
+        drop(exchange);
+
+        let mut exchange = Exchange::new(durable_config(&dir));
+        exchange.recover().unwrap();
+
+        // deliveries to subscribers of the previous run are queued again
+        assert_eq!(status(&exchange, QUEUE_NAME, 0), None);
+        assert_eq!(status(&exchange, QUEUE_NAME, 1), Some(EventStatus::Running));
+        assert_eq!(status(&exchange, QUEUE_NAME, 2), Some(EventStatus::Queued));
+        assert_eq!(status(&exchange, QUEUE_NAME, 3), Some(EventStatus::Queued));
+        assert!(exchange.queues[QUEUE_NAME].delivered_events().is_empty());
+
+        // dedup keys and id counters survive as well
+        assert!(matches!(
+            add_event(&mut exchange, QUEUE_NAME, Some("key")),
+            Ok(CommandResp::AddEvent { id: 3, .. })
+        ));
+        assert!(matches!(
+            add_event(&mut exchange, QUEUE_NAME, None),
+            Ok(CommandResp::AddEvent { id: 4, .. })
+        ));
+        assert_eq!(claim(&mut exchange, 10), vec![2, 3, 4]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn failed_batches_are_rolled_back() {
+        let mut exchange = Exchange::default();
+        add_queue(&mut exchange, QUEUE_NAME, Default::default());
+
+        let (results, applied) = exchange.execute_batch(&[
+            Command::AddEvent {
+                queue_name: QUEUE_NAME.to_string(),
+                event: new_event(0),
+                delay: None,
+                dedup_key: Some("key".to_string()),
+            },
+            Command::AddQueue {
+                name: "other".to_string(),
+                config: QueueConfig::default(),
+                dead_letter: None,
+                priority_aging: None,
+            },
+            Command::AddEvent {
+                queue_name: "missing".to_string(),
+                event: new_event(0),
+                delay: None,
+                dedup_key: None,
+            },
+            Command::AddEvent {
+                queue_name: QUEUE_NAME.to_string(),
+                event: new_event(0),
+                delay: None,
+                dedup_key: None,
+            },
+        ]);
+        assert!(!applied);
+        assert_eq!(results.len(), 3);
+        assert!(results[2].is_err());
+
+        assert!(!exchange.queues.contains_key("other"));
+        assert_eq!(status(&exchange, QUEUE_NAME, 0), None);
+        assert!(matches!(
+            add_event(&mut exchange, QUEUE_NAME, Some("key")),
+            Ok(CommandResp::AddEvent { id: 0, .. })
+        ));
+
+        let (results, applied) = exchange.execute_batch(&[
+            Command::AddEvent {
+                queue_name: QUEUE_NAME.to_string(),
+                event: new_event(0),
+                delay: None,
+                dedup_key: None,
+            },
+            Command::AddEvent {
+                queue_name: QUEUE_NAME.to_string(),
+                event: new_event(0),
+                delay: None,
+                dedup_key: None,
+            },
+        ]);
+        assert!(applied);
+        assert!(matches!(
+            results[1],
+            Ok(CommandResp::AddEvent { id: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn batches_with_internal_commands_are_refused() {
+        let mut exchange = Exchange::default();
+        add_queue(&mut exchange, QUEUE_NAME, Default::default());
+        add_event(&mut exchange, QUEUE_NAME, None).unwrap();
+
+        let command = proto::exchange::Command::try_from(Command::DeliverEvent {
+            queue_name: QUEUE_NAME.to_string(),
+            event_id: 0,
+            worker_id: "worker".to_string(),
+        })
+        .unwrap();
+        let resp = exchange.apply_batch(vec![command]);
+        assert!(!resp.applied);
+        assert_eq!(status(&exchange, QUEUE_NAME, 0), Some(EventStatus::Queued));
+    }
+
+    #[test]
+    fn drop_oldest_policy_expires_the_oldest_events() {
+        let mut exchange = Exchange::default();
+        add_queue(
+            &mut exchange,
+            QUEUE_NAME,
+            proto::exchange::QueueConfig {
+                max_queued_events: Some(2),
+                overflow_policy: proto::exchange::OverflowPolicy::DropOldest.into(),
+                ..Default::default()
+            },
+        );
+        for _ in 0..3 {
+            add_event(&mut exchange, QUEUE_NAME, None).unwrap();
+        }
+
+        assert_eq!(status(&exchange, QUEUE_NAME, 0), None);
+        let queue = exchange.queue(&QUEUE_NAME.to_string()).unwrap();
+        let dead_letter = queue.get_dead_letter(&0).unwrap();
+        assert_eq!(*dead_letter.event().status(), EventStatus::Expired);
+        assert_eq!(claim(&mut exchange, 10), vec![1, 2]);
+
+        // events which do not fit an empty queue are refused
+        let resp = exchange.apply_command(&Command::AddEvents {
+            queue_name: QUEUE_NAME.to_string(),
+            events: vec![new_event(0), new_event(0), new_event(0)],
+            delay: None,
+            dedup_key: None,
+        });
+        assert_eq!(
+            resp.map_err(|err| ErrorCode::from_error(&err)).err(),
+            Some(ErrorCode::QueueFull)
+        );
+    }
+
+    #[test]
+    fn dead_letter_policy_reports_dead_lettered_events() {
+        let mut exchange = Exchange::default();
+        add_queue(
+            &mut exchange,
+            QUEUE_NAME,
+            proto::exchange::QueueConfig {
+                max_queued_events: Some(1),
+                overflow_policy: proto::exchange::OverflowPolicy::DeadLetter.into(),
+                ..Default::default()
+            },
+        );
+
+        assert!(matches!(
+            add_event(&mut exchange, QUEUE_NAME, None),
+            Ok(CommandResp::AddEvent {
+                id: 0,
+                dead_lettered: false
+            })
+        ));
+        assert!(matches!(
+            add_event(&mut exchange, QUEUE_NAME, None),
+            Ok(CommandResp::AddEvent {
+                id: 1,
+                dead_lettered: true
+            })
+        ));
+    }
+
+    #[test]
+    fn publish_adds_to_every_queue_or_to_none() {
+        let mut exchange = Exchange::default();
+        add_queue(
+            &mut exchange,
+            "full",
+            proto::exchange::QueueConfig {
+                max_queued_events: Some(1),
+                ..Default::default()
+            },
+        );
+        add_queue(&mut exchange, QUEUE_NAME, Default::default());
+        for queue_name in ["full", QUEUE_NAME] {
+            exchange
+                .apply_command(&Command::AddBinding {
+                    topic_pattern: "orders.#".to_string(),
+                    queue_name: queue_name.to_string(),
+                    selector: Selector::default(),
+                })
+                .unwrap();
+        }
+        add_event(&mut exchange, "full", None).unwrap();
+
+        let resp = exchange.apply_command(&Command::Publish {
+            topic: "orders.created".to_string(),
+            event: new_event(0),
+        });
+        assert!(resp.is_err());
+        let queue = exchange.queue(&QUEUE_NAME.to_string()).unwrap();
+        assert!(queue.list_events(None, None, 0).is_empty());
+    }
+
+    fn subscribe(
+        exchange: &mut Exchange,
+        prefetch: u64,
+    ) -> Result<mpsc::Receiver<proto::exchange::Event>> {
+        let (events, receiver) = mpsc::channel(prefetch.max(1) as usize);
+        exchange.subscribe(
+            QUEUE_NAME.to_string(),
+            prefetch,
+            "worker".to_string(),
+            Selector::default(),
+            events,
+        )?;
+        Ok(receiver)
+    }
+
+    fn received_ids(receiver: &mut mpsc::Receiver<proto::exchange::Event>) -> Vec<u64> {
+        let mut event_ids: Vec<u64> = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            event_ids.push(event.id);
+        }
+        event_ids
+    }
+
+    #[test]
+    fn subscribers_receive_events_up_to_their_prefetch() {
+        let mut exchange = Exchange::default();
+        add_queue(&mut exchange, QUEUE_NAME, Default::default());
+        for _ in 0..3 {
+            add_event(&mut exchange, QUEUE_NAME, None).unwrap();
+        }
+
+        let mut receiver = subscribe(&mut exchange, 2).unwrap();
+        assert_eq!(received_ids(&mut receiver), vec![0, 1]);
+        // finishing an event makes room for the next one
+        update_status(&mut exchange, QUEUE_NAME, 0, EventStatus::Complete);
+        assert_eq!(received_ids(&mut receiver), vec![2]);
+
+        let max_prefetch = exchange.config.max_prefetch;
+        assert!(subscribe(&mut exchange, max_prefetch + 1).is_err());
+    }
+
+    #[test]
+    fn events_of_disconnected_subscribers_are_redelivered() {
+        let mut exchange = Exchange::default();
+        add_queue(&mut exchange, QUEUE_NAME, Default::default());
+        add_event(&mut exchange, QUEUE_NAME, None).unwrap();
+
+        let mut receiver = subscribe(&mut exchange, 1).unwrap();
+        assert_eq!(received_ids(&mut receiver), vec![0]);
+        drop(receiver);
+
+        // the next dispatch notices the closed stream and requeues the event
+        let mut receiver = subscribe(&mut exchange, 1).unwrap();
+        assert_eq!(received_ids(&mut receiver), vec![0]);
+        assert_eq!(exchange.subscribers[QUEUE_NAME].len(), 1);
+    }
+
+    fn heartbeat(exchange: &mut Exchange, event_id: u64) -> Result<CommandResp> {
+        exchange.apply_command(&Command::Heartbeat {
+            queue_name: QUEUE_NAME.to_string(),
+            event_id,
+        })
+    }
+
+    #[test]
+    fn events_which_stop_heartbeating_are_moved_to_missed_heartbeat() {
+        let mut exchange = Exchange::default();
+        add_queue(&mut exchange, QUEUE_NAME, Default::default());
+        add_event(&mut exchange, QUEUE_NAME, None).unwrap();
+        add_event(&mut exchange, QUEUE_NAME, None).unwrap();
+        assert!(heartbeat(&mut exchange, 0).is_err());
+        assert_eq!(claim(&mut exchange, 2), vec![0, 1]);
+        heartbeat(&mut exchange, 0).unwrap();
+
+        let interval = chrono::Duration::from_std(exchange.config.heartbeat_interval).unwrap();
+        let now = chrono::Utc::now();
+        exchange.sweep(now).unwrap();
+        heartbeat(&mut exchange, 0).unwrap();
+
+        exchange
+            .sweep(now + interval + chrono::Duration::seconds(1))
+            .unwrap();
+        // the event left the queue, the one which never heartbeated is
+        // still running
+        assert!(heartbeat(&mut exchange, 0).is_err());
+        heartbeat(&mut exchange, 1).unwrap();
+    }
+
+    fn add_delayed_event(
+        exchange: &mut Exchange,
+        not_before: Option<chrono::DateTime<chrono::Utc>>,
+        delay: Option<chrono::Duration>,
+    ) {
+        let mut event = new_event(0);
+        event.set_not_before(not_before);
+        exchange
+            .apply_command(&Command::AddEvent {
+                queue_name: QUEUE_NAME.to_string(),
+                event,
+                delay,
+                dedup_key: None,
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn delayed_events_are_claimable_once_due() {
+        let mut exchange = Exchange::default();
+        add_queue(&mut exchange, QUEUE_NAME, Default::default());
+        let now = chrono::Utc::now();
+        let seconds = chrono::Duration::seconds;
+        add_delayed_event(&mut exchange, None, Some(seconds(10)));
+        add_delayed_event(&mut exchange, Some(now + seconds(20)), None);
+        // the delay takes precedence over the event's not before
+        add_delayed_event(&mut exchange, Some(now + seconds(20)), Some(seconds(5)));
+        add_delayed_event(&mut exchange, None, None);
+
+        assert_eq!(claim(&mut exchange, 10), vec![3]);
+        assert_eq!(status(&exchange, QUEUE_NAME, 0), Some(EventStatus::Queued));
+        for (elapsed, event_ids) in [(6, vec![2]), (11, vec![0]), (21, vec![1])] {
+            exchange.replay_time = Some(now + seconds(elapsed));
+            assert_eq!(claim(&mut exchange, 10), event_ids);
+        }
+    }
+
+    #[test]
+    fn delayed_events_are_pushed_to_subscribers_once_due() {
+        let mut exchange = Exchange::default();
+        add_queue(&mut exchange, QUEUE_NAME, Default::default());
+        let mut receiver = subscribe(&mut exchange, 1).unwrap();
+        add_delayed_event(&mut exchange, None, Some(chrono::Duration::seconds(10)));
+        assert!(received_ids(&mut receiver).is_empty());
+
+        let wakeup = exchange.next_delayed_wakeup().unwrap();
+        assert!(wakeup <= std::time::Duration::from_secs(10));
+        exchange
+            .dispatch_delayed_events(chrono::Utc::now() + chrono::Duration::seconds(11))
+            .unwrap();
+        assert_eq!(received_ids(&mut receiver), vec![0]);
+        assert!(exchange.next_delayed_wakeup().is_none());
+    }
+
+    #[test]
+    fn finished_workflows_are_removed_after_the_retention() {
+        let mut exchange = Exchange::default();
+        add_queue(&mut exchange, QUEUE_NAME, Default::default());
+        let node =
+            |name: &str| WorkflowNode::new(name.to_string(), QUEUE_NAME.to_string(), new_event(0));
+        let workflow_id = match exchange.apply_command(&Command::SubmitWorkflow {
+            name: "workflow".to_string(),
+            nodes: vec![node("a"), node("b")],
+            edges: vec![WorkflowEdge::new(
+                "a".to_string(),
+                "b".to_string(),
+                EventStatus::Complete,
+            )],
+        }) {
+            Ok(CommandResp::SubmitWorkflow { id }) => id,
+            resp => panic!("unexpected submit response: {:?}", resp),
+        };
+
+        assert_eq!(claim(&mut exchange, 10), vec![0]);
+        update_status(&mut exchange, QUEUE_NAME, 0, EventStatus::Errored);
+        let workflow = &exchange.workflows[&workflow_id];
+        assert_eq!(*workflow.status(), WorkflowStatus::Failed);
+
+        let finished_at = workflow.finished_at().unwrap();
+        exchange.sweep(finished_at).unwrap();
+        assert!(exchange.workflows.contains_key(&workflow_id));
+        exchange
+            .sweep(finished_at + chrono::Duration::hours(2))
+            .unwrap();
+        assert!(exchange.workflows.is_empty());
+    }
+
+    fn add_join_trigger(exchange: &mut Exchange, condition: JoinCondition) -> u64 {
+        let resp = exchange.apply_command(&Command::AddJoinTrigger {
+            events: (0..3)
+                .map(|event_id| (QUEUE_NAME.to_string(), event_id))
+                .collect(),
+            condition,
+            commands: vec![Command::AddEvent {
+                queue_name: "joined".to_string(),
+                event: new_event(0),
+                delay: None,
+                dedup_key: None,
+            }],
+        });
+        match resp {
+            Ok(CommandResp::AddJoinTrigger { id }) => id,
+            resp => panic!("unexpected add join trigger response: {:?}", resp),
+        }
+    }
+
+    fn joined_events(exchange: &Exchange) -> usize {
+        exchange
+            .queue(&"joined".to_string())
+            .unwrap()
+            .list_events(None, None, 10)
+            .len()
+    }
+
+    #[test]
+    fn quorum_joins_run_their_commands_once_enough_events_complete() {
+        let mut exchange = Exchange::default();
+        add_queue(&mut exchange, QUEUE_NAME, Default::default());
+        add_queue(&mut exchange, "joined", Default::default());
+        for _ in 0..3 {
+            add_event(&mut exchange, QUEUE_NAME, None).unwrap();
+        }
+        let id = add_join_trigger(&mut exchange, JoinCondition::Quorum(2));
+        assert_eq!(claim(&mut exchange, 3), vec![0, 1, 2]);
+
+        update_status(&mut exchange, QUEUE_NAME, 0, EventStatus::Errored);
+        update_status(&mut exchange, QUEUE_NAME, 1, EventStatus::Complete);
+        assert_eq!(joined_events(&exchange), 0);
+        update_status(&mut exchange, QUEUE_NAME, 2, EventStatus::Complete);
+        assert_eq!(joined_events(&exchange), 1);
+        assert!(!exchange.join_triggers.contains_key(&id));
+    }
+
+    #[test]
+    fn quorum_joins_out_of_reach_are_dropped_without_running() {
+        let mut exchange = Exchange::default();
+        add_queue(&mut exchange, QUEUE_NAME, Default::default());
+        add_queue(&mut exchange, "joined", Default::default());
+        for _ in 0..3 {
+            add_event(&mut exchange, QUEUE_NAME, None).unwrap();
+        }
+        let id = add_join_trigger(&mut exchange, JoinCondition::Quorum(2));
+        assert_eq!(claim(&mut exchange, 3), vec![0, 1, 2]);
+
+        update_status(&mut exchange, QUEUE_NAME, 0, EventStatus::Errored);
+        assert!(exchange.join_triggers.contains_key(&id));
+        update_status(&mut exchange, QUEUE_NAME, 1, EventStatus::Timedout);
+        assert!(!exchange.join_triggers.contains_key(&id));
+        update_status(&mut exchange, QUEUE_NAME, 2, EventStatus::Complete);
+        assert_eq!(joined_events(&exchange), 0);
+    }
+
+    fn add_command_trigger(exchange: &mut Exchange, event_id: u64) -> u64 {
+        let resp = exchange.apply_command(&Command::AddCommandTrigger {
+            queue_name: QUEUE_NAME.to_string(),
+            event_id,
+            trigger: Trigger::OnEventComplete(event_id),
+            command: Box::new(Command::AddEvent {
+                queue_name: "joined".to_string(),
+                event: new_event(0),
+                delay: None,
+                dedup_key: None,
+            }),
+        });
+        match resp {
+            Ok(CommandResp::AddCommandTrigger { transaction_id }) => transaction_id,
+            resp => panic!("unexpected add command trigger response: {:?}", resp),
+        }
+    }
+
+    #[test]
+    fn transactions_can_be_looked_up_listed_and_aborted() {
+        let mut exchange = Exchange::default();
+        add_queue(&mut exchange, QUEUE_NAME, Default::default());
+        add_queue(&mut exchange, "joined", Default::default());
+        for _ in 0..2 {
+            add_event(&mut exchange, QUEUE_NAME, None).unwrap();
+        }
+        let first = add_command_trigger(&mut exchange, 0);
+        let second = add_command_trigger(&mut exchange, 1);
+        assert_ne!(first, second);
+        // a second trigger on the event joins its transaction
+        assert_eq!(add_command_trigger(&mut exchange, 1), second);
+
+        let resp = exchange.apply_command(&Command::GetTransaction {
+            queue_name: QUEUE_NAME.to_string(),
+            transaction_id: second,
+        });
+        assert!(matches!(
+            resp,
+            Ok(CommandResp::GetTransaction { transaction })
+                if transaction.event_id() == 1
+                    && transaction.get_command_triggers()[0].get_commands().len() == 2
+        ));
+        let resp = exchange.apply_command(&Command::ListTransactions {
+            queue_name: QUEUE_NAME.to_string(),
+        });
+        assert!(matches!(
+            resp,
+            Ok(CommandResp::ListTransactions { transactions })
+                if transactions.iter().map(|transaction| transaction.id()).eq([first, second])
+        ));
+
+        exchange
+            .apply_command(&Command::AbortTransaction {
+                queue_name: QUEUE_NAME.to_string(),
+                transaction_id: first,
+            })
+            .unwrap();
+        assert!(
+            exchange
+                .apply_command(&Command::AbortTransaction {
+                    queue_name: QUEUE_NAME.to_string(),
+                    transaction_id: first,
+                })
+                .is_err()
+        );
+        // only the triggers of the transaction which was not aborted run
+        assert_eq!(claim(&mut exchange, 2), vec![0, 1]);
+        update_status(&mut exchange, QUEUE_NAME, 0, EventStatus::Complete);
+        assert_eq!(joined_events(&exchange), 0);
+        update_status(&mut exchange, QUEUE_NAME, 1, EventStatus::Complete);
+        assert_eq!(joined_events(&exchange), 2);
+    }
+
+    // an event the queue refuses to add, only queued events can be added
+    fn refused_event() -> Event {
+        let mut event = new_event(0);
+        event.set_status(EventStatus::Running);
+        event
+    }
+
+    fn add_events(exchange: &mut Exchange, events: Vec<Event>) -> Result<CommandResp> {
+        exchange.apply_command(&Command::AddEvents {
+            queue_name: QUEUE_NAME.to_string(),
+            events,
+            delay: None,
+            dedup_key: None,
+        })
+    }
+
+    #[test]
+    fn added_events_report_their_ids_in_order() {
+        let mut exchange = Exchange::default();
+        add_queue(&mut exchange, QUEUE_NAME, Default::default());
+        let resp = add_events(&mut exchange, vec![new_event(0), new_event(1)]).unwrap();
+        let proto_resp = proto::exchange::CommandResp::try_from(resp).unwrap();
+        assert!(matches!(
+            proto_resp.command_resp,
+            Some(proto::exchange::command_resp::CommandResp::AddEventsResp(resp))
+                if resp.ids == vec![0, 1]
+        ));
+
+        // one refused event refuses every event of the command
+        assert!(add_events(&mut exchange, vec![new_event(0), refused_event()]).is_err());
+        assert!(matches!(
+            add_events(&mut exchange, vec![new_event(0)]),
+            Ok(CommandResp::AddEvents { ids, .. }) if ids == vec![2]
+        ));
+        assert_eq!(claim(&mut exchange, 10), vec![1, 0, 2]);
+    }
+
+    #[test]
+    fn workflow_nodes_refused_by_their_queue_fail() {
+        let mut exchange = Exchange::default();
+        add_queue(&mut exchange, QUEUE_NAME, Default::default());
+
+        let node = |name: &str, event_template: Event| {
+            WorkflowNode::new(name.to_string(), QUEUE_NAME.to_string(), event_template)
+        };
+        let edge = |from: &str, to: &str| {
+            WorkflowEdge::new(from.to_string(), to.to_string(), EventStatus::Complete)
+        };
+        let workflow_id = match exchange.apply_command(&Command::SubmitWorkflow {
+            name: "workflow".to_string(),
+            nodes: vec![
+                node("a", new_event(0)),
+                node("b", refused_event()),
+                node("c", new_event(0)),
+                node("d", new_event(0)),
+            ],
+            edges: vec![edge("a", "b"), edge("a", "c"), edge("b", "d")],
+        }) {
+            Ok(CommandResp::SubmitWorkflow { id }) => id,
+            resp => panic!("unexpected submit response: {:?}", resp),
+        };
+
+        assert_eq!(claim(&mut exchange, 10), vec![0]);
+        update_status(&mut exchange, QUEUE_NAME, 0, EventStatus::Complete);
+
+        // b is refused by its queue, c still starts and d is skipped
+        assert_eq!(claim(&mut exchange, 10), vec![1]);
+        let workflow = &exchange.workflows[&workflow_id];
+        assert_eq!(*workflow.status(), WorkflowStatus::Running);
+        update_status(&mut exchange, QUEUE_NAME, 1, EventStatus::Complete);
+
+        let workflow = &exchange.workflows[&workflow_id];
+        assert_eq!(*workflow.status(), WorkflowStatus::Failed);
+    }
+
+    #[test]
+    fn workflows_with_root_nodes_refused_by_their_queue_are_refused() {
+        let mut exchange = Exchange::default();
+        add_queue(&mut exchange, QUEUE_NAME, Default::default());
+        let resp = exchange.apply_command(&Command::SubmitWorkflow {
+            name: "workflow".to_string(),
+            nodes: vec![
+                WorkflowNode::new("a".to_string(), QUEUE_NAME.to_string(), new_event(0)),
+                WorkflowNode::new("b".to_string(), QUEUE_NAME.to_string(), refused_event()),
+            ],
+            edges: Vec::new(),
+        });
+        assert!(resp.is_err());
+        assert!(exchange.workflows.is_empty());
+        assert_eq!(claim(&mut exchange, 10), Vec::<u64>::new());
+    }
+
+    fn add_schedule(exchange: &mut Exchange, event_template: Event) -> Result<CommandResp> {
+        exchange.apply_command(&Command::AddSchedule {
+            queue_name: QUEUE_NAME.to_string(),
+            cron_expr: "* * * * * *".to_string(),
+            timezone: String::new(),
+            event_template,
+            missed_fire_policy: MissedFirePolicy::CatchUp,
+        })
+    }
+
+    #[test]
+    fn schedules_with_templates_refused_by_the_queue_are_refused() {
+        let mut exchange = Exchange::default();
+        // no room for the scheduled at attribute added on every fire
+        add_queue(
+            &mut exchange,
+            QUEUE_NAME,
+            proto::exchange::QueueConfig {
+                max_attributes: Some(1),
+                ..Default::default()
+            },
+        );
+        let mut event_template = new_event(0);
+        event_template.add_attribute(Attribute::new("key".to_string(), "value".to_string()));
+        assert!(add_schedule(&mut exchange, event_template).is_err());
+        assert!(exchange.schedules.is_empty());
+    }
+
+    #[test]
+    fn fire_times_refused_by_the_queue_are_dropped() {
+        let mut exchange = Exchange::default();
+        add_queue(
+            &mut exchange,
+            QUEUE_NAME,
+            proto::exchange::QueueConfig {
+                max_queued_events: Some(1),
+                ..Default::default()
+            },
+        );
+        add_event(&mut exchange, QUEUE_NAME, None).unwrap();
+        let id = match add_schedule(&mut exchange, new_event(0)) {
+            Ok(CommandResp::AddSchedule { id, .. }) => id,
+            resp => panic!("unexpected add schedule response: {:?}", resp),
+        };
+
+        let now = chrono::Utc::now() + chrono::Duration::seconds(10);
+        exchange.replay_time = Some(now);
+        let resp = exchange.apply_command(&Command::FireSchedule { id });
+        assert!(matches!(
+            resp,
+            Ok(CommandResp::FireSchedule { event_ids }) if event_ids.is_empty()
+        ));
+        // the schedule moved past the refused fire times
+        assert!(!exchange.schedules[&id].is_due(now));
+        assert_eq!(claim(&mut exchange, 10), vec![0]);
+    }
+
+    #[test]
+    fn replays_make_room_under_the_drop_oldest_policy() {
+        let mut exchange = Exchange::default();
+        add_queue(
+            &mut exchange,
+            QUEUE_NAME,
+            proto::exchange::QueueConfig {
+                max_queued_events: Some(2),
+                overflow_policy: proto::exchange::OverflowPolicy::DropOldest.into(),
+                ..Default::default()
+            },
+        );
+        add_event(&mut exchange, QUEUE_NAME, None).unwrap();
+        assert_eq!(claim(&mut exchange, 1), vec![0]);
+        update_status(&mut exchange, QUEUE_NAME, 0, EventStatus::Errored);
+        add_event(&mut exchange, QUEUE_NAME, None).unwrap();
+        add_event(&mut exchange, QUEUE_NAME, None).unwrap();
+
+        let resp = exchange.apply_command(&Command::ReplayDeadLetters {
+            queue_name: QUEUE_NAME.to_string(),
+            event_ids: Vec::new(),
+        });
+        assert!(matches!(
+            resp,
+            Ok(CommandResp::ReplayDeadLetters { event_ids }) if event_ids == vec![0]
+        ));
+        // the oldest queued event made room and is now the dead letter
+        assert_eq!(status(&exchange, QUEUE_NAME, 1), None);
+        let queue = exchange.queue(&QUEUE_NAME.to_string()).unwrap();
+        assert_eq!(queue.dead_letter_ids(&[]).unwrap(), vec![1]);
+        assert_eq!(claim(&mut exchange, 10), vec![0, 2]);
+    }
+
+    #[test]
+    fn queries_describe_the_state_without_being_logged() {
+        let dir = test_dir("queries");
+        let mut exchange = Exchange::new(ExchangeConfig {
+            wal: Some(WalConfig::new(dir.join("wal.log"))),
+            ..Default::default()
+        });
+        exchange.recover().unwrap();
+        add_queue(&mut exchange, QUEUE_NAME, Default::default());
+        add_queue(&mut exchange, "joined", Default::default());
+        for _ in 0..3 {
+            add_event(&mut exchange, QUEUE_NAME, None).unwrap();
+        }
+        assert_eq!(claim(&mut exchange, 1), vec![0]);
+        let next_sequence = exchange.wal.as_ref().unwrap().next_sequence();
+
+        assert!(matches!(
+            exchange.apply_command(&Command::ListQueues {}),
+            Ok(CommandResp::ListQueues { names }) if names == vec!["joined", QUEUE_NAME]
+        ));
+        assert!(matches!(
+            exchange.apply_command(&Command::GetEvent {
+                queue_name: QUEUE_NAME.to_string(),
+                event_id: 0,
+            }),
+            Ok(CommandResp::GetEvent { event }) if *event.status() == EventStatus::Running
+        ));
+        let resp = exchange.apply_command(&Command::ListEvents {
+            queue_name: QUEUE_NAME.to_string(),
+            status: Some(EventStatus::Queued),
+            after_event_id: Some(1),
+            limit: 10,
+        });
+        assert!(matches!(
+            resp,
+            Ok(CommandResp::ListEvents { events })
+                if events.iter().map(|event| event.id()).eq([2])
+        ));
+        let resp = exchange
+            .apply_command(&Command::DescribeQueue {
+                queue_name: QUEUE_NAME.to_string(),
+            })
+            .unwrap();
+        let proto_resp = proto::exchange::CommandResp::try_from(resp).unwrap();
+        assert!(matches!(
+            proto_resp.command_resp,
+            Some(proto::exchange::command_resp::CommandResp::DescribeQueueResp(
+                proto::exchange::DescribeQueueResp {
+                    description: Some(description),
+                }
+            )) if description.queued_events == 2 && description.running_events == 1
+        ));
+        assert!(
+            exchange
+                .apply_command(&Command::GetEvent {
+                    queue_name: QUEUE_NAME.to_string(),
+                    event_id: 3,
+                })
+                .is_err()
+        );
+        assert_eq!(
+            exchange.wal.as_ref().unwrap().next_sequence(),
+            next_sequence
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn queues_with_events_workflows_wait_on_cannot_be_deleted() {
+        let mut exchange = Exchange::default();
+        add_queue(&mut exchange, QUEUE_NAME, Default::default());
+        exchange
+            .apply_command(&Command::SubmitWorkflow {
+                name: "workflow".to_string(),
+                nodes: vec![WorkflowNode::new(
+                    "a".to_string(),
+                    QUEUE_NAME.to_string(),
+                    new_event(0),
+                )],
+                edges: Vec::new(),
+            })
+            .unwrap();
+
+        let delete = Command::DeleteQueue {
+            queue_name: QUEUE_NAME.to_string(),
+            refuse_if_running: false,
+        };
+        let err = exchange.apply_command(&delete).unwrap_err();
+        assert_eq!(
+            ErrorCode::from_error(&err),
+            ErrorCode::QueueHasDependentEvents
+        );
+        assert!(exchange.queues.contains_key(QUEUE_NAME));
+
+        update_status(&mut exchange, QUEUE_NAME, 0, EventStatus::Complete);
+        exchange.apply_command(&delete).unwrap();
+        assert!(exchange.queues.is_empty());
+    }
+
+    fn delete_queue(exchange: &mut Exchange, refuse_if_running: bool) -> Result<CommandResp> {
+        exchange.apply_command(&Command::DeleteQueue {
+            queue_name: QUEUE_NAME.to_string(),
+            refuse_if_running,
+        })
+    }
+
+    #[test]
+    fn queues_with_running_events_are_only_deleted_when_asked_to() {
+        let mut exchange = Exchange::default();
+        add_queue(&mut exchange, QUEUE_NAME, Default::default());
+        add_event(&mut exchange, QUEUE_NAME, None).unwrap();
+        add_schedule(&mut exchange, new_event(0)).unwrap();
+        exchange
+            .apply_command(&Command::AddBinding {
+                topic_pattern: "a.#".to_string(),
+                queue_name: QUEUE_NAME.to_string(),
+                selector: Selector::default(),
+            })
+            .unwrap();
+        assert_eq!(claim(&mut exchange, 1), vec![0]);
+
+        let err = delete_queue(&mut exchange, true).unwrap_err();
+        assert_eq!(
+            ErrorCode::from_error(&err),
+            ErrorCode::QueueHasRunningEvents
+        );
+        assert!(exchange.queue(&QUEUE_NAME.to_string()).is_ok());
+
+        delete_queue(&mut exchange, false).unwrap();
+        assert!(exchange.queues.is_empty());
+        assert!(exchange.schedules.is_empty());
+        assert!(exchange.bindings.is_empty());
+        assert!(delete_queue(&mut exchange, false).is_err());
+    }
+
+    #[test]
+    fn paused_queues_accept_events_but_hand_none_out() {
+        let mut exchange = Exchange::default();
+        add_queue(&mut exchange, QUEUE_NAME, Default::default());
+        let mut receiver = subscribe(&mut exchange, 1).unwrap();
+        exchange
+            .apply_command(&Command::PauseQueue {
+                queue_name: QUEUE_NAME.to_string(),
+            })
+            .unwrap();
+        add_event(&mut exchange, QUEUE_NAME, None).unwrap();
+        add_event(&mut exchange, QUEUE_NAME, None).unwrap();
+        assert!(received_ids(&mut receiver).is_empty());
+        assert!(claim(&mut exchange, 10).is_empty());
+
+        exchange
+            .apply_command(&Command::ResumeQueue {
+                queue_name: QUEUE_NAME.to_string(),
+            })
+            .unwrap();
+        assert_eq!(received_ids(&mut receiver), vec![0]);
+        assert_eq!(claim(&mut exchange, 10), vec![1]);
+    }
+
+    #[test]
+    fn purges_only_remove_events_with_the_status() {
+        let mut exchange = Exchange::default();
+        add_queue(&mut exchange, QUEUE_NAME, Default::default());
+        for _ in 0..3 {
+            add_event(&mut exchange, QUEUE_NAME, None).unwrap();
+        }
+        assert_eq!(claim(&mut exchange, 1), vec![0]);
+
+        let resp = exchange.apply_command(&Command::PurgeQueue {
+            queue_name: QUEUE_NAME.to_string(),
+            status: Some(EventStatus::Queued),
+        });
+        assert!(matches!(resp, Ok(CommandResp::PurgeQueue { purged: 2 })));
+        assert_eq!(status(&exchange, QUEUE_NAME, 0), Some(EventStatus::Running));
+        assert_eq!(status(&exchange, QUEUE_NAME, 1), None);
+    }
+
+    fn add_expiring_event(exchange: &mut Exchange, ttl_seconds: i64, delay: Option<i64>) {
+        let mut event = new_event(0);
+        event.set_ttl(Some(chrono::Duration::seconds(ttl_seconds)));
+        exchange
+            .apply_command(&Command::AddEvent {
+                queue_name: QUEUE_NAME.to_string(),
+                event,
+                delay: delay.map(chrono::Duration::seconds),
+                dedup_key: None,
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn queued_events_past_their_ttl_expire() {
+        let mut exchange = Exchange::default();
+        add_queue(&mut exchange, QUEUE_NAME, Default::default());
+        let now = chrono::Utc::now();
+        add_expiring_event(&mut exchange, 5, None);
+        assert_eq!(claim(&mut exchange, 1), vec![0]);
+        add_expiring_event(&mut exchange, 5, None);
+        // the ttl runs from when the event is added, delays included
+        add_expiring_event(&mut exchange, 5, Some(10));
+        add_expiring_event(&mut exchange, 60, None);
+
+        exchange.sweep(now + chrono::Duration::seconds(6)).unwrap();
+        // running events finish their attempt
+        assert_eq!(status(&exchange, QUEUE_NAME, 0), Some(EventStatus::Running));
+        let queue = exchange.queue(&QUEUE_NAME.to_string()).unwrap();
+        for event_id in [1, 2] {
+            assert_eq!(
+                queue.get_dead_letter(&event_id).unwrap().event().status(),
+                &EventStatus::Expired
+            );
         }
+        assert_eq!(claim(&mut exchange, 10), vec![3]);
     }
 }
@@ -0,0 +1,254 @@
+use anyhow::Result;
+use std::collections;
+use thiserror::Error;
+
+use crate::rpc::proto;
+
+use super::event::EventStatus;
+use super::transaction::Command;
+
+#[derive(Debug, Error)]
+pub enum JoinError {
+    #[error("join trigger has no events")]
+    NoEvents,
+    #[error("quorum of {count} is out of range for {events} events")]
+    QuorumOutOfRange { count: u64, events: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JoinCondition {
+    AllComplete,
+    // any event ended in a failure status
+    AnyError,
+    // at least the given number of events completed
+    Quorum(u64),
+}
+
+// commands run once the final statuses of a set of events, possibly in
+// different queues, satisfy the condition
+#[derive(Debug, Clone)]
+pub struct JoinTrigger {
+    id: u64,
+    // final status of each event, indexed by queue name and event id
+    events: collections::BTreeMap<(String, u64), Option<EventStatus>>,
+    condition: JoinCondition,
+    commands: Vec<Command>,
+}
+
+impl JoinTrigger {
+    pub fn new(
+        id: u64,
+        events: Vec<(String, u64)>,
+        condition: JoinCondition,
+        commands: Vec<Command>,
+    ) -> Result<JoinTrigger> {
+        let events: collections::BTreeMap<(String, u64), Option<EventStatus>> =
+            events.into_iter().map(|event| (event, None)).collect();
+        if events.is_empty() {
+            return Err(JoinError::NoEvents.into());
+        }
+        if let JoinCondition::Quorum(count) = condition
+            && (count == 0 || count > events.len() as u64)
+        {
+            return Err(JoinError::QuorumOutOfRange {
+                count,
+                events: events.len() as u64,
+            }
+            .into());
+        }
+        Ok(JoinTrigger {
+            id,
+            events,
+            condition,
+            commands,
+        })
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn commands(&self) -> &Vec<Command> {
+        &self.commands
+    }
+
+    pub fn waits_on_queue(&self, queue_name: &str) -> bool {
+        self.events
+            .iter()
+            .any(|((name, _), outcome)| name == queue_name && outcome.is_none())
+    }
+
+    pub fn waits_on_event(&self, queue_name: &str, event_id: u64) -> bool {
+        self.events
+            .get(&(queue_name.to_string(), event_id))
+            .is_some_and(|outcome| outcome.is_none())
+    }
+
+    // records the final status of the event, returns false if the join
+    // trigger does not wait on it
+    pub fn record(&mut self, queue_name: &str, event_id: u64, status: &EventStatus) -> bool {
+        match self.events.get_mut(&(queue_name.to_string(), event_id)) {
+            Some(outcome) if outcome.is_none() => {
+                *outcome = Some(status.clone());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // None while undecided, otherwise whether the commands should run
+    pub fn resolution(&self) -> Option<bool> {
+        let total = self.events.len() as u64;
+        let mut complete: u64 = 0;
+        let mut failed: u64 = 0;
+        for outcome in self.events.values().flatten() {
+            if *outcome == EventStatus::Complete {
+                complete += 1;
+            } else {
+                failed += 1;
+            }
+        }
+        let pending = total - complete - failed;
+
+        match self.condition {
+            JoinCondition::AllComplete if failed > 0 => Some(false),
+            JoinCondition::AllComplete if complete == total => Some(true),
+            JoinCondition::AnyError if failed > 0 => Some(true),
+            JoinCondition::AnyError if pending == 0 => Some(false),
+            JoinCondition::Quorum(count) if complete >= count => Some(true),
+            JoinCondition::Quorum(count) if complete + pending < count => Some(false),
+            _ => None,
+        }
+    }
+}
+
+impl TryFrom<proto::exchange::JoinCondition> for JoinCondition {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: proto::exchange::JoinCondition) -> Result<JoinCondition, Self::Error> {
+        match obj.condition_type {
+            Some(proto::exchange::join_condition::ConditionType::OnAllComplete(_)) => {
+                Ok(JoinCondition::AllComplete)
+            }
+            Some(proto::exchange::join_condition::ConditionType::OnAnyError(_)) => {
+                Ok(JoinCondition::AnyError)
+            }
+            Some(proto::exchange::join_condition::ConditionType::OnQuorum(obj)) => {
+                Ok(JoinCondition::Quorum(obj.count))
+            }
+            None => Err(anyhow::anyhow!("received None")),
+        }
+    }
+}
+
+impl TryFrom<JoinCondition> for proto::exchange::JoinCondition {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: JoinCondition) -> Result<proto::exchange::JoinCondition, Self::Error> {
+        let condition_type = match obj {
+            JoinCondition::AllComplete => {
+                proto::exchange::join_condition::ConditionType::OnAllComplete(
+                    proto::exchange::OnAllComplete {},
+                )
+            }
+            JoinCondition::AnyError => proto::exchange::join_condition::ConditionType::OnAnyError(
+                proto::exchange::OnAnyError {},
+            ),
+            JoinCondition::Quorum(count) => {
+                proto::exchange::join_condition::ConditionType::OnQuorum(
+                    proto::exchange::OnQuorum { count },
+                )
+            }
+        };
+        Ok(proto::exchange::JoinCondition {
+            condition_type: Some(condition_type),
+        })
+    }
+}
+
+impl TryFrom<JoinTrigger> for proto::exchange::JoinTrigger {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: JoinTrigger) -> Result<proto::exchange::JoinTrigger, Self::Error> {
+        let mut events: Vec<proto::exchange::JoinEvent> = Vec::new();
+        for ((queue_name, event_id), outcome) in obj.events {
+            events.push(proto::exchange::JoinEvent {
+                queue_name,
+                event_id,
+                outcome: match outcome {
+                    Some(outcome) => Some(proto::exchange::Status::try_from(outcome)?),
+                    None => None,
+                },
+            });
+        }
+        let mut commands: Vec<proto::exchange::Command> = Vec::new();
+        for command in obj.commands {
+            commands.push(proto::exchange::Command::try_from(command)?);
+        }
+        Ok(proto::exchange::JoinTrigger {
+            id: obj.id,
+            events,
+            condition: Some(proto::exchange::JoinCondition::try_from(obj.condition)?),
+            commands,
+        })
+    }
+}
+
+impl TryFrom<proto::exchange::JoinTrigger> for JoinTrigger {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: proto::exchange::JoinTrigger) -> Result<JoinTrigger, Self::Error> {
+        let mut events: collections::BTreeMap<(String, u64), Option<EventStatus>> =
+            collections::BTreeMap::new();
+        for event in obj.events {
+            let outcome = match event.outcome {
+                Some(outcome) => Some(EventStatus::try_from(outcome)?),
+                None => None,
+            };
+            events.insert((event.queue_name, event.event_id), outcome);
+        }
+        let mut commands: Vec<Command> = Vec::new();
+        for command in obj.commands {
+            commands.push(Command::try_from(command)?);
+        }
+        Ok(JoinTrigger {
+            id: obj.id,
+            events,
+            condition: JoinCondition::try_from(
+                obj.condition.ok_or(anyhow::anyhow!("condition was None"))?,
+            )?,
+            commands,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_join_trigger(events: u64, condition: JoinCondition) -> Result<JoinTrigger> {
+        let events = (0..events)
+            .map(|event_id| ("test".to_string(), event_id))
+            .collect();
+        JoinTrigger::new(0, events, condition, Vec::new())
+    }
+
+    #[test]
+    fn refuses_joins_which_can_never_resolve() {
+        assert!(new_join_trigger(0, JoinCondition::AllComplete).is_err());
+        assert!(new_join_trigger(2, JoinCondition::Quorum(0)).is_err());
+        assert!(new_join_trigger(2, JoinCondition::Quorum(3)).is_err());
+        assert!(new_join_trigger(2, JoinCondition::Quorum(2)).is_ok());
+    }
+
+    #[test]
+    fn any_error_resolves_on_the_first_failure() {
+        let mut join_trigger = new_join_trigger(2, JoinCondition::AnyError).unwrap();
+        assert!(join_trigger.record("test", 0, &EventStatus::Complete));
+        assert_eq!(join_trigger.resolution(), None);
+        // outcomes are only recorded once
+        assert!(!join_trigger.record("test", 0, &EventStatus::Errored));
+        assert!(join_trigger.record("test", 1, &EventStatus::Expired));
+        assert_eq!(join_trigger.resolution(), Some(true));
+    }
+}
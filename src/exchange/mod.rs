@@ -1,5 +1,13 @@
+pub mod dead_letter;
 pub mod event;
+#[allow(clippy::module_inception)]
 pub mod exchange;
+pub mod join;
 pub mod queue;
+pub mod retry;
+pub mod routing;
+pub mod schedule;
+pub mod selector;
+pub mod subscriber;
 pub mod transaction;
-
+pub mod workflow;
@@ -2,26 +2,156 @@ use anyhow::Result;
 use std::collections;
 use thiserror::Error;
 
-use crate::exchange::event::Event;
+use crate::{exchange::event::Event, rpc::proto};
 
-use super::{event::EventStatus, transaction::Transaction};
+use super::{
+    dead_letter::{DeadLetter, DeadLetterConfig},
+    event::{EventStatus, MAX_DURATION_MS},
+    retry::RetryPolicy,
+    selector::{MatchType, Selector},
+    transaction::{Command, Transaction, Trigger},
+};
 
 #[derive(Debug, Error)]
 pub enum QueueError {
     #[error("event not found: {0}")]
     EventNotFound(u64),
+    #[error("event not running: {0}")]
+    EventNotRunning(u64),
+    #[error("invalid event: {0}")]
+    InvalidEvent(String),
+    #[error("dead letter not found: {0}")]
+    DeadLetterNotFound(u64),
+    #[error("transaction already exists for event: {0}")]
+    TransactionAlreadyExists(u64),
+    #[error("queue is full with {0} queued events")]
+    QueueFull(u64),
+    #[error("payload of {size} bytes exceeds the limit of {max}")]
+    PayloadTooLarge { size: u64, max: u64 },
+    #[error("{count} attributes exceed the limit of {max}")]
+    TooManyAttributes { count: u64, max: u64 },
+    #[error("invalid queue config: {0}")]
+    InvalidConfig(String),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum OverflowPolicy {
+    // the event is refused
+    #[default]
+    Reject,
+    // the oldest queued events are expired to make room
+    DropOldest,
+    // the event is added to the dead letters instead and reported as
+    // dead-lettered
+    DeadLetter,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueueConfig {
+    // includes delayed events
+    max_queued_events: Option<u64>,
+    max_payload_bytes: Option<u64>,
+    max_attributes: Option<u64>,
+    // defaults for events which do not define their own
+    retry_policy: Option<RetryPolicy>,
+    time_to_run: Option<chrono::Duration>,
+    // overrides the exchange's heartbeat interval
+    heartbeat_interval: Option<chrono::Duration>,
+    overflow_policy: OverflowPolicy,
+    // overrides the exchange's dedup window
+    dedup_window: Option<chrono::Duration>,
+}
+
+impl QueueConfig {
+    pub fn retry_policy(&self) -> Option<&RetryPolicy> {
+        self.retry_policy.as_ref()
+    }
+
+    pub fn set_retry_policy(&mut self, retry_policy: Option<RetryPolicy>) {
+        self.retry_policy = retry_policy;
+    }
+
+    fn validate(&self) -> Result<()> {
+        for (name, limit) in [
+            ("max_queued_events", self.max_queued_events),
+            ("max_payload_bytes", self.max_payload_bytes),
+            ("max_attributes", self.max_attributes),
+        ] {
+            if limit == Some(0) {
+                return Err(
+                    QueueError::InvalidConfig(format!("{} has to be positive", name)).into(),
+                );
+            }
+        }
+        for (name, duration) in [
+            ("time_to_run", self.time_to_run),
+            ("heartbeat_interval", self.heartbeat_interval),
+            ("dedup_window", self.dedup_window),
+        ] {
+            if duration.is_some_and(|duration| duration <= chrono::Duration::zero()) {
+                return Err(
+                    QueueError::InvalidConfig(format!("{} has to be positive", name)).into(),
+                );
+            }
+            if duration.is_some_and(|duration| {
+                u64::try_from(duration.num_milliseconds()).is_ok_and(|ms| ms > MAX_DURATION_MS)
+            }) {
+                return Err(QueueError::InvalidConfig(format!(
+                    "{} exceeds {} ms",
+                    name, MAX_DURATION_MS
+                ))
+                .into());
+            }
+        }
+        Ok(())
+    }
+}
+
+// claimable events of a priority level matching a selector, in queue order
+type MatchingEvents<'a> = std::iter::Peekable<Box<dyn Iterator<Item = u64> + 'a>>;
+
+#[derive(Debug, Clone)]
 pub struct Queue {
     name: String,
 
     events: collections::HashMap<u64, Event>,
     event_idx: u64,
 
+    // ids of claimable events by priority level, FIFO within a level
+    queued_events: collections::BTreeMap<u32, collections::BTreeSet<u64>>,
+    // queued events which are not claimable before the given time
+    delayed_events: collections::BTreeSet<(chrono::DateTime<chrono::Utc>, u64)>,
+    // ids of claimable events by attribute name and value, so claims
+    // with a selector only visit the events which can match
+    attribute_index: collections::BTreeMap<(String, String), collections::BTreeSet<u64>>,
+
+    config: QueueConfig,
+    // queued events gain a priority level for every interval they wait
+    // so lower priorities are not starved
+    priority_aging: Option<chrono::Duration>,
+    // events are still added to a paused queue but none can be claimed
+    paused: bool,
+
+    // events which ended in a failure status are only kept when the
+    // queue has a dead-letter config, indexed by event_id
+    dead_letter: Option<DeadLetterConfig>,
+    dead_letters: collections::BTreeMap<u64, DeadLetter>,
+
+    // deadlines of running events, entries are skipped when popped if
+    // the event is no longer running with the same deadline
+    deadlines: collections::BinaryHeap<std::cmp::Reverse<(chrono::DateTime<chrono::Utc>, u64)>>,
+    // expiry times of queued events, entries are skipped when popped if
+    // the event is no longer queued with the same expiry time
+    expirations: collections::BinaryHeap<std::cmp::Reverse<(chrono::DateTime<chrono::Utc>, u64)>>,
+
     // indexed by event_id
     transactions: collections::HashMap<u64, Transaction>,
-    transaction_idx: std::sync::atomic::AtomicU64,
+    transaction_idx: u64,
+
+    // ids assigned to the events added with a dedup key and when they
+    // were added, ordered by that time so old keys are pruned first
+    dedup_keys: collections::HashMap<String, (Vec<u64>, chrono::DateTime<chrono::Utc>)>,
+    dedup_order: collections::BTreeSet<(chrono::DateTime<chrono::Utc>, String)>,
 }
 
 impl Queue {
@@ -30,8 +160,20 @@ impl Queue {
             name,
             events: collections::HashMap::new(),
             event_idx: 0,
+            queued_events: collections::BTreeMap::new(),
+            delayed_events: collections::BTreeSet::new(),
+            attribute_index: collections::BTreeMap::new(),
+            config: QueueConfig::default(),
+            priority_aging: None,
+            paused: false,
+            dead_letter: None,
+            dead_letters: collections::BTreeMap::new(),
+            deadlines: collections::BinaryHeap::new(),
+            expirations: collections::BinaryHeap::new(),
             transactions: collections::HashMap::new(),
-            transaction_idx: std::sync::atomic::AtomicU64::new(0),
+            transaction_idx: 0,
+            dedup_keys: collections::HashMap::new(),
+            dedup_order: collections::BTreeSet::new(),
         }
     }
 
@@ -39,56 +181,1873 @@ impl Queue {
         self.name.clone()
     }
 
-    pub fn add_event(&mut self, mut event: Event) -> u64 {
+    pub fn config(&self) -> &QueueConfig {
+        &self.config
+    }
+
+    // the dead-letter config has to be set first, as the dead-letter
+    // overflow policy depends on it
+    pub fn set_config(&mut self, config: QueueConfig) -> Result<()> {
+        config.validate()?;
+        if config.overflow_policy == OverflowPolicy::DeadLetter && self.dead_letter.is_none() {
+            return Err(QueueError::InvalidConfig(
+                "dead-letter overflow policy requires a dead-letter config".to_string(),
+            )
+            .into());
+        }
+        self.config = config;
+        Ok(())
+    }
+
+    pub fn priority_aging(&self) -> Option<chrono::Duration> {
+        self.priority_aging
+    }
+
+    pub fn set_priority_aging(&mut self, priority_aging: Option<chrono::Duration>) {
+        self.priority_aging = priority_aging;
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn dead_letter(&self) -> Option<&DeadLetterConfig> {
+        self.dead_letter.as_ref()
+    }
+
+    pub fn set_dead_letter(&mut self, dead_letter: Option<DeadLetterConfig>) {
+        self.dead_letter = dead_letter;
+    }
+
+    pub fn event(&self, event_id: &u64) -> Option<&Event> {
+        self.events.get(event_id)
+    }
+
+    // the exchange moves events through every other status, so new events
+    // have to start out queued
+    pub fn validate_event(&self, event: &Event) -> Result<()> {
+        if *event.status() != EventStatus::Queued {
+            return Err(QueueError::InvalidEvent(format!(
+                "new events have to be queued, got {:?}",
+                event.status()
+            ))
+            .into());
+        }
+        let size = event.data().len() as u64;
+        if let Some(max) = self.config.max_payload_bytes
+            && size > max
+        {
+            return Err(QueueError::PayloadTooLarge { size, max }.into());
+        }
+        let count = event.attributes().len() as u64;
+        if let Some(max) = self.config.max_attributes
+            && count > max
+        {
+            return Err(QueueError::TooManyAttributes { count, max }.into());
+        }
+        if event
+            .ttl()
+            .is_some_and(|ttl| ttl <= chrono::Duration::zero())
+        {
+            return Err(QueueError::InvalidEvent("ttl has to be positive".to_string()).into());
+        }
+        if event.ttl().is_some_and(|ttl| {
+            u64::try_from(ttl.num_milliseconds()).is_ok_and(|ms| ms > MAX_DURATION_MS)
+        }) {
+            return Err(
+                QueueError::InvalidEvent(format!("ttl exceeds {} ms", MAX_DURATION_MS)).into(),
+            );
+        }
+        Ok(())
+    }
+
+    // fails if the events do not fit and the queue rejects overflowing
+    // events, or if they would not fit even once the oldest queued events
+    // were dropped
+    fn check_capacity(&self, count: u64) -> Result<()> {
+        let max = if let Some(max) = self.config.max_queued_events {
+            max
+        } else {
+            return Ok(());
+        };
+        let fits = match self.config.overflow_policy {
+            OverflowPolicy::Reject => self.queued_len() + count <= max,
+            OverflowPolicy::DropOldest => count <= max,
+            OverflowPolicy::DeadLetter => true,
+        };
+        if !fits {
+            return Err(QueueError::QueueFull(max).into());
+        }
+        Ok(())
+    }
+
+    // the oldest queued events which have to make room for the given
+    // number of events under the drop-oldest policy, ordered by id
+    pub fn overflow_victims(&self, count: u64) -> Vec<u64> {
+        let max = match self.config.max_queued_events {
+            Some(max) if self.config.overflow_policy == OverflowPolicy::DropOldest => max,
+            _ => return Vec::new(),
+        };
+        let excess = (self.queued_len() + count).saturating_sub(max);
+        let excess = usize::try_from(excess).unwrap_or(usize::MAX);
+        if excess == 0 {
+            return Vec::new();
+        }
+
+        // ids are ordered within a priority level, delayed events are
+        // ordered by time so all of them are candidates
+        let mut candidates: collections::BTreeSet<u64> = collections::BTreeSet::new();
+        for event_ids in self.queued_events.values() {
+            candidates.extend(event_ids.iter().take(excess));
+        }
+        candidates.extend(self.delayed_events.iter().map(|(_, event_id)| *event_id));
+        candidates.into_iter().take(excess).collect()
+    }
+
+    // whether the event was added to the dead letters, which is where the
+    // dead-letter overflow policy puts events added to a full queue
+    pub fn is_dead_letter(&self, event_id: &u64) -> bool {
+        self.dead_letters.contains_key(event_id)
+    }
+
+    pub fn dead_letter_subset(&self, event_ids: &[u64]) -> Vec<u64> {
+        event_ids
+            .iter()
+            .filter(|event_id| self.is_dead_letter(event_id))
+            .cloned()
+            .collect()
+    }
+
+    // fails the way adding the events would, without adding them
+    pub fn check_events(&self, events: &[Event]) -> Result<()> {
+        for event in events.iter() {
+            self.validate_event(event)?;
+        }
+        self.check_capacity(events.len() as u64)
+    }
+
+    pub fn add_event(&mut self, event: Event, now: chrono::DateTime<chrono::Utc>) -> Result<u64> {
+        self.check_events(std::slice::from_ref(&event))?;
+        Ok(self.insert_event(event, now))
+    }
+
+    // every event is validated before the first one is added, so nothing
+    // is added if any of them is invalid
+    pub fn add_events(
+        &mut self,
+        events: Vec<Event>,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<u64>> {
+        self.check_events(&events)?;
+        Ok(events
+            .into_iter()
+            .map(|event| self.insert_event(event, now))
+            .collect())
+    }
+
+    fn insert_event(&mut self, mut event: Event, now: chrono::DateTime<chrono::Utc>) -> u64 {
         let event_idx = self.event_idx;
         event.set_id(event_idx);
-        self.events.insert(event_idx, event);
+        event.set_expires_at(Queue::expires_at(&event, now));
         self.event_idx += 1;
+
+        // the exchange expires the overflow victims of the drop-oldest
+        // policy before adding, so only dead-lettering is left here
+        if self.overflows_to_dead_letters() {
+            self.add_dead_letter(event, now);
+            return event_idx;
+        }
+
+        let queued = *event.status() == EventStatus::Queued;
+        self.events.insert(event_idx, event);
+        if queued {
+            self.enqueue(&event_idx, now);
+        }
         event_idx
     }
 
+    // ttls are bounded when events are added, so only a clock near the end
+    // of chrono's range leaves an event without an expiry
+    fn expires_at(
+        event: &Event,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Option<chrono::DateTime<chrono::Utc>> {
+        event.ttl().and_then(|ttl| now.checked_add_signed(ttl))
+    }
+
+    // whether the next event is dead-lettered as the queue is full
+    fn overflows_to_dead_letters(&self) -> bool {
+        self.config.overflow_policy == OverflowPolicy::DeadLetter
+            && self
+                .config
+                .max_queued_events
+                .is_some_and(|max| self.queued_len() >= max)
+    }
+
+    // number of queued events including delayed ones
+    fn queued_len(&self) -> u64 {
+        let claimable: usize = self
+            .queued_events
+            .values()
+            .map(|event_ids| event_ids.len())
+            .sum();
+        (claimable + self.delayed_events.len()) as u64
+    }
+
+    // indexes a queued event, events with a not before time are held back
+    // until they are promoted once that time has passed
+    fn enqueue(&mut self, event_id: &u64, now: chrono::DateTime<chrono::Utc>) {
+        let (not_before, expires_at) = if let Some(event) = self.events.get(event_id) {
+            (event.not_before(), event.expires_at())
+        } else {
+            return;
+        };
+        if let Some(expires_at) = expires_at {
+            self.expirations
+                .push(std::cmp::Reverse((expires_at, *event_id)));
+        }
+        match not_before {
+            Some(not_before) => {
+                self.delayed_events.insert((not_before, *event_id));
+            }
+            None => self.insert_queued(*event_id, now),
+        }
+    }
+
+    // the time the event became claimable is kept for priority aging
+    fn insert_queued(&mut self, event_id: u64, now: chrono::DateTime<chrono::Utc>) {
+        let event = if let Some(event) = self.events.get_mut(&event_id) {
+            event
+        } else {
+            return;
+        };
+        if event.queued_at().is_none() {
+            event.set_queued_at(Some(now));
+        }
+        for attribute in event.attributes() {
+            self.attribute_index
+                .entry((attribute.name().clone(), attribute.value().clone()))
+                .or_default()
+                .insert(event_id);
+        }
+        self.queued_events
+            .entry(event.priority())
+            .or_default()
+            .insert(event_id);
+    }
+
+    // removes a claimable event from its priority level and the
+    // attribute index, returns false if it was not claimable
+    fn remove_queued(&mut self, event_id: &u64) -> bool {
+        let event = if let Some(event) = self.events.get(event_id) {
+            event
+        } else {
+            return false;
+        };
+        let priority = event.priority();
+        let removed = match self.queued_events.get_mut(&priority) {
+            Some(event_ids) => {
+                let removed = event_ids.remove(event_id);
+                if event_ids.is_empty() {
+                    self.queued_events.remove(&priority);
+                }
+                removed
+            }
+            None => false,
+        };
+        if !removed {
+            return false;
+        }
+
+        for attribute in event.attributes() {
+            let key = (attribute.name().clone(), attribute.value().clone());
+            if let Some(event_ids) = self.attribute_index.get_mut(&key) {
+                event_ids.remove(event_id);
+                if event_ids.is_empty() {
+                    self.attribute_index.remove(&key);
+                }
+            }
+        }
+        true
+    }
+
+    // pops the event with the highest priority, after aging, from the
+    // head of each priority level
+    fn pop_queued(&mut self, now: chrono::DateTime<chrono::Utc>) -> Option<u64> {
+        let mut next: Option<(u64, u32, u64)> = None;
+        for (priority, event_ids) in self.queued_events.iter().rev() {
+            let event_id = if let Some(event_id) = event_ids.first() {
+                *event_id
+            } else {
+                continue;
+            };
+            let aging = if let Some(priority_aging) = self.priority_aging {
+                self.events
+                    .get(&event_id)
+                    .and_then(|event| event.queued_at())
+                    .map(|queued_at| Queue::aged_levels(now - queued_at, priority_aging))
+                    .unwrap_or(0)
+            } else {
+                // without aging the highest level always wins
+                next = Some((0, *priority, event_id));
+                break;
+            };
+            let effective = u64::from(*priority) + aging;
+            if next.is_none_or(|(best, _, _)| effective > best) {
+                next = Some((effective, *priority, event_id));
+            }
+        }
+
+        let (_, _, event_id) = next?;
+        self.remove_queued(&event_id);
+        Some(event_id)
+    }
+
+    // claimable events with an attribute matching the matcher, found
+    // through the attribute index
+    fn matching_event_ids(
+        &self,
+        name: &String,
+        match_type: &MatchType,
+    ) -> collections::BTreeSet<u64> {
+        let mut event_ids: collections::BTreeSet<u64> = collections::BTreeSet::new();
+        let prefix = match match_type {
+            MatchType::Equals(value) => {
+                if let Some(ids) = self.attribute_index.get(&(name.clone(), value.clone())) {
+                    event_ids.extend(ids);
+                }
+                return event_ids;
+            }
+            MatchType::OneOf(values) => {
+                for value in values {
+                    if let Some(ids) = self.attribute_index.get(&(name.clone(), value.clone())) {
+                        event_ids.extend(ids);
+                    }
+                }
+                return event_ids;
+            }
+            MatchType::Exists => "",
+            MatchType::Prefix(prefix) => prefix.as_str(),
+        };
+
+        // index entries of a name are ordered by value so the values with
+        // the prefix are contiguous
+        for ((entry_name, value), ids) in self
+            .attribute_index
+            .range((name.clone(), prefix.to_string())..)
+        {
+            if entry_name != name || !value.starts_with(prefix) {
+                break;
+            }
+            event_ids.extend(ids);
+        }
+        event_ids
+    }
+
+    // pops up to max claimable events matching the selector, ordered the
+    // same way as pop_queued
+    fn pop_selected(
+        &mut self,
+        selector: &Selector,
+        max: u64,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Vec<u64> {
+        let mut candidates: Option<collections::BTreeSet<u64>> = None;
+        for matcher in selector.matchers() {
+            let event_ids = self.matching_event_ids(matcher.name(), matcher.match_type());
+            candidates = Some(match candidates {
+                Some(candidates) => candidates.intersection(&event_ids).cloned().collect(),
+                None => event_ids,
+            });
+            if candidates
+                .as_ref()
+                .is_some_and(|candidates| candidates.is_empty())
+            {
+                return Vec::new();
+            }
+        }
+
+        let candidates = candidates.unwrap_or_default();
+
+        // the matching events of each level in queue order, found from
+        // whichever of the level and the candidates is smaller
+        let mut levels: Vec<(u32, MatchingEvents)> = Vec::new();
+        for (priority, event_ids) in self.queued_events.iter().rev() {
+            let matching: Box<dyn Iterator<Item = u64> + '_> = if candidates.len() < event_ids.len()
+            {
+                Box::new(
+                    candidates
+                        .iter()
+                        .filter(|event_id| event_ids.contains(event_id))
+                        .cloned(),
+                )
+            } else {
+                Box::new(
+                    event_ids
+                        .iter()
+                        .filter(|event_id| candidates.contains(event_id))
+                        .cloned(),
+                )
+            };
+            levels.push((*priority, matching.peekable()));
+        }
+
+        // picks the head of a level the same way as pop_queued, only the
+        // heads of the levels are looked at so no full sort is needed
+        let mut picked: Vec<u64> = Vec::new();
+        while (picked.len() as u64) < max {
+            let mut next: Option<(u64, usize)> = None;
+            for (idx, (priority, matching)) in levels.iter_mut().enumerate() {
+                let event_id = if let Some(event_id) = matching.peek() {
+                    *event_id
+                } else {
+                    continue;
+                };
+                let aging = if let Some(priority_aging) = self.priority_aging {
+                    self.events
+                        .get(&event_id)
+                        .and_then(|event| event.queued_at())
+                        .map(|queued_at| Queue::aged_levels(now - queued_at, priority_aging))
+                        .unwrap_or(0)
+                } else {
+                    next = Some((0, idx));
+                    break;
+                };
+                let effective = u64::from(*priority) + aging;
+                if next.is_none_or(|(best, _)| effective > best) {
+                    next = Some((effective, idx));
+                }
+            }
+            match next.and_then(|(_, idx)| levels[idx].1.next()) {
+                Some(event_id) => picked.push(event_id),
+                None => break,
+            }
+        }
+        drop(levels);
+
+        picked
+            .into_iter()
+            .filter(|event_id| self.remove_queued(event_id))
+            .collect()
+    }
+
+    fn aged_levels(waited: chrono::Duration, priority_aging: chrono::Duration) -> u64 {
+        let interval_ms = priority_aging.num_milliseconds();
+        if interval_ms <= 0 {
+            return 0;
+        }
+        u64::try_from(waited.num_milliseconds() / interval_ms).unwrap_or(0)
+    }
+
+    fn unqueue(&mut self, event_id: &u64) {
+        self.remove_queued(event_id);
+        if let Some(not_before) = self
+            .events
+            .get(event_id)
+            .and_then(|event| event.not_before())
+        {
+            self.delayed_events.remove(&(not_before, *event_id));
+        }
+    }
+
+    // earliest time a delayed event becomes claimable
+    pub fn next_not_before(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.delayed_events
+            .first()
+            .map(|(not_before, _)| *not_before)
+    }
+
+    // moves delayed events whose time has come to the claimable queue
+    pub fn promote_delayed_events(&mut self, now: chrono::DateTime<chrono::Utc>) {
+        while let Some((not_before, event_id)) = self.delayed_events.first().cloned() {
+            if not_before > now {
+                break;
+            }
+            self.delayed_events.pop_first();
+            if let Some(event) = self.events.get_mut(&event_id) {
+                event.set_not_before(None);
+            }
+            self.insert_queued(event_id, now);
+        }
+    }
+
+    fn should_retry(&self, event_id: &u64, status: &EventStatus) -> bool {
+        let event = if let Some(event) = self.events.get(event_id) {
+            event
+        } else {
+            return false;
+        };
+        match event.retry_policy().or(self.config.retry_policy.as_ref()) {
+            Some(retry_policy) => retry_policy.should_retry(status, event.attempt()),
+            None => false,
+        }
+    }
+
+    // queues the event again for its next attempt once the backoff of the
+    // retry policy has passed
+    fn retry_event(&mut self, event_id: &u64, now: chrono::DateTime<chrono::Utc>) {
+        let queue_retry_policy = self.config.retry_policy.clone();
+        let event = if let Some(event) = self.events.get_mut(event_id) {
+            event
+        } else {
+            return;
+        };
+        let backoff = match event.retry_policy().or(queue_retry_policy.as_ref()) {
+            Some(retry_policy) => retry_policy.backoff(event.attempt(), *event_id),
+            None => chrono::Duration::zero(),
+        };
+
+        event.set_attempt(event.attempt() + 1);
+        event.set_status(EventStatus::Queued);
+        event.set_worker_id(None);
+        event.set_deadline(None);
+        // backoffs are capped by the retry policy, an overflow can only
+        // leave the event waiting as long as possible
+        event.set_not_before(Some(
+            now.checked_add_signed(backoff)
+                .unwrap_or(chrono::DateTime::<chrono::Utc>::MAX_UTC),
+        ));
+        self.enqueue(event_id, now);
+    }
+
     pub fn update_event_status(
         &mut self,
         event_id: &u64,
         status: EventStatus,
+        now: chrono::DateTime<chrono::Utc>,
     ) -> (Option<Event>, bool) {
         match status {
+            EventStatus::Errored | EventStatus::MissedHeartbeat | EventStatus::Timedout
+                if self.should_retry(event_id, &status) =>
+            {
+                self.unqueue(event_id);
+                if let Some(event) = self.events.get_mut(event_id) {
+                    event.add_failure(status, now);
+                }
+                self.retry_event(event_id, now);
+                (None, true)
+            }
             EventStatus::Complete
             | EventStatus::Errored
             | EventStatus::MissedHeartbeat
-            | EventStatus::Timedout => {
+            | EventStatus::Timedout
+            | EventStatus::Expired => {
+                if !self.events.contains_key(event_id) {
+                    return (None, false);
+                }
+                self.unqueue(event_id);
                 let mut event = if let Some(event) = self.events.remove(event_id) {
                     event
                 } else {
                     return (None, false);
                 };
+                let failed = status != EventStatus::Complete;
+                if failed {
+                    event.add_failure(status.clone(), now);
+                }
                 event.set_status(status);
+                if failed {
+                    self.add_dead_letter(event.clone(), now);
+                }
                 (Some(event), true)
             }
-            EventStatus::Running | EventStatus::Queued => {
+            EventStatus::Running => {
+                if !self.events.contains_key(event_id) {
+                    return (None, false);
+                }
+                self.unqueue(event_id);
+                let worker_id = self
+                    .events
+                    .get(event_id)
+                    .and_then(|event| event.worker_id().cloned());
+                self.start_event(event_id, worker_id, now);
+                (None, true)
+            }
+            EventStatus::Queued => {
                 let event = if let Some(event) = self.events.get_mut(event_id) {
                     event
                 } else {
                     return (None, false);
                 };
+                event.set_worker_id(None);
+                event.set_delivered(false);
+                event.set_deadline(None);
                 event.set_status(status);
+                self.enqueue(event_id, now);
                 (None, true)
             }
         }
     }
 
+    // moves the event to running, restarting the event's deadline. Missed
+    // heartbeats are only checked once the worker sent a heartbeat for the
+    // attempt, unless the queue sets a heartbeat interval, in which case
+    // every running event has to heartbeat.
+    fn start_event(
+        &mut self,
+        event_id: &u64,
+        worker_id: Option<String>,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Option<&Event> {
+        let default_time_to_run = self.config.time_to_run;
+        let event = self.events.get_mut(event_id)?;
+        event.set_status(EventStatus::Running);
+        event.set_worker_id(worker_id);
+        event.set_delivered(false);
+        event.set_queued_at(None);
+
+        match event
+            .time_to_run()
+            .or(default_time_to_run)
+            .and_then(|time_to_run| now.checked_add_signed(time_to_run))
+        {
+            Some(deadline) => {
+                event.set_deadline(Some(deadline));
+                self.deadlines
+                    .push(std::cmp::Reverse((deadline, *event_id)));
+            }
+            None => event.set_deadline(None),
+        }
+
+        let require_heartbeat = self.config.heartbeat_interval.is_some();
+        if require_heartbeat && !self.transactions.contains_key(event_id) {
+            self.create_transaction(*event_id).ok()?;
+        }
+        if let Some(transaction) = self.transactions.get_mut(event_id) {
+            if require_heartbeat {
+                transaction.update_heartbeat();
+            } else {
+                transaction.clear_heartbeat();
+            }
+        }
+        self.events.get(event_id)
+    }
+
+    pub fn claim_events(
+        &mut self,
+        max: u64,
+        worker_id: &str,
+        selector: &Selector,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Vec<Event> {
+        if self.paused {
+            return Vec::new();
+        }
+        self.promote_delayed_events(now);
+
+        let event_ids = if selector.is_empty() {
+            let mut event_ids: Vec<u64> = Vec::new();
+            while (event_ids.len() as u64) < max {
+                match self.pop_queued(now) {
+                    Some(event_id) => event_ids.push(event_id),
+                    None => break,
+                }
+            }
+            event_ids
+        } else {
+            self.pop_selected(selector, max, now)
+        };
+
+        let mut claimed: Vec<Event> = Vec::new();
+        for event_id in event_ids {
+            if let Some(event) = self.start_event(&event_id, Some(worker_id.to_string()), now) {
+                claimed.push(event.clone());
+            }
+        }
+        claimed
+    }
+
+    pub fn requeue_event(&mut self, event_id: &u64, now: chrono::DateTime<chrono::Utc>) -> bool {
+        match self.events.get(event_id) {
+            Some(event) if *event.status() == EventStatus::Running => {}
+            _ => return false,
+        }
+        self.update_event_status(event_id, EventStatus::Queued, now);
+        true
+    }
+
+    // claims the given queued event for a subscriber, replaying a delivery
+    // recorded in the write-ahead log
+    pub fn deliver_event(
+        &mut self,
+        event_id: &u64,
+        worker_id: &str,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<()> {
+        self.promote_delayed_events(now);
+        match self.events.get(event_id) {
+            Some(event) if *event.status() == EventStatus::Queued => {}
+            Some(_) => {
+                return Err(
+                    QueueError::InvalidEvent(format!("event {} is not queued", event_id)).into(),
+                );
+            }
+            None => return Err(QueueError::EventNotFound(*event_id).into()),
+        }
+        self.unqueue(event_id);
+        self.start_event(event_id, Some(worker_id.to_string()), now);
+        self.set_delivered(event_id);
+        Ok(())
+    }
+
+    pub fn set_delivered(&mut self, event_id: &u64) {
+        if let Some(event) = self.events.get_mut(event_id) {
+            event.set_delivered(true);
+        }
+    }
+
+    // running events which were pushed to subscribers, ordered by id
+    pub fn delivered_events(&self) -> Vec<u64> {
+        let mut event_ids: Vec<u64> = self
+            .events
+            .values()
+            .filter(|event| *event.status() == EventStatus::Running && event.delivered())
+            .map(|event| event.id())
+            .collect();
+        event_ids.sort();
+        event_ids
+    }
+
+    // records a heartbeat for a running event, opening a transaction
+    // for the event if it does not have one yet
+    pub fn heartbeat(&mut self, event_id: &u64) -> Result<()> {
+        match self.events.get(event_id) {
+            Some(event) if *event.status() == EventStatus::Running => {}
+            Some(_) => return Err(QueueError::EventNotRunning(*event_id).into()),
+            None => return Err(QueueError::EventNotFound(*event_id).into()),
+        }
+
+        if !self.transactions.contains_key(event_id) {
+            self.create_transaction(*event_id)?;
+        }
+        if let Some(transaction) = self.transactions.get_mut(event_id) {
+            transaction.update_heartbeat();
+        }
+        Ok(())
+    }
+
+    pub fn missed_heartbeats(
+        &self,
+        now: chrono::DateTime<chrono::Utc>,
+        heartbeat_interval: chrono::Duration,
+    ) -> Vec<u64> {
+        let heartbeat_interval = self.config.heartbeat_interval.unwrap_or(heartbeat_interval);
+        let mut event_ids: Vec<u64> = Vec::new();
+        for (event_id, transaction) in self.transactions.iter() {
+            let last_heartbeat_time = if let Some(time) = transaction.last_heartbeat_time() {
+                time
+            } else {
+                continue;
+            };
+            let running = self
+                .events
+                .get(event_id)
+                .is_some_and(|event| *event.status() == EventStatus::Running);
+            if running && now - last_heartbeat_time > heartbeat_interval {
+                event_ids.push(*event_id);
+            }
+        }
+        event_ids.sort();
+        event_ids
+    }
+
+    // pops the running events whose deadline has passed
+    pub fn timedout_events(&mut self, now: chrono::DateTime<chrono::Utc>) -> Vec<u64> {
+        let mut event_ids: Vec<u64> = Vec::new();
+        while let Some(std::cmp::Reverse((deadline, event_id))) = self.deadlines.peek().cloned() {
+            if deadline > now {
+                break;
+            }
+            self.deadlines.pop();
+
+            let timedout = self.events.get(&event_id).is_some_and(|event| {
+                *event.status() == EventStatus::Running && event.deadline() == Some(deadline)
+            });
+            if timedout {
+                event_ids.push(event_id);
+            }
+        }
+        event_ids
+    }
+
+    // pops the queued events whose expiry time has passed, events are
+    // only expired while queued so running events finish their attempt
+    pub fn expired_events(&mut self, now: chrono::DateTime<chrono::Utc>) -> Vec<u64> {
+        let mut event_ids: Vec<u64> = Vec::new();
+        while let Some(std::cmp::Reverse((expires_at, event_id))) = self.expirations.peek().cloned()
+        {
+            if expires_at > now {
+                break;
+            }
+            self.expirations.pop();
+
+            let expired = self.events.get(&event_id).is_some_and(|event| {
+                *event.status() == EventStatus::Queued && event.expires_at() == Some(expires_at)
+            });
+            if expired {
+                event_ids.push(event_id);
+            }
+        }
+        // an event queued more than once has an entry per time
+        event_ids.sort();
+        event_ids.dedup();
+        event_ids
+    }
+
+    pub fn add_command_trigger(
+        &mut self,
+        event_id: &u64,
+        trigger: Trigger,
+        command: Command,
+    ) -> Result<u64> {
+        if !self.events.contains_key(event_id) {
+            return Err(QueueError::EventNotFound(*event_id).into());
+        }
+
+        if !self.transactions.contains_key(event_id) {
+            self.create_transaction(*event_id)?;
+        }
+        let transaction = if let Some(transaction) = self.transactions.get_mut(event_id) {
+            transaction
+        } else {
+            return Err(QueueError::EventNotFound(*event_id).into());
+        };
+        transaction.add_command_trigger(trigger, command);
+        Ok(transaction.id())
+    }
+
+    // keeps the failed event if the queue has a dead-letter config,
+    // dropping the oldest dead letters past the configured limit
+    fn add_dead_letter(&mut self, event: Event, now: chrono::DateTime<chrono::Utc>) {
+        let max_events = if let Some(dead_letter) = &self.dead_letter {
+            dead_letter.max_events()
+        } else {
+            return;
+        };
+
+        self.dead_letters
+            .insert(event.id(), DeadLetter::new(event, now));
+        if let Some(max_events) = max_events {
+            while self.dead_letters.len() as u64 > max_events {
+                self.dead_letters.pop_first();
+            }
+        }
+    }
+
+    // queued and running events ordered by id, optionally only those
+    // with the given status
+    pub fn list_events(
+        &self,
+        status: Option<&EventStatus>,
+        after_event_id: Option<u64>,
+        limit: u64,
+    ) -> Vec<Event> {
+        let mut event_ids: Vec<u64> = self
+            .events
+            .iter()
+            .filter(|(event_id, event)| {
+                after_event_id.is_none_or(|after_event_id| **event_id > after_event_id)
+                    && status.is_none_or(|status| event.status() == status)
+            })
+            .map(|(event_id, _)| *event_id)
+            .collect();
+        event_ids.sort();
+        if limit > 0 {
+            event_ids.truncate(usize::try_from(limit).unwrap_or(usize::MAX));
+        }
+        event_ids
+            .iter()
+            .filter_map(|event_id| self.events.get(event_id).cloned())
+            .collect()
+    }
+
+    pub fn has_running_events(&self) -> bool {
+        self.events
+            .values()
+            .any(|event| *event.status() == EventStatus::Running)
+    }
+
+    // removes the queued and running events, optionally only those with
+    // the given status, dropping their transactions without running the
+    // command triggers
+    pub fn purge_events(&mut self, status: Option<&EventStatus>) -> Vec<u64> {
+        let event_ids = self.purgeable_events(status);
+        for event_id in event_ids.iter() {
+            self.unqueue(event_id);
+            self.events.remove(event_id);
+            self.transactions.remove(event_id);
+        }
+        event_ids
+    }
+
+    // the events purge_events removes, ordered by id
+    pub fn purgeable_events(&self, status: Option<&EventStatus>) -> Vec<u64> {
+        let mut event_ids: Vec<u64> = self
+            .events
+            .iter()
+            .filter(|(_, event)| status.is_none_or(|status| event.status() == status))
+            .map(|(event_id, _)| *event_id)
+            .collect();
+        event_ids.sort();
+        event_ids
+    }
+
+    pub fn describe(&self, now: chrono::DateTime<chrono::Utc>) -> QueueDescription {
+        let mut queued_events: u64 = 0;
+        let mut running_events: u64 = 0;
+        let mut oldest_queued_at: Option<chrono::DateTime<chrono::Utc>> = None;
+        for event in self.events.values() {
+            match event.status() {
+                EventStatus::Queued => queued_events += 1,
+                EventStatus::Running => running_events += 1,
+                _ => {}
+            }
+            // only claimable events have a queued at time
+            if let Some(queued_at) = event.queued_at()
+                && oldest_queued_at.is_none_or(|oldest| queued_at < oldest)
+            {
+                oldest_queued_at = Some(queued_at);
+            }
+        }
+
+        QueueDescription {
+            name: self.name.clone(),
+            queued_events,
+            running_events,
+            dead_letters: self.dead_letters.len() as u64,
+            transactions: self.transactions.len() as u64,
+            paused: self.paused,
+            config: self.config.clone(),
+            oldest_queued_age: oldest_queued_at
+                .map(|queued_at| (now - queued_at).max(chrono::Duration::zero())),
+        }
+    }
+
+    pub fn list_dead_letters(&self, after_event_id: Option<u64>, limit: u64) -> Vec<DeadLetter> {
+        let start = match after_event_id {
+            Some(after_event_id) => std::ops::Bound::Excluded(after_event_id),
+            None => std::ops::Bound::Unbounded,
+        };
+        let limit = if limit > 0 {
+            usize::try_from(limit).unwrap_or(usize::MAX)
+        } else {
+            usize::MAX
+        };
+        self.dead_letters
+            .range((start, std::ops::Bound::Unbounded))
+            .take(limit)
+            .map(|(_, dead_letter)| dead_letter.clone())
+            .collect()
+    }
+
+    pub fn get_dead_letter(&self, event_id: &u64) -> Result<&DeadLetter> {
+        match self.dead_letters.get(event_id) {
+            Some(dead_letter) => Ok(dead_letter),
+            None => Err(QueueError::DeadLetterNotFound(*event_id).into()),
+        }
+    }
+
+    // resolves the ids a dead-letter command applies to, every dead
+    // letter when none are given
+    pub fn dead_letter_ids(&self, event_ids: &[u64]) -> Result<Vec<u64>> {
+        if event_ids.is_empty() {
+            return Ok(self.dead_letters.keys().cloned().collect());
+        }
+        for event_id in event_ids {
+            if !self.dead_letters.contains_key(event_id) {
+                return Err(QueueError::DeadLetterNotFound(*event_id).into());
+            }
+        }
+        Ok(event_ids.to_vec())
+    }
+
+    // fails the way replaying the dead letters would, without replaying
+    // them
+    pub fn check_replay(&self, event_ids: &[u64]) -> Result<()> {
+        self.dead_letter_ids(event_ids)?;
+        self.check_capacity(event_ids.len() as u64)
+    }
+
+    // moves dead-lettered events back to the queue under their original
+    // ids with a fresh set of attempts and a fresh ttl, the failure
+    // history is kept. Replayed events count against the queue's limit
+    // like added ones, under the dead-letter policy those which do not fit
+    // stay dead-lettered and are not returned.
+    pub fn replay_dead_letters(
+        &mut self,
+        event_ids: &[u64],
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<u64>> {
+        let event_ids = self.dead_letter_ids(event_ids)?;
+        self.check_replay(&event_ids)?;
+        let mut replayed: Vec<u64> = Vec::new();
+        for event_id in event_ids {
+            if self.overflows_to_dead_letters() {
+                continue;
+            }
+            let mut event = if let Some(dead_letter) = self.dead_letters.remove(&event_id) {
+                dead_letter.into_event()
+            } else {
+                continue;
+            };
+            event.set_status(EventStatus::Queued);
+            event.set_worker_id(None);
+            event.set_deadline(None);
+            event.set_not_before(None);
+            event.set_attempt(1);
+            event.set_expires_at(Queue::expires_at(&event, now));
+            self.events.insert(event_id, event);
+            self.enqueue(&event_id, now);
+            replayed.push(event_id);
+        }
+        Ok(replayed)
+    }
+
+    pub fn purge_dead_letters(&mut self, event_ids: &[u64]) -> Result<u64> {
+        let event_ids = self.dead_letter_ids(event_ids)?;
+        let mut purged: u64 = 0;
+        for event_id in event_ids {
+            if self.dead_letters.remove(&event_id).is_some() {
+                purged += 1;
+            }
+        }
+        Ok(purged)
+    }
+
+    // finished events are removed from the queue, so only queued and
+    // running events can have a transaction
     pub fn create_transaction(&mut self, event_id: u64) -> Result<u64> {
-        let trans_id = self
-            .transaction_idx
-            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-        self.transactions.insert(
-            event_id.clone(),
-            Transaction::new(trans_id.clone(), event_id.clone()),
-        );
+        if !self.events.contains_key(&event_id) {
+            return Err(QueueError::EventNotFound(event_id).into());
+        }
+        if self.transactions.contains_key(&event_id) {
+            return Err(QueueError::TransactionAlreadyExists(event_id).into());
+        }
+
+        let trans_id = self.transaction_idx;
+        self.transaction_idx += 1;
+        self.transactions
+            .insert(event_id, Transaction::new(trans_id, event_id));
         Ok(trans_id)
     }
 
+    pub fn transaction(&self, transaction_id: u64) -> Option<&Transaction> {
+        self.transactions
+            .values()
+            .find(|transaction| transaction.id() == transaction_id)
+    }
+
+    // ordered by transaction id
+    pub fn transactions(&self) -> Vec<&Transaction> {
+        let mut transactions: Vec<&Transaction> = self.transactions.values().collect();
+        transactions.sort_by_key(|transaction| transaction.id());
+        transactions
+    }
+
     pub fn remove_transaction(&mut self, event_id: &u64) -> Option<Transaction> {
         self.transactions.remove(event_id)
     }
+
+    // drops the transaction without running its command triggers
+    pub fn abort_transaction(&mut self, transaction_id: u64) -> Option<Transaction> {
+        let event_id = self.transaction(transaction_id)?.event_id();
+        self.transactions.remove(&event_id)
+    }
+
+    // the ids assigned when the dedup key was added, None once the key is
+    // older than the dedup window
+    pub fn dedup_event_ids(
+        &self,
+        dedup_key: &str,
+        now: chrono::DateTime<chrono::Utc>,
+        dedup_window: chrono::Duration,
+    ) -> Option<&Vec<u64>> {
+        let dedup_window = self.config.dedup_window.unwrap_or(dedup_window);
+        match self.dedup_keys.get(dedup_key) {
+            Some((event_ids, added_at)) if Queue::dedup_key_live(*added_at, dedup_window, now) => {
+                Some(event_ids)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn add_dedup_key(
+        &mut self,
+        dedup_key: String,
+        event_ids: Vec<u64>,
+        now: chrono::DateTime<chrono::Utc>,
+    ) {
+        if let Some((_, added_at)) = self.dedup_keys.insert(dedup_key.clone(), (event_ids, now)) {
+            self.dedup_order.remove(&(added_at, dedup_key.clone()));
+        }
+        self.dedup_order.insert((now, dedup_key));
+    }
+
+    // windows are bounded by the queue config, a key whose window ends
+    // past chrono's range is kept
+    fn dedup_key_live(
+        added_at: chrono::DateTime<chrono::Utc>,
+        dedup_window: chrono::Duration,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> bool {
+        added_at
+            .checked_add_signed(dedup_window)
+            .is_none_or(|expires_at| expires_at > now)
+    }
+
+    // forgets the dedup keys older than the dedup window
+    pub fn prune_dedup_keys(
+        &mut self,
+        now: chrono::DateTime<chrono::Utc>,
+        dedup_window: chrono::Duration,
+    ) {
+        let dedup_window = self.config.dedup_window.unwrap_or(dedup_window);
+        while let Some((added_at, dedup_key)) = self.dedup_order.first().cloned() {
+            if Queue::dedup_key_live(added_at, dedup_window, now) {
+                break;
+            }
+            self.dedup_order.pop_first();
+            self.dedup_keys.remove(&dedup_key);
+        }
+    }
+}
+
+impl TryFrom<&Queue> for proto::storage::QueueState {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: &Queue) -> Result<Self, Self::Error> {
+        let mut event_ids: Vec<&u64> = obj.events.keys().collect();
+        event_ids.sort();
+        let mut events: Vec<proto::storage::EventState> = Vec::new();
+        for event_id in event_ids {
+            events.push(proto::storage::EventState::try_from(&obj.events[event_id])?);
+        }
+
+        let mut transaction_ids: Vec<&u64> = obj.transactions.keys().collect();
+        transaction_ids.sort();
+        let mut transactions: Vec<proto::storage::TransactionState> = Vec::new();
+        for event_id in transaction_ids {
+            transactions.push(proto::storage::TransactionState::try_from(
+                &obj.transactions[event_id],
+            )?);
+        }
+
+        let mut dead_letters: Vec<proto::storage::DeadLetterState> = Vec::new();
+        for dead_letter in obj.dead_letters.values() {
+            dead_letters.push(proto::storage::DeadLetterState::try_from(dead_letter)?);
+        }
+
+        let dedup_keys: Vec<proto::storage::DedupKeyState> = obj
+            .dedup_order
+            .iter()
+            .filter_map(|(_, dedup_key)| {
+                let (event_ids, added_at) = obj.dedup_keys.get(dedup_key)?;
+                Some(proto::storage::DedupKeyState {
+                    key: dedup_key.clone(),
+                    event_ids: event_ids.clone(),
+                    added_at: added_at.timestamp_millis(),
+                })
+            })
+            .collect();
+
+        Ok(proto::storage::QueueState {
+            name: obj.name.clone(),
+            events,
+            event_idx: obj.event_idx,
+            transactions,
+            transaction_idx: obj.transaction_idx,
+            retry_policy: None,
+            dead_letter: match obj.dead_letter.clone() {
+                Some(dead_letter) => {
+                    Some(proto::exchange::DeadLetterConfig::try_from(dead_letter)?)
+                }
+                None => None,
+            },
+            dead_letters,
+            priority_aging_ms: match obj.priority_aging {
+                Some(priority_aging) => Some(u64::try_from(priority_aging.num_milliseconds())?),
+                None => None,
+            },
+            paused: obj.paused,
+            config: Some(proto::exchange::QueueConfig::try_from(obj.config.clone())?),
+            dedup_keys,
+        })
+    }
+}
+
+impl TryFrom<proto::storage::QueueState> for Queue {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: proto::storage::QueueState) -> Result<Self, Self::Error> {
+        let mut queue = Queue::new(obj.name);
+        queue.event_idx = obj.event_idx;
+        queue.transaction_idx = obj.transaction_idx;
+        queue.paused = obj.paused;
+        queue.config = match obj.config {
+            Some(config) => QueueConfig::try_from(config)?,
+            None => QueueConfig::default(),
+        };
+        if queue.config.retry_policy.is_none()
+            && let Some(retry_policy) = obj.retry_policy
+        {
+            queue.config.retry_policy = Some(RetryPolicy::try_from(retry_policy)?);
+        }
+        queue.dead_letter = match obj.dead_letter {
+            Some(dead_letter) => Some(DeadLetterConfig::try_from(dead_letter)?),
+            None => None,
+        };
+        for dead_letter_state in obj.dead_letters {
+            let dead_letter = DeadLetter::try_from(dead_letter_state)?;
+            queue
+                .dead_letters
+                .insert(dead_letter.event().id(), dead_letter);
+        }
+
+        queue.priority_aging = match obj.priority_aging_ms {
+            Some(priority_aging_ms) => Some(chrono::Duration::milliseconds(i64::try_from(
+                priority_aging_ms,
+            )?)),
+            None => None,
+        };
+
+        let now = chrono::Utc::now();
+        for event_state in obj.events {
+            let event = Event::try_from(event_state)?;
+            let event_id = event.id();
+            let status = event.status().clone();
+            if let (EventStatus::Running, Some(deadline)) = (&status, event.deadline()) {
+                queue
+                    .deadlines
+                    .push(std::cmp::Reverse((deadline, event_id)));
+            }
+            queue.events.insert(event_id, event);
+            if status == EventStatus::Queued {
+                queue.enqueue(&event_id, now);
+            }
+        }
+
+        for transaction_state in obj.transactions {
+            let transaction = Transaction::try_from(transaction_state)?;
+            queue
+                .transactions
+                .insert(transaction.event_id(), transaction);
+        }
+
+        for dedup_key_state in obj.dedup_keys {
+            let added_at =
+                chrono::DateTime::from_timestamp_millis(dedup_key_state.added_at).ok_or(
+                    anyhow::anyhow!("invalid dedup key added at: {}", dedup_key_state.added_at),
+                )?;
+            queue.add_dedup_key(dedup_key_state.key, dedup_key_state.event_ids, added_at);
+        }
+
+        Ok(queue)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct QueueDescription {
+    name: String,
+    queued_events: u64,
+    running_events: u64,
+    dead_letters: u64,
+    transactions: u64,
+    paused: bool,
+    config: QueueConfig,
+    oldest_queued_age: Option<chrono::Duration>,
+}
+
+impl TryFrom<QueueDescription> for proto::exchange::QueueDescription {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: QueueDescription) -> Result<Self, Self::Error> {
+        let oldest_queued_age_ms = match obj.oldest_queued_age {
+            Some(age) => Some(u64::try_from(age.num_milliseconds())?),
+            None => None,
+        };
+        Ok(proto::exchange::QueueDescription {
+            name: obj.name,
+            queued_events: obj.queued_events,
+            running_events: obj.running_events,
+            dead_letters: obj.dead_letters,
+            transactions: obj.transactions,
+            oldest_queued_age_ms,
+            paused: obj.paused,
+            config: Some(proto::exchange::QueueConfig::try_from(obj.config)?),
+        })
+    }
+}
+
+impl TryFrom<proto::exchange::OverflowPolicy> for OverflowPolicy {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: proto::exchange::OverflowPolicy) -> Result<OverflowPolicy, Self::Error> {
+        match obj {
+            proto::exchange::OverflowPolicy::Unspecified
+            | proto::exchange::OverflowPolicy::Reject => Ok(OverflowPolicy::Reject),
+            proto::exchange::OverflowPolicy::DropOldest => Ok(OverflowPolicy::DropOldest),
+            proto::exchange::OverflowPolicy::DeadLetter => Ok(OverflowPolicy::DeadLetter),
+        }
+    }
+}
+
+impl TryFrom<OverflowPolicy> for proto::exchange::OverflowPolicy {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: OverflowPolicy) -> Result<proto::exchange::OverflowPolicy, Self::Error> {
+        match obj {
+            OverflowPolicy::Reject => Ok(proto::exchange::OverflowPolicy::Reject),
+            OverflowPolicy::DropOldest => Ok(proto::exchange::OverflowPolicy::DropOldest),
+            OverflowPolicy::DeadLetter => Ok(proto::exchange::OverflowPolicy::DeadLetter),
+        }
+    }
+}
+
+impl TryFrom<proto::exchange::QueueConfig> for QueueConfig {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: proto::exchange::QueueConfig) -> Result<QueueConfig, Self::Error> {
+        let config = QueueConfig {
+            max_queued_events: obj.max_queued_events,
+            max_payload_bytes: obj.max_payload_bytes,
+            max_attributes: obj.max_attributes,
+            retry_policy: match obj.retry_policy {
+                Some(retry_policy) => Some(RetryPolicy::try_from(retry_policy)?),
+                None => None,
+            },
+            time_to_run: match obj.time_to_run_ms {
+                Some(time_to_run_ms) => Some(chrono::Duration::milliseconds(i64::try_from(
+                    time_to_run_ms,
+                )?)),
+                None => None,
+            },
+            heartbeat_interval: match obj.heartbeat_interval_ms {
+                Some(heartbeat_interval_ms) => Some(chrono::Duration::milliseconds(i64::try_from(
+                    heartbeat_interval_ms,
+                )?)),
+                None => None,
+            },
+            overflow_policy: OverflowPolicy::try_from(proto::exchange::OverflowPolicy::try_from(
+                obj.overflow_policy,
+            )?)?,
+            dedup_window: match obj.dedup_window_ms {
+                Some(dedup_window_ms) => Some(chrono::Duration::milliseconds(i64::try_from(
+                    dedup_window_ms,
+                )?)),
+                None => None,
+            },
+        };
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+impl TryFrom<QueueConfig> for proto::exchange::QueueConfig {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: QueueConfig) -> Result<proto::exchange::QueueConfig, Self::Error> {
+        Ok(proto::exchange::QueueConfig {
+            max_queued_events: obj.max_queued_events,
+            max_payload_bytes: obj.max_payload_bytes,
+            max_attributes: obj.max_attributes,
+            retry_policy: match obj.retry_policy {
+                Some(retry_policy) => Some(proto::exchange::RetryPolicy::try_from(retry_policy)?),
+                None => None,
+            },
+            time_to_run_ms: match obj.time_to_run {
+                Some(time_to_run) => Some(u64::try_from(time_to_run.num_milliseconds())?),
+                None => None,
+            },
+            heartbeat_interval_ms: match obj.heartbeat_interval {
+                Some(heartbeat_interval) => {
+                    Some(u64::try_from(heartbeat_interval.num_milliseconds())?)
+                }
+                None => None,
+            },
+            overflow_policy: proto::exchange::OverflowPolicy::try_from(obj.overflow_policy)? as i32,
+            dedup_window_ms: match obj.dedup_window {
+                Some(dedup_window) => Some(u64::try_from(dedup_window.num_milliseconds())?),
+                None => None,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange::event::{Attribute, bounded_duration};
+    use crate::exchange::selector::AttributeMatcher;
+    use crate::rpc::proto;
+
+    fn new_event(priority: u32) -> Event {
+        Event::try_from(proto::exchange::Event {
+            priority,
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    fn new_queue(config: QueueConfig) -> Queue {
+        let mut queue = Queue::new("test".to_string());
+        queue.set_dead_letter(Some(DeadLetterConfig::new(None)));
+        queue.set_config(config).unwrap();
+        queue
+    }
+
+    fn claim_ids(queue: &mut Queue, max: u64, now: chrono::DateTime<chrono::Utc>) -> Vec<u64> {
+        queue
+            .claim_events(max, "worker", &Selector::default(), now)
+            .iter()
+            .map(|event| event.id())
+            .collect()
+    }
+
+    #[test]
+    fn claims_higher_priorities_first_and_fifo_within_a_priority() {
+        let now = chrono::Utc::now();
+        let mut queue = new_queue(QueueConfig::default());
+        for priority in [0, 5, 0, 5, 1] {
+            queue.add_event(new_event(priority), now).unwrap();
+        }
+
+        assert_eq!(claim_ids(&mut queue, 2, now), vec![1, 3]);
+        assert_eq!(claim_ids(&mut queue, 10, now), vec![4, 0, 2]);
+        assert!(claim_ids(&mut queue, 10, now).is_empty());
+        assert_eq!(
+            queue.event(&4).map(|event| event.status().clone()),
+            Some(EventStatus::Running)
+        );
+    }
+
+    #[test]
+    fn heartbeats_are_only_checked_once_the_worker_sent_one() {
+        let now = chrono::Utc::now();
+        let interval = chrono::Duration::seconds(30);
+        let mut queue = new_queue(QueueConfig::default());
+        let event_id = queue.add_event(new_event(0), now).unwrap();
+        queue
+            .add_command_trigger(
+                &event_id,
+                Trigger::OnEventComplete(event_id),
+                Command::ListQueues {},
+            )
+            .unwrap();
+        assert_eq!(claim_ids(&mut queue, 1, now), vec![event_id]);
+
+        // the transaction of the trigger does not require heartbeats
+        let later = now + interval * 2;
+        assert!(queue.missed_heartbeats(later, interval).is_empty());
+
+        queue.heartbeat(&event_id).unwrap();
+        assert!(queue.missed_heartbeats(now, interval).is_empty());
+        assert_eq!(queue.missed_heartbeats(later, interval), vec![event_id]);
+
+        // a new attempt starts without heartbeats again
+        queue.requeue_event(&event_id, now);
+        assert_eq!(claim_ids(&mut queue, 1, now), vec![event_id]);
+        assert!(queue.missed_heartbeats(later, interval).is_empty());
+    }
+
+    #[test]
+    fn retries_failed_events_then_dead_letters_them() {
+        let now = chrono::Utc::now();
+        let retry_policy = RetryPolicy::try_from(proto::exchange::RetryPolicy {
+            max_attempts: 2,
+            initial_backoff_ms: 1000,
+            ..Default::default()
+        })
+        .unwrap();
+        let mut queue = new_queue(QueueConfig {
+            retry_policy: Some(retry_policy),
+            ..Default::default()
+        });
+        let event_id = queue.add_event(new_event(0), now).unwrap();
+
+        assert_eq!(claim_ids(&mut queue, 1, now), vec![event_id]);
+        let (finished, updated) = queue.update_event_status(&event_id, EventStatus::Errored, now);
+        assert!(finished.is_none());
+        assert!(updated);
+
+        // the retry waits for its backoff before it can be claimed
+        assert!(claim_ids(&mut queue, 1, now).is_empty());
+        let later = now + chrono::Duration::seconds(1);
+        assert_eq!(claim_ids(&mut queue, 1, later), vec![event_id]);
+        assert_eq!(queue.event(&event_id).map(|event| event.attempt()), Some(2));
+
+        let (finished, updated) = queue.update_event_status(&event_id, EventStatus::Errored, later);
+        assert_eq!(
+            finished.map(|event| event.status().clone()),
+            Some(EventStatus::Errored)
+        );
+        assert!(updated);
+        assert!(queue.event(&event_id).is_none());
+
+        let dead_letters = queue.list_dead_letters(None, 0);
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].event().id(), event_id);
+        assert_eq!(dead_letters[0].event().failures().len(), 2);
+
+        assert_eq!(
+            queue.replay_dead_letters(&[], later).unwrap(),
+            vec![event_id]
+        );
+        assert_eq!(claim_ids(&mut queue, 1, later), vec![event_id]);
+        assert_eq!(queue.event(&event_id).map(|event| event.attempt()), Some(1));
+    }
+
+    #[test]
+    fn completed_events_are_not_dead_lettered() {
+        let now = chrono::Utc::now();
+        let mut queue = new_queue(QueueConfig::default());
+        let event_id = queue.add_event(new_event(0), now).unwrap();
+        claim_ids(&mut queue, 1, now);
+
+        let (finished, updated) = queue.update_event_status(&event_id, EventStatus::Complete, now);
+        assert!(finished.is_some());
+        assert!(updated);
+        assert!(queue.list_dead_letters(None, 0).is_empty());
+    }
+
+    fn new_region_event(priority: u32, region: &str) -> Event {
+        let mut event = new_event(priority);
+        event.add_attribute(Attribute::new("region".to_string(), region.to_string()));
+        event
+    }
+
+    fn claim_selected(
+        queue: &mut Queue,
+        max: u64,
+        match_type: MatchType,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Vec<u64> {
+        let selector = Selector::new(vec![AttributeMatcher::new(
+            "region".to_string(),
+            match_type,
+        )]);
+        queue
+            .claim_events(max, "worker", &selector, now)
+            .iter()
+            .map(|event| event.id())
+            .collect()
+    }
+
+    #[test]
+    fn claims_selected_events_in_priority_order() {
+        let now = chrono::Utc::now();
+        let mut queue = new_queue(QueueConfig::default());
+        for (priority, region) in [(0, "eu-west"), (1, "us"), (0, "eu-north"), (2, "eu-west")] {
+            queue
+                .add_event(new_region_event(priority, region), now)
+                .unwrap();
+        }
+        queue.add_event(new_event(3), now).unwrap();
+
+        let equals = MatchType::Equals("eu-west".to_string());
+        assert_eq!(
+            claim_selected(&mut queue, 10, equals.clone(), now),
+            vec![3, 0]
+        );
+        assert!(claim_selected(&mut queue, 10, equals, now).is_empty());
+        // the limit stops the claim at the highest matching priorities
+        let prefix = MatchType::Prefix("eu".to_string());
+        assert_eq!(claim_selected(&mut queue, 1, prefix.clone(), now), vec![2]);
+        assert_eq!(
+            claim_selected(&mut queue, 10, MatchType::Exists, now),
+            vec![1]
+        );
+        assert!(claim_selected(&mut queue, 10, prefix, now).is_empty());
+        // events without the attribute are left to unselective claims
+        assert_eq!(claim_ids(&mut queue, 10, now), vec![4]);
+    }
+
+    #[test]
+    fn selected_claims_age_priorities_like_unselected_claims() {
+        let now = chrono::Utc::now();
+        let mut queue = new_queue(QueueConfig::default());
+        queue.set_priority_aging(Some(chrono::Duration::seconds(1)));
+        queue
+            .add_event(
+                new_region_event(0, "eu"),
+                now - chrono::Duration::seconds(5),
+            )
+            .unwrap();
+        queue.add_event(new_region_event(3, "eu"), now).unwrap();
+        queue.add_event(new_region_event(6, "eu"), now).unwrap();
+
+        // the oldest event aged past the event of priority 3
+        let equals = MatchType::Equals("eu".to_string());
+        assert_eq!(claim_selected(&mut queue, 10, equals, now), vec![2, 0, 1]);
+    }
+
+    #[test]
+    fn creates_one_transaction_per_event_with_its_own_id() {
+        let now = chrono::Utc::now();
+        let mut queue = new_queue(QueueConfig::default());
+        queue.add_event(new_event(0), now).unwrap();
+        queue.add_event(new_event(0), now).unwrap();
+
+        assert_eq!(queue.create_transaction(1).unwrap(), 0);
+        assert_eq!(queue.create_transaction(0).unwrap(), 1);
+        assert!(queue.create_transaction(0).is_err());
+        assert!(queue.create_transaction(2).is_err());
+        assert_eq!(queue.transaction(1).map(|t| t.event_id()), Some(0));
+
+        assert!(queue.abort_transaction(1).is_some());
+        assert!(queue.transaction(1).is_none());
+        // ids are not reused once a transaction is gone
+        assert_eq!(queue.create_transaction(0).unwrap(), 2);
+    }
+
+    #[test]
+    fn legacy_retry_policy_is_read_from_older_snapshots() {
+        let retry_policy = |max_attempts| proto::exchange::RetryPolicy {
+            max_attempts,
+            ..Default::default()
+        };
+        let queue = Queue::try_from(proto::storage::QueueState {
+            name: "test".to_string(),
+            retry_policy: Some(retry_policy(3)),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(
+            queue
+                .config()
+                .retry_policy()
+                .map(|policy| policy.max_attempts()),
+            Some(3)
+        );
+
+        // the config's policy wins over the legacy field
+        let queue = Queue::try_from(proto::storage::QueueState {
+            name: "test".to_string(),
+            retry_policy: Some(retry_policy(3)),
+            config: Some(proto::exchange::QueueConfig {
+                retry_policy: Some(retry_policy(2)),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(
+            queue
+                .config()
+                .retry_policy()
+                .map(|policy| policy.max_attempts()),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn dedup_keys_are_forgotten_after_the_window() {
+        let now = chrono::Utc::now();
+        let window = chrono::Duration::seconds(10);
+        let mut queue = new_queue(QueueConfig::default());
+        queue.add_dedup_key("key".to_string(), vec![3, 4], now);
+
+        let before = now + window - chrono::Duration::milliseconds(1);
+        assert_eq!(
+            queue.dedup_event_ids("key", before, window),
+            Some(&vec![3, 4])
+        );
+        assert_eq!(queue.dedup_event_ids("key", now + window, window), None);
+        assert_eq!(queue.dedup_event_ids("other", now, window), None);
+
+        queue.prune_dedup_keys(before, window);
+        assert_eq!(queue.dedup_keys.len(), 1);
+        queue.prune_dedup_keys(now + window, window);
+        assert!(queue.dedup_keys.is_empty());
+        assert!(queue.dedup_order.is_empty());
+    }
+
+    #[test]
+    fn queue_dedup_window_overrides_the_exchange_window() {
+        let now = chrono::Utc::now();
+        let mut queue = new_queue(QueueConfig {
+            dedup_window: Some(chrono::Duration::seconds(1)),
+            ..Default::default()
+        });
+        queue.add_dedup_key("key".to_string(), vec![0], now);
+
+        let later = now + chrono::Duration::seconds(2);
+        assert_eq!(
+            queue.dedup_event_ids("key", later, chrono::Duration::hours(1)),
+            None
+        );
+    }
+
+    #[test]
+    fn dedup_windows_past_the_end_of_time_stay_live() {
+        let added_at = chrono::DateTime::<chrono::Utc>::MAX_UTC - chrono::Duration::seconds(1);
+        let window = bounded_duration(MAX_DURATION_MS).unwrap();
+        assert!(Queue::dedup_key_live(added_at, window, added_at));
+
+        let mut queue = new_queue(QueueConfig::default());
+        queue.add_dedup_key("key".to_string(), vec![0], added_at);
+        queue.prune_dedup_keys(added_at, window);
+        assert!(queue.dedup_event_ids("key", added_at, window).is_some());
+    }
+
+    #[test]
+    fn reject_policy_refuses_events_past_the_limit() {
+        let now = chrono::Utc::now();
+        let mut queue = new_queue(QueueConfig {
+            max_queued_events: Some(2),
+            ..Default::default()
+        });
+        queue
+            .add_events(vec![new_event(0), new_event(0)], now)
+            .unwrap();
+
+        let err = queue.add_event(new_event(0), now).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<QueueError>(),
+            Some(QueueError::QueueFull(2))
+        ));
+        assert!(queue.overflow_victims(1).is_empty());
+    }
+
+    #[test]
+    fn drop_oldest_policy_picks_the_oldest_queued_events() {
+        let now = chrono::Utc::now();
+        let mut queue = new_queue(QueueConfig {
+            max_queued_events: Some(3),
+            overflow_policy: OverflowPolicy::DropOldest,
+            ..Default::default()
+        });
+        for priority in [5, 0, 5] {
+            queue.add_event(new_event(priority), now).unwrap();
+        }
+
+        assert!(queue.overflow_victims(0).is_empty());
+        assert_eq!(queue.overflow_victims(2), vec![0, 1]);
+        assert!(queue.check_events(&[new_event(0), new_event(0)]).is_ok());
+
+        // more events than the queue can ever hold are refused
+        let events: Vec<Event> = (0..4).map(|_| new_event(0)).collect();
+        let err = queue.check_events(&events).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<QueueError>(),
+            Some(QueueError::QueueFull(3))
+        ));
+    }
+
+    #[test]
+    fn dead_letter_policy_dead_letters_events_past_the_limit() {
+        let now = chrono::Utc::now();
+        let mut queue = new_queue(QueueConfig {
+            max_queued_events: Some(1),
+            overflow_policy: OverflowPolicy::DeadLetter,
+            ..Default::default()
+        });
+        let event_ids = queue
+            .add_events(vec![new_event(0), new_event(0), new_event(0)], now)
+            .unwrap();
+
+        assert_eq!(event_ids, vec![0, 1, 2]);
+        assert!(!queue.is_dead_letter(&0));
+        assert_eq!(queue.dead_letter_subset(&event_ids), vec![1, 2]);
+        assert_eq!(claim_ids(&mut queue, 10, now), vec![0]);
+    }
+
+    #[test]
+    fn configs_with_durations_past_the_bound_are_refused() {
+        for time_to_run_ms in [0, MAX_DURATION_MS + 1] {
+            let config = proto::exchange::QueueConfig {
+                time_to_run_ms: Some(time_to_run_ms),
+                ..Default::default()
+            };
+            assert!(QueueConfig::try_from(config).is_err());
+        }
+
+        let config = proto::exchange::QueueConfig {
+            dedup_window_ms: Some(MAX_DURATION_MS),
+            ..Default::default()
+        };
+        assert!(QueueConfig::try_from(config).is_ok());
+    }
+
+    #[test]
+    fn deadlines_and_expiry_times_past_the_end_of_time_are_left_unset() {
+        let now = chrono::DateTime::<chrono::Utc>::MAX_UTC - chrono::Duration::seconds(1);
+        let mut queue = new_queue(QueueConfig {
+            time_to_run: bounded_duration(MAX_DURATION_MS),
+            ..Default::default()
+        });
+        let mut event = new_event(0);
+        event.set_ttl(bounded_duration(MAX_DURATION_MS));
+        let event_id = queue.add_event(event, now).unwrap();
+        assert_eq!(
+            queue.event(&event_id).and_then(|event| event.expires_at()),
+            None
+        );
+
+        assert_eq!(claim_ids(&mut queue, 1, now), vec![event_id]);
+        assert_eq!(
+            queue.event(&event_id).and_then(|event| event.deadline()),
+            None
+        );
+    }
+
+    #[test]
+    fn queue_heartbeat_interval_requires_heartbeats_from_every_event() {
+        let now = chrono::Utc::now();
+        let interval = chrono::Duration::seconds(30);
+        let mut queue = new_queue(QueueConfig {
+            heartbeat_interval: Some(interval),
+            ..Default::default()
+        });
+        let event_id = queue.add_event(new_event(0), now).unwrap();
+        assert_eq!(claim_ids(&mut queue, 1, now), vec![event_id]);
+
+        assert!(
+            queue
+                .missed_heartbeats(chrono::Utc::now(), interval)
+                .is_empty()
+        );
+        let later = chrono::Utc::now() + interval * 2;
+        assert_eq!(
+            queue.missed_heartbeats(later, chrono::Duration::hours(1)),
+            vec![event_id]
+        );
+    }
+
+    // dead-letters a claimed event and fills the queue up to its limit
+    fn dead_letter_and_fill(queue: &mut Queue, now: chrono::DateTime<chrono::Utc>) -> u64 {
+        let event_id = queue.add_event(new_event(0), now).unwrap();
+        claim_ids(queue, 1, now);
+        queue.update_event_status(&event_id, EventStatus::Errored, now);
+        queue.add_event(new_event(0), now).unwrap();
+        event_id
+    }
+
+    #[test]
+    fn replays_into_a_full_queue_follow_the_overflow_policy() {
+        let now = chrono::Utc::now();
+        let mut queue = new_queue(QueueConfig {
+            max_queued_events: Some(1),
+            ..Default::default()
+        });
+        let event_id = dead_letter_and_fill(&mut queue, now);
+        let err = queue.replay_dead_letters(&[event_id], now).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<QueueError>(),
+            Some(QueueError::QueueFull(1))
+        ));
+        assert!(queue.is_dead_letter(&event_id));
+
+        let mut queue = new_queue(QueueConfig {
+            max_queued_events: Some(1),
+            overflow_policy: OverflowPolicy::DeadLetter,
+            ..Default::default()
+        });
+        let event_id = dead_letter_and_fill(&mut queue, now);
+        assert!(queue.replay_dead_letters(&[], now).unwrap().is_empty());
+        assert!(queue.is_dead_letter(&event_id));
+    }
 }
@@ -0,0 +1,204 @@
+use anyhow::Result;
+
+use crate::rpc::proto;
+
+use super::event::{EventStatus, MAX_DURATION_MS, bounded_duration};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    // total number of attempts, including the first one
+    max_attempts: u32,
+    initial_backoff: chrono::Duration,
+    max_backoff: chrono::Duration,
+    multiplier: f64,
+    // fraction of the backoff randomly added or removed, from 0 to 1
+    jitter: f64,
+    retryable_statuses: Vec<EventStatus>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            initial_backoff: chrono::Duration::seconds(1),
+            max_backoff: chrono::Duration::minutes(5),
+            multiplier: 2.0,
+            jitter: 0.2,
+            retryable_statuses: vec![
+                EventStatus::Errored,
+                EventStatus::MissedHeartbeat,
+                EventStatus::Timedout,
+            ],
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    // whether an event which ended with the status on the given attempt
+    // should be queued again
+    pub fn should_retry(&self, status: &EventStatus, attempt: u32) -> bool {
+        attempt < self.max_attempts && self.retryable_statuses.contains(status)
+    }
+
+    // exponential backoff before the next attempt, with jitter applied so
+    // events failing together do not all come back at the same time. The
+    // jitter is derived from the seed so replaying the write-ahead log
+    // computes the same backoff.
+    pub fn backoff(&self, attempt: u32, seed: u64) -> chrono::Duration {
+        let exponent = i32::try_from(attempt.saturating_sub(1)).unwrap_or(i32::MAX);
+        let initial_ms = self.initial_backoff.num_milliseconds() as f64;
+        let max_ms = self.max_backoff.num_milliseconds() as f64;
+        let backoff_ms = (initial_ms * self.multiplier.powi(exponent)).min(max_ms);
+
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let factor = 1.0 + jitter * (2.0 * RetryPolicy::unit_hash(seed, attempt) - 1.0);
+        let backoff_ms = (backoff_ms * factor).clamp(0.0, MAX_DURATION_MS as f64);
+        chrono::Duration::milliseconds(backoff_ms as i64)
+    }
+
+    // maps the seed and attempt to a value in [0, 1) using splitmix64
+    fn unit_hash(seed: u64, attempt: u32) -> f64 {
+        let mut x = seed ^ (u64::from(attempt) << 32);
+        x = x.wrapping_add(0x9e3779b97f4a7c15);
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+        x ^= x >> 31;
+        (x >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl TryFrom<proto::exchange::RetryPolicy> for RetryPolicy {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: proto::exchange::RetryPolicy) -> Result<RetryPolicy, Self::Error> {
+        let default = RetryPolicy::default();
+
+        let mut retryable_statuses: Vec<EventStatus> = Vec::new();
+        for status in obj.retryable_statuses {
+            retryable_statuses.push(EventStatus::try_from(status)?);
+        }
+        if retryable_statuses.is_empty() {
+            retryable_statuses = default.retryable_statuses;
+        }
+
+        let initial_backoff = bounded_duration(obj.initial_backoff_ms).ok_or(anyhow::anyhow!(
+            "initial backoff of {} ms exceeds {} ms",
+            obj.initial_backoff_ms,
+            MAX_DURATION_MS
+        ))?;
+        let max_backoff = if obj.max_backoff_ms > 0 {
+            bounded_duration(obj.max_backoff_ms).ok_or(anyhow::anyhow!(
+                "max backoff of {} ms exceeds {} ms",
+                obj.max_backoff_ms,
+                MAX_DURATION_MS
+            ))?
+        } else {
+            default.max_backoff
+        };
+
+        Ok(RetryPolicy {
+            max_attempts: obj.max_attempts.max(1),
+            initial_backoff,
+            max_backoff,
+            multiplier: if obj.multiplier > 0.0 {
+                obj.multiplier
+            } else {
+                default.multiplier
+            },
+            jitter: obj.jitter,
+            retryable_statuses,
+        })
+    }
+}
+
+impl TryFrom<RetryPolicy> for proto::exchange::RetryPolicy {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: RetryPolicy) -> Result<proto::exchange::RetryPolicy, Self::Error> {
+        let mut retryable_statuses: Vec<proto::exchange::Status> = Vec::new();
+        for status in obj.retryable_statuses {
+            retryable_statuses.push(proto::exchange::Status::try_from(status)?);
+        }
+
+        Ok(proto::exchange::RetryPolicy {
+            max_attempts: obj.max_attempts,
+            initial_backoff_ms: u64::try_from(obj.initial_backoff.num_milliseconds())?,
+            max_backoff_ms: u64::try_from(obj.max_backoff.num_milliseconds())?,
+            multiplier: obj.multiplier,
+            jitter: obj.jitter,
+            retryable_statuses,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_grows_until_the_max_backoff() {
+        let retry_policy = RetryPolicy::try_from(proto::exchange::RetryPolicy {
+            max_attempts: 5,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 300,
+            ..Default::default()
+        })
+        .unwrap();
+        let backoffs: Vec<i64> = (1..=4)
+            .map(|attempt| retry_policy.backoff(attempt, 7).num_milliseconds())
+            .collect();
+        assert_eq!(backoffs, vec![100, 200, 300, 300]);
+
+        assert!(retry_policy.should_retry(&EventStatus::Errored, 4));
+        assert!(!retry_policy.should_retry(&EventStatus::Errored, 5));
+        assert!(!retry_policy.should_retry(&EventStatus::Expired, 1));
+    }
+
+    #[test]
+    fn jitter_is_deterministic_and_bounded() {
+        let retry_policy = RetryPolicy::try_from(proto::exchange::RetryPolicy {
+            initial_backoff_ms: 1000,
+            jitter: 0.5,
+            ..Default::default()
+        })
+        .unwrap();
+        for seed in 0..100 {
+            let backoff = retry_policy.backoff(1, seed);
+            assert_eq!(backoff, retry_policy.backoff(1, seed));
+            assert!((500..=1500).contains(&backoff.num_milliseconds()));
+        }
+    }
+
+    #[test]
+    fn backoffs_past_the_bound_are_refused_or_capped() {
+        for retry_policy in [
+            proto::exchange::RetryPolicy {
+                initial_backoff_ms: u64::MAX,
+                ..Default::default()
+            },
+            proto::exchange::RetryPolicy {
+                max_backoff_ms: MAX_DURATION_MS + 1,
+                ..Default::default()
+            },
+        ] {
+            assert!(RetryPolicy::try_from(retry_policy).is_err());
+        }
+
+        let retry_policy = RetryPolicy::try_from(proto::exchange::RetryPolicy {
+            initial_backoff_ms: MAX_DURATION_MS,
+            max_backoff_ms: MAX_DURATION_MS,
+            multiplier: f64::MAX,
+            jitter: 1.0,
+            ..Default::default()
+        })
+        .unwrap();
+        for attempt in [1, 2, u32::MAX] {
+            let backoff_ms = retry_policy.backoff(attempt, 3).num_milliseconds();
+            assert!((0..=MAX_DURATION_MS as i64).contains(&backoff_ms));
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use anyhow::Result;
+use thiserror::Error;
+
+use crate::rpc::proto;
+
+use super::event::Event;
+use super::selector::Selector;
+
+// matches exactly one word of a topic
+const SINGLE_WORD_WILDCARD: &str = "*";
+// matches zero or more words of a topic
+const MULTI_WORD_WILDCARD: &str = "#";
+
+#[derive(Debug, Error)]
+pub enum RoutingError {
+    #[error("invalid topic pattern: {0}")]
+    InvalidTopicPattern(String),
+    #[error("invalid topic: {0}")]
+    InvalidTopic(String),
+}
+
+// routes events published to topics matching the pattern to a queue,
+// similar to the bindings of an AMQP topic exchange
+#[derive(Debug, Clone)]
+pub struct Binding {
+    id: u64,
+    topic_pattern: String,
+    queue_name: String,
+    selector: Selector,
+}
+
+impl Binding {
+    pub fn new(
+        id: u64,
+        topic_pattern: String,
+        queue_name: String,
+        selector: Selector,
+    ) -> Result<Binding> {
+        Binding::validate_topic_pattern(&topic_pattern)?;
+        Ok(Binding {
+            id,
+            topic_pattern,
+            queue_name,
+            selector,
+        })
+    }
+
+    // wildcards have to make up a whole word, "a.*.c" is valid while
+    // "a.b*.c" is not
+    fn validate_topic_pattern(topic_pattern: &str) -> Result<()> {
+        for word in topic_pattern.split('.') {
+            let wildcard = word == SINGLE_WORD_WILDCARD || word == MULTI_WORD_WILDCARD;
+            if word.is_empty()
+                || (!wildcard
+                    && (word.contains(SINGLE_WORD_WILDCARD) || word.contains(MULTI_WORD_WILDCARD)))
+            {
+                return Err(RoutingError::InvalidTopicPattern(topic_pattern.to_string()).into());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn validate_topic(topic: &str) -> Result<()> {
+        if topic.split('.').any(|word| word.is_empty()) {
+            return Err(RoutingError::InvalidTopic(topic.to_string()).into());
+        }
+        Ok(())
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn queue_name(&self) -> &String {
+        &self.queue_name
+    }
+
+    pub fn matches(&self, topic: &str, event: &Event) -> bool {
+        let pattern: Vec<&str> = self.topic_pattern.split('.').collect();
+        let topic: Vec<&str> = topic.split('.').collect();
+        Binding::words_match(&pattern, &topic) && self.selector.matches(event)
+    }
+
+    // matches word by word keeping which topic prefixes the pattern seen
+    // so far matches, so patterns with many # stay linear in the length
+    // of the pattern times the length of the topic
+    fn words_match(pattern: &[&str], topic: &[&str]) -> bool {
+        let mut matched = vec![false; topic.len() + 1];
+        matched[0] = true;
+        for word in pattern {
+            if *word == MULTI_WORD_WILDCARD {
+                for idx in 1..=topic.len() {
+                    matched[idx] = matched[idx] || matched[idx - 1];
+                }
+                continue;
+            }
+            for idx in (1..=topic.len()).rev() {
+                matched[idx] =
+                    matched[idx - 1] && (*word == SINGLE_WORD_WILDCARD || *word == topic[idx - 1]);
+            }
+            matched[0] = false;
+        }
+        matched[topic.len()]
+    }
+}
+
+impl TryFrom<Binding> for proto::exchange::Binding {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: Binding) -> Result<proto::exchange::Binding, Self::Error> {
+        Ok(proto::exchange::Binding {
+            id: obj.id,
+            topic_pattern: obj.topic_pattern,
+            queue_name: obj.queue_name,
+            selector: if obj.selector.is_empty() {
+                None
+            } else {
+                Some(proto::exchange::AttributeSelector::try_from(obj.selector)?)
+            },
+        })
+    }
+}
+
+impl TryFrom<proto::exchange::Binding> for Binding {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: proto::exchange::Binding) -> Result<Binding, Self::Error> {
+        let selector = match obj.selector {
+            Some(selector) => Selector::try_from(selector)?,
+            None => Selector::default(),
+        };
+        Binding::new(obj.id, obj.topic_pattern, obj.queue_name, selector)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn topic_matches(topic_pattern: &str, topic: &str) -> bool {
+        let pattern: Vec<&str> = topic_pattern.split('.').collect();
+        let topic: Vec<&str> = topic.split('.').collect();
+        Binding::words_match(&pattern, &topic)
+    }
+
+    #[test]
+    fn matches_wildcards() {
+        assert!(topic_matches("a.b.c", "a.b.c"));
+        assert!(!topic_matches("a.b.c", "a.b"));
+        assert!(topic_matches("a.*.c", "a.b.c"));
+        assert!(!topic_matches("a.*.c", "a.c"));
+        assert!(topic_matches("a.#", "a"));
+        assert!(topic_matches("a.#", "a.b.c"));
+        assert!(topic_matches("#.c", "a.b.c"));
+        assert!(topic_matches("a.#.c", "a.c"));
+        assert!(!topic_matches("a.#.c", "a.b.d"));
+        assert!(topic_matches("#", "a.b.c"));
+        assert!(topic_matches("*.#.*", "a.b"));
+        assert!(!topic_matches("*.#.*", "a"));
+    }
+
+    #[test]
+    fn patterns_with_many_multi_word_wildcards_match_quickly() {
+        let pattern = vec!["#"; 64].join(".") + ".z";
+        let topic = vec!["a"; 64].join(".");
+        assert!(!topic_matches(&pattern, &topic));
+        assert!(topic_matches(&pattern, &(topic + ".z")));
+    }
+
+    #[test]
+    fn refuses_partial_wildcards() {
+        assert!(Binding::validate_topic_pattern("a.b*.c").is_err());
+        assert!(Binding::validate_topic_pattern("a..c").is_err());
+        assert!(Binding::validate_topic_pattern("a.*.#").is_ok());
+        assert!(Binding::validate_topic("a..c").is_err());
+    }
+}
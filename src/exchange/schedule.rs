@@ -0,0 +1,389 @@
+use anyhow::Result;
+use std::str::FromStr;
+use thiserror::Error;
+use tracing::warn;
+
+use crate::rpc::proto;
+
+use super::event::Event;
+
+// fire times later than this are treated as missed by the skip policy
+const SKIP_GRACE_PERIOD_SECONDS: i64 = 60;
+// upper bound on the fire times materialized at once by the catch-up
+// policy, older fire times past it are dropped
+const MAX_CATCH_UP_FIRES: usize = 1000;
+
+#[derive(Debug, Error)]
+pub enum ScheduleError {
+    #[error("invalid cron expression {expr}: {reason}")]
+    InvalidCronExpr { expr: String, reason: String },
+    #[error("invalid timezone: {0}")]
+    InvalidTimezone(String),
+}
+
+// what to do with fire times which passed while the exchange was down
+#[derive(Debug, Clone, PartialEq)]
+pub enum MissedFirePolicy {
+    // only fire times within the grace period are materialized
+    Skip,
+    // every missed fire time is materialized
+    CatchUp,
+}
+
+// fire times of a schedule which are due, the schedule only advances
+// past them once they were materialized
+#[derive(Debug, Clone)]
+pub struct DueFireTimes {
+    fire_times: Vec<chrono::DateTime<chrono::Utc>>,
+    next_fire_time: Option<chrono::DateTime<chrono::Utc>>,
+    dropped: u64,
+}
+
+impl DueFireTimes {
+    pub fn fire_times(&self) -> &Vec<chrono::DateTime<chrono::Utc>> {
+        &self.fire_times
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    id: u64,
+    queue_name: String,
+    cron_expr: String,
+    timezone: chrono_tz::Tz,
+    cron: cron::Schedule,
+    event_template: Event,
+    missed_fire_policy: MissedFirePolicy,
+
+    next_fire_time: Option<chrono::DateTime<chrono::Utc>>,
+    last_fire_time: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl Schedule {
+    pub fn new(
+        id: u64,
+        queue_name: String,
+        cron_expr: String,
+        timezone: String,
+        event_template: Event,
+        missed_fire_policy: MissedFirePolicy,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Schedule> {
+        let mut schedule = Schedule {
+            id,
+            queue_name,
+            cron: Schedule::parse_cron_expr(&cron_expr)?,
+            cron_expr,
+            timezone: Schedule::parse_timezone(&timezone)?,
+            event_template,
+            missed_fire_policy,
+            next_fire_time: None,
+            last_fire_time: None,
+        };
+        schedule.next_fire_time = schedule.fire_time_after(now);
+        Ok(schedule)
+    }
+
+    // accepts the standard five field format as well as the extended
+    // format with seconds and an optional year
+    fn parse_cron_expr(cron_expr: &str) -> Result<cron::Schedule> {
+        let expr = if cron_expr.split_whitespace().count() == 5 {
+            format!("0 {}", cron_expr)
+        } else {
+            cron_expr.to_string()
+        };
+        match cron::Schedule::from_str(&expr) {
+            Ok(cron) => Ok(cron),
+            Err(err) => Err(ScheduleError::InvalidCronExpr {
+                expr: cron_expr.to_string(),
+                reason: err.to_string(),
+            }
+            .into()),
+        }
+    }
+
+    fn parse_timezone(timezone: &str) -> Result<chrono_tz::Tz> {
+        if timezone.is_empty() {
+            return Ok(chrono_tz::UTC);
+        }
+        match chrono_tz::Tz::from_str(timezone) {
+            Ok(timezone) => Ok(timezone),
+            Err(_) => Err(ScheduleError::InvalidTimezone(timezone.to_string()).into()),
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn queue_name(&self) -> &String {
+        &self.queue_name
+    }
+
+    pub fn event_template(&self) -> &Event {
+        &self.event_template
+    }
+
+    pub fn next_fire_time(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.next_fire_time
+    }
+
+    pub fn is_due(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        self.next_fire_time
+            .is_some_and(|next_fire_time| next_fire_time <= now)
+    }
+
+    // fire times are computed in the schedule's timezone so expressions
+    // like "0 2 * * *" follow daylight saving changes
+    fn fire_time_after(
+        &self,
+        time: chrono::DateTime<chrono::Utc>,
+    ) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.cron
+            .after(&time.with_timezone(&self.timezone))
+            .next()
+            .map(|fire_time| fire_time.with_timezone(&chrono::Utc))
+    }
+
+    // returns the fire times up to now to materialize according to the
+    // missed fire policy, without advancing the schedule
+    pub fn due_fire_times(&self, now: chrono::DateTime<chrono::Utc>) -> DueFireTimes {
+        let mut fire_times: Vec<chrono::DateTime<chrono::Utc>> = Vec::new();
+        let mut dropped: u64 = 0;
+        let mut next_fire_time = self.next_fire_time;
+        while let Some(fire_time) = next_fire_time
+            && fire_time <= now
+        {
+            let missed = now - fire_time > chrono::Duration::seconds(SKIP_GRACE_PERIOD_SECONDS);
+            if self.missed_fire_policy == MissedFirePolicy::Skip && missed {
+                dropped += 1;
+            } else {
+                if fire_times.len() >= MAX_CATCH_UP_FIRES {
+                    fire_times.remove(0);
+                    dropped += 1;
+                }
+                fire_times.push(fire_time);
+            }
+            next_fire_time = self.fire_time_after(fire_time);
+        }
+
+        DueFireTimes {
+            fire_times,
+            next_fire_time,
+            dropped,
+        }
+    }
+
+    // advances the schedule past fire times which were materialized
+    pub fn advance(&mut self, due: DueFireTimes) {
+        if due.dropped > 0 {
+            warn!(
+                schedule_id = self.id,
+                dropped = due.dropped,
+                "dropped missed schedule fire times"
+            );
+        }
+        if let Some(fire_time) = due.fire_times.last() {
+            self.last_fire_time = Some(*fire_time);
+        }
+        self.next_fire_time = due.next_fire_time;
+    }
+}
+
+impl TryFrom<proto::exchange::MissedFirePolicy> for MissedFirePolicy {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: proto::exchange::MissedFirePolicy) -> Result<MissedFirePolicy, Self::Error> {
+        match obj {
+            proto::exchange::MissedFirePolicy::Unspecified
+            | proto::exchange::MissedFirePolicy::Skip => Ok(MissedFirePolicy::Skip),
+            proto::exchange::MissedFirePolicy::CatchUp => Ok(MissedFirePolicy::CatchUp),
+        }
+    }
+}
+
+impl TryFrom<MissedFirePolicy> for proto::exchange::MissedFirePolicy {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: MissedFirePolicy) -> Result<proto::exchange::MissedFirePolicy, Self::Error> {
+        match obj {
+            MissedFirePolicy::Skip => Ok(proto::exchange::MissedFirePolicy::Skip),
+            MissedFirePolicy::CatchUp => Ok(proto::exchange::MissedFirePolicy::CatchUp),
+        }
+    }
+}
+
+impl TryFrom<Schedule> for proto::exchange::Schedule {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: Schedule) -> Result<proto::exchange::Schedule, Self::Error> {
+        Ok(proto::exchange::Schedule {
+            id: obj.id,
+            queue_name: obj.queue_name,
+            cron_expr: obj.cron_expr,
+            timezone: obj.timezone.name().to_string(),
+            event_template: Some(proto::exchange::Event::try_from(obj.event_template)?),
+            missed_fire_policy: proto::exchange::MissedFirePolicy::try_from(obj.missed_fire_policy)?
+                as i32,
+            next_fire_time: obj
+                .next_fire_time
+                .map(|next_fire_time| next_fire_time.timestamp_millis()),
+            last_fire_time: obj
+                .last_fire_time
+                .map(|last_fire_time| last_fire_time.timestamp_millis()),
+        })
+    }
+}
+
+impl TryFrom<proto::exchange::Schedule> for Schedule {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: proto::exchange::Schedule) -> Result<Schedule, Self::Error> {
+        let event_template = Event::try_from(
+            obj.event_template
+                .ok_or(anyhow::anyhow!("event template was None"))?,
+        )?;
+        let missed_fire_policy = MissedFirePolicy::try_from(
+            proto::exchange::MissedFirePolicy::try_from(obj.missed_fire_policy)?,
+        )?;
+        let next_fire_time = match obj.next_fire_time {
+            Some(next_fire_time) => Some(
+                chrono::DateTime::from_timestamp_millis(next_fire_time).ok_or(anyhow::anyhow!(
+                    "invalid next fire time: {}",
+                    next_fire_time
+                ))?,
+            ),
+            None => None,
+        };
+        let last_fire_time = match obj.last_fire_time {
+            Some(last_fire_time) => Some(
+                chrono::DateTime::from_timestamp_millis(last_fire_time).ok_or(anyhow::anyhow!(
+                    "invalid last fire time: {}",
+                    last_fire_time
+                ))?,
+            ),
+            None => None,
+        };
+
+        Ok(Schedule {
+            id: obj.id,
+            queue_name: obj.queue_name,
+            cron: Schedule::parse_cron_expr(&obj.cron_expr)?,
+            cron_expr: obj.cron_expr,
+            timezone: Schedule::parse_timezone(&obj.timezone)?,
+            event_template,
+            missed_fire_policy,
+            next_fire_time,
+            last_fire_time,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_schedule(
+        cron_expr: &str,
+        timezone: &str,
+        missed_fire_policy: MissedFirePolicy,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Schedule {
+        let event_template = Event::try_from(proto::exchange::Event::default()).unwrap();
+        Schedule::new(
+            0,
+            "test".to_string(),
+            cron_expr.to_string(),
+            timezone.to_string(),
+            event_template,
+            missed_fire_policy,
+            now,
+        )
+        .unwrap()
+    }
+
+    fn time(rfc3339: &str) -> chrono::DateTime<chrono::Utc> {
+        chrono::DateTime::parse_from_rfc3339(rfc3339)
+            .unwrap()
+            .with_timezone(&chrono::Utc)
+    }
+
+    #[test]
+    fn catch_up_materializes_every_missed_fire_time() {
+        let mut schedule = new_schedule(
+            "* * * * *",
+            "",
+            MissedFirePolicy::CatchUp,
+            time("2024-01-01T00:00:00Z"),
+        );
+        let now = time("2024-01-01T00:10:00Z");
+        let due = schedule.due_fire_times(now);
+        assert_eq!(due.fire_times().len(), 10);
+        assert_eq!(due.fire_times()[0], time("2024-01-01T00:01:00Z"));
+
+        schedule.advance(due);
+        assert!(!schedule.is_due(now));
+        assert_eq!(
+            schedule.next_fire_time(),
+            Some(time("2024-01-01T00:11:00Z"))
+        );
+    }
+
+    #[test]
+    fn skip_only_materializes_fire_times_within_the_grace_period() {
+        let mut schedule = new_schedule(
+            "* * * * *",
+            "",
+            MissedFirePolicy::Skip,
+            time("2024-01-01T00:00:00Z"),
+        );
+        let now = time("2024-01-01T00:10:00Z");
+        let due = schedule.due_fire_times(now);
+        assert_eq!(
+            due.fire_times(),
+            &vec![time("2024-01-01T00:09:00Z"), time("2024-01-01T00:10:00Z")]
+        );
+
+        schedule.advance(due);
+        assert_eq!(
+            schedule.next_fire_time(),
+            Some(time("2024-01-01T00:11:00Z"))
+        );
+    }
+
+    #[test]
+    fn catch_up_keeps_the_latest_fire_times_past_the_bound() {
+        let schedule = new_schedule(
+            "* * * * * *",
+            "",
+            MissedFirePolicy::CatchUp,
+            time("2024-01-01T00:00:00Z"),
+        );
+        let due = schedule.due_fire_times(time("2024-01-01T01:00:00Z"));
+        assert_eq!(due.fire_times().len(), MAX_CATCH_UP_FIRES);
+        assert_eq!(due.fire_times().last(), Some(&time("2024-01-01T01:00:00Z")));
+    }
+
+    #[test]
+    fn fire_times_follow_the_timezone() {
+        // 02:00 does not exist in Berlin on the day clocks move forward
+        let schedule = new_schedule(
+            "0 2 * * *",
+            "Europe/Berlin",
+            MissedFirePolicy::Skip,
+            time("2024-03-30T12:00:00Z"),
+        );
+        assert_eq!(
+            schedule.next_fire_time(),
+            Some(time("2024-04-01T00:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn refuses_invalid_expressions_and_timezones() {
+        assert!(Schedule::parse_cron_expr("* * *").is_err());
+        assert!(Schedule::parse_cron_expr("0 0 * * * * 2030").is_ok());
+        assert!(Schedule::parse_timezone("Mars/Olympus").is_err());
+        assert_eq!(Schedule::parse_timezone("").unwrap(), chrono_tz::UTC);
+    }
+}
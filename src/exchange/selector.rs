@@ -0,0 +1,162 @@
+use anyhow::Result;
+use std::collections;
+use thiserror::Error;
+
+use crate::rpc::proto;
+
+use super::event::Event;
+
+#[derive(Debug, Error)]
+pub enum SelectorError {
+    #[error("attribute matcher is missing a name")]
+    MissingName,
+    #[error("attribute matcher for {0} is missing a match type")]
+    MissingMatchType(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MatchType {
+    Equals(String),
+    OneOf(collections::BTreeSet<String>),
+    Exists,
+    Prefix(String),
+}
+
+// satisfied when any attribute of the event with the name matches
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeMatcher {
+    name: String,
+    match_type: MatchType,
+}
+
+impl AttributeMatcher {
+    pub fn new(name: String, match_type: MatchType) -> AttributeMatcher {
+        AttributeMatcher { name, match_type }
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn match_type(&self) -> &MatchType {
+        &self.match_type
+    }
+
+    pub fn matches_value(&self, value: &str) -> bool {
+        match &self.match_type {
+            MatchType::Equals(expected) => value == expected,
+            MatchType::OneOf(values) => values.contains(value),
+            MatchType::Exists => true,
+            MatchType::Prefix(prefix) => value.starts_with(prefix.as_str()),
+        }
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        event.attributes().iter().any(|attribute| {
+            *attribute.name() == self.name && self.matches_value(attribute.value())
+        })
+    }
+}
+
+// matches events satisfying every matcher, the empty selector matches
+// every event
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Selector {
+    matchers: Vec<AttributeMatcher>,
+}
+
+impl Selector {
+    pub fn new(matchers: Vec<AttributeMatcher>) -> Selector {
+        Selector { matchers }
+    }
+
+    pub fn matchers(&self) -> &Vec<AttributeMatcher> {
+        &self.matchers
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.matchers.is_empty()
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        self.matchers.iter().all(|matcher| matcher.matches(event))
+    }
+}
+
+impl TryFrom<proto::exchange::AttributeMatcher> for AttributeMatcher {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: proto::exchange::AttributeMatcher) -> Result<AttributeMatcher, Self::Error> {
+        if obj.name.is_empty() {
+            return Err(SelectorError::MissingName.into());
+        }
+        let match_type = match obj.match_type {
+            Some(proto::exchange::attribute_matcher::MatchType::Equals(value)) => {
+                MatchType::Equals(value)
+            }
+            Some(proto::exchange::attribute_matcher::MatchType::OneOf(values)) => {
+                MatchType::OneOf(values.values.into_iter().collect())
+            }
+            Some(proto::exchange::attribute_matcher::MatchType::Exists(_)) => MatchType::Exists,
+            Some(proto::exchange::attribute_matcher::MatchType::Prefix(prefix)) => {
+                MatchType::Prefix(prefix)
+            }
+            None => return Err(SelectorError::MissingMatchType(obj.name).into()),
+        };
+        Ok(AttributeMatcher {
+            name: obj.name,
+            match_type,
+        })
+    }
+}
+
+impl TryFrom<AttributeMatcher> for proto::exchange::AttributeMatcher {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: AttributeMatcher) -> Result<proto::exchange::AttributeMatcher, Self::Error> {
+        let match_type = match obj.match_type {
+            MatchType::Equals(value) => {
+                proto::exchange::attribute_matcher::MatchType::Equals(value)
+            }
+            MatchType::OneOf(values) => proto::exchange::attribute_matcher::MatchType::OneOf(
+                proto::exchange::AttributeValues {
+                    values: values.into_iter().collect(),
+                },
+            ),
+            MatchType::Exists => {
+                proto::exchange::attribute_matcher::MatchType::Exists(proto::exchange::Exists {})
+            }
+            MatchType::Prefix(prefix) => {
+                proto::exchange::attribute_matcher::MatchType::Prefix(prefix)
+            }
+        };
+        Ok(proto::exchange::AttributeMatcher {
+            name: obj.name,
+            match_type: Some(match_type),
+        })
+    }
+}
+
+impl TryFrom<proto::exchange::AttributeSelector> for Selector {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: proto::exchange::AttributeSelector) -> Result<Selector, Self::Error> {
+        let mut matchers: Vec<AttributeMatcher> = Vec::new();
+        for matcher in obj.matchers {
+            matchers.push(AttributeMatcher::try_from(matcher)?);
+        }
+        Ok(Selector { matchers })
+    }
+}
+
+impl TryFrom<Selector> for proto::exchange::AttributeSelector {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: Selector) -> Result<proto::exchange::AttributeSelector, Self::Error> {
+        let mut matchers: Vec<proto::exchange::AttributeMatcher> = Vec::new();
+        for matcher in obj.matchers {
+            matchers.push(proto::exchange::AttributeMatcher::try_from(matcher)?);
+        }
+        Ok(proto::exchange::AttributeSelector { matchers })
+    }
+}
@@ -0,0 +1,78 @@
+use std::collections;
+use tokio::sync::mpsc;
+
+use crate::rpc::proto;
+
+use super::selector::Selector;
+
+#[derive(Debug, Clone)]
+pub struct Subscriber {
+    id: u64,
+    worker_id: String,
+    prefetch: u64,
+    // only events matching the selector are pushed to the subscriber
+    selector: Selector,
+
+    // events pushed to the subscriber which have not been acknowledged yet
+    in_flight: collections::HashSet<u64>,
+    sender: mpsc::Sender<proto::exchange::Event>,
+}
+
+impl Subscriber {
+    pub fn new(
+        id: u64,
+        worker_id: String,
+        prefetch: u64,
+        selector: Selector,
+        sender: mpsc::Sender<proto::exchange::Event>,
+    ) -> Subscriber {
+        Subscriber {
+            id,
+            worker_id,
+            prefetch: prefetch.max(1),
+            selector,
+            in_flight: collections::HashSet::new(),
+            sender,
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn worker_id(&self) -> &String {
+        &self.worker_id
+    }
+
+    pub fn selector(&self) -> &Selector {
+        &self.selector
+    }
+
+    pub fn has_capacity(&self) -> bool {
+        (self.in_flight.len() as u64) < self.prefetch
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
+
+    // returns false if the stream has been dropped and the event
+    // was not delivered
+    pub fn push(&mut self, event: proto::exchange::Event) -> bool {
+        let event_id = event.id;
+        if self.sender.try_send(event).is_err() {
+            return false;
+        }
+        self.in_flight.insert(event_id);
+        true
+    }
+
+    // returns true if the event was in-flight for this subscriber
+    pub fn release(&mut self, event_id: &u64) -> bool {
+        self.in_flight.remove(event_id)
+    }
+
+    pub fn take_in_flight(&mut self) -> Vec<u64> {
+        self.in_flight.drain().collect()
+    }
+}
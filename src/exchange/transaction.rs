@@ -1,8 +1,21 @@
-use crate::{exchange::event::EventStatus, rpc::proto};
+use crate::{
+    exchange::{event::EventStatus, exchange::ErrorCode},
+    rpc::proto,
+};
 
-use super::event::Event;
+use super::{
+    dead_letter::{DeadLetter, DeadLetterConfig},
+    event::{Event, MAX_DURATION_MS, bounded_duration},
+    join::{JoinCondition, JoinTrigger},
+    queue::{QueueConfig, QueueDescription},
+    retry::RetryPolicy,
+    routing::Binding,
+    schedule::{MissedFirePolicy, Schedule},
+    selector::Selector,
+    workflow::{Workflow, WorkflowEdge, WorkflowNode},
+};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Transaction {
     id: u64,
     event_id: u64,
@@ -40,16 +53,122 @@ impl Transaction {
         }
     }
 
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn event_id(&self) -> u64 {
+        self.event_id
+    }
+
     pub fn update_heartbeat(&mut self) {
         self.last_heartbeat_time = Some(chrono::Utc::now());
     }
 
+    pub fn clear_heartbeat(&mut self) {
+        self.last_heartbeat_time = None;
+    }
+
+    pub fn last_heartbeat_time(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.last_heartbeat_time
+    }
+
     pub fn get_command_triggers(&self) -> &Vec<CommandTrigger> {
         &self.command_triggers
     }
 }
 
-#[derive(Debug)]
+impl TryFrom<&Transaction> for proto::storage::TransactionState {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: &Transaction) -> Result<Self, Self::Error> {
+        let mut command_triggers: Vec<proto::storage::CommandTriggerState> = Vec::new();
+        for command_trigger in obj.command_triggers.iter() {
+            let mut commands: Vec<proto::exchange::Command> = Vec::new();
+            for command in command_trigger.commands.iter() {
+                commands.push(proto::exchange::Command::try_from(command.clone())?);
+            }
+            command_triggers.push(proto::storage::CommandTriggerState {
+                trigger: Some(proto::exchange::Trigger::try_from(
+                    command_trigger.trigger.clone(),
+                )?),
+                commands,
+            });
+        }
+
+        Ok(proto::storage::TransactionState {
+            id: obj.id,
+            event_id: obj.event_id,
+            command_triggers,
+            last_heartbeat_time: obj.last_heartbeat_time.map(|time| time.timestamp_millis()),
+        })
+    }
+}
+
+impl TryFrom<&Transaction> for proto::exchange::Transaction {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: &Transaction) -> Result<Self, Self::Error> {
+        let mut command_triggers: Vec<proto::exchange::CommandTrigger> = Vec::new();
+        for command_trigger in obj.command_triggers.iter() {
+            let mut commands: Vec<proto::exchange::Command> = Vec::new();
+            for command in command_trigger.commands.iter() {
+                commands.push(proto::exchange::Command::try_from(command.clone())?);
+            }
+            command_triggers.push(proto::exchange::CommandTrigger {
+                trigger: Some(proto::exchange::Trigger::try_from(
+                    command_trigger.trigger.clone(),
+                )?),
+                commands,
+            });
+        }
+
+        Ok(proto::exchange::Transaction {
+            id: obj.id,
+            event_id: obj.event_id,
+            command_triggers,
+            last_heartbeat_time: obj.last_heartbeat_time.map(|time| time.timestamp_millis()),
+        })
+    }
+}
+
+impl TryFrom<proto::storage::TransactionState> for Transaction {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: proto::storage::TransactionState) -> Result<Self, Self::Error> {
+        let mut command_triggers: Vec<CommandTrigger> = Vec::new();
+        for command_trigger in obj.command_triggers {
+            let trigger = Trigger::new(
+                obj.event_id,
+                command_trigger
+                    .trigger
+                    .ok_or(anyhow::anyhow!("trigger was None"))?,
+            )?;
+            let mut commands: Vec<Command> = Vec::new();
+            for command in command_trigger.commands {
+                commands.push(Command::try_from(command)?);
+            }
+            command_triggers.push(CommandTrigger { trigger, commands });
+        }
+
+        let last_heartbeat_time = match obj.last_heartbeat_time {
+            Some(time) => Some(
+                chrono::DateTime::from_timestamp_millis(time)
+                    .ok_or(anyhow::anyhow!("invalid heartbeat time: {}", time))?,
+            ),
+            None => None,
+        };
+
+        Ok(Transaction {
+            id: obj.id,
+            event_id: obj.event_id,
+            command_triggers,
+            last_heartbeat_time,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct CommandTrigger {
     trigger: Trigger,
     commands: Vec<Command>,
@@ -64,6 +183,7 @@ impl CommandTrigger {
                 true
             }
             Trigger::OnEventTimedout(_) if event_status == EventStatus::Timedout => true,
+            Trigger::OnEventExpired(_) if event_status == EventStatus::Expired => true,
             _ => false,
         }
     }
@@ -73,26 +193,99 @@ impl CommandTrigger {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Trigger {
     OnEventComplete(u64),
     OnEventError(u64),
     OnEventMissedHeartbeat(u64),
     OnEventTimedout(u64),
+    OnEventExpired(u64),
 }
 
-#[derive(Debug)]
+impl Trigger {
+    // the trigger on the event reacting to the final status, None for
+    // statuses which are not final
+    pub fn on_status(event_id: u64, status: &EventStatus) -> Option<Trigger> {
+        match status {
+            EventStatus::Complete => Some(Trigger::OnEventComplete(event_id)),
+            EventStatus::Errored => Some(Trigger::OnEventError(event_id)),
+            EventStatus::MissedHeartbeat => Some(Trigger::OnEventMissedHeartbeat(event_id)),
+            EventStatus::Timedout => Some(Trigger::OnEventTimedout(event_id)),
+            EventStatus::Expired => Some(Trigger::OnEventExpired(event_id)),
+            EventStatus::Queued | EventStatus::Running => None,
+        }
+    }
+
+    pub fn new(event_id: u64, obj: proto::exchange::Trigger) -> Result<Trigger, anyhow::Error> {
+        match obj.trigger_type {
+            Some(proto::exchange::trigger::TriggerType::OnEventComplete(_)) => {
+                Ok(Trigger::OnEventComplete(event_id))
+            }
+            Some(proto::exchange::trigger::TriggerType::OnEventError(_)) => {
+                Ok(Trigger::OnEventError(event_id))
+            }
+            Some(proto::exchange::trigger::TriggerType::OnEventMissedHeartbeat(_)) => {
+                Ok(Trigger::OnEventMissedHeartbeat(event_id))
+            }
+            Some(proto::exchange::trigger::TriggerType::OnEventTimedout(_)) => {
+                Ok(Trigger::OnEventTimedout(event_id))
+            }
+            Some(proto::exchange::trigger::TriggerType::OnEventExpired(_)) => {
+                Ok(Trigger::OnEventExpired(event_id))
+            }
+            None => Err(anyhow::anyhow!("received None")),
+        }
+    }
+}
+
+impl TryFrom<Trigger> for proto::exchange::Trigger {
+    type Error = anyhow::Error;
+
+    fn try_from(value: Trigger) -> Result<Self, Self::Error> {
+        let trigger_type = match value {
+            Trigger::OnEventComplete(_) => proto::exchange::trigger::TriggerType::OnEventComplete(
+                proto::exchange::OnEventComplete {},
+            ),
+            Trigger::OnEventError(_) => proto::exchange::trigger::TriggerType::OnEventError(
+                proto::exchange::OnEventError {},
+            ),
+            Trigger::OnEventMissedHeartbeat(_) => {
+                proto::exchange::trigger::TriggerType::OnEventMissedHeartbeat(
+                    proto::exchange::OnEventMissedHeartbeat {},
+                )
+            }
+            Trigger::OnEventTimedout(_) => proto::exchange::trigger::TriggerType::OnEventTimedout(
+                proto::exchange::OnEventTimedout {},
+            ),
+            Trigger::OnEventExpired(_) => proto::exchange::trigger::TriggerType::OnEventExpired(
+                proto::exchange::OnEventExpired {},
+            ),
+        };
+        Ok(proto::exchange::Trigger {
+            trigger_type: Some(trigger_type),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum Command {
     AddQueue {
         name: String,
+        config: QueueConfig,
+        dead_letter: Option<DeadLetterConfig>,
+        priority_aging: Option<chrono::Duration>,
     },
     AddEvent {
         queue_name: String,
         event: Event,
+        delay: Option<chrono::Duration>,
+        dedup_key: Option<String>,
     },
     AddEvents {
         queue_name: String,
         events: Vec<Event>,
+        delay: Option<chrono::Duration>,
+        dedup_key: Option<String>,
     },
     UpdateEventStatus {
         queue_name: String,
@@ -103,15 +296,296 @@ pub enum Command {
         queue_name: String,
         event_id: u64,
     },
+    ClaimEvents {
+        queue_name: String,
+        max: u64,
+        worker_id: String,
+        selector: Selector,
+    },
+    Heartbeat {
+        queue_name: String,
+        event_id: u64,
+    },
+    AddCommandTrigger {
+        queue_name: String,
+        event_id: u64,
+        trigger: Trigger,
+        command: Box<Command>,
+    },
+    ListDeadLetters {
+        queue_name: String,
+        after_event_id: Option<u64>,
+        limit: u64,
+    },
+    GetDeadLetter {
+        queue_name: String,
+        event_id: u64,
+    },
+    ReplayDeadLetters {
+        queue_name: String,
+        event_ids: Vec<u64>,
+    },
+    PurgeDeadLetters {
+        queue_name: String,
+        event_ids: Vec<u64>,
+    },
+    AddSchedule {
+        queue_name: String,
+        cron_expr: String,
+        timezone: String,
+        event_template: Event,
+        missed_fire_policy: MissedFirePolicy,
+    },
+    RemoveSchedule {
+        id: u64,
+    },
+    ListSchedules {
+        queue_name: String,
+    },
+    FireSchedule {
+        id: u64,
+    },
+    AddBinding {
+        topic_pattern: String,
+        queue_name: String,
+        selector: Selector,
+    },
+    RemoveBinding {
+        id: u64,
+    },
+    ListBindings {
+        queue_name: String,
+    },
+    Publish {
+        topic: String,
+        event: Event,
+    },
+    SubmitWorkflow {
+        name: String,
+        nodes: Vec<WorkflowNode>,
+        edges: Vec<WorkflowEdge>,
+    },
+    GetWorkflow {
+        id: u64,
+    },
+    AdvanceWorkflow {
+        workflow_id: u64,
+        node: String,
+        status: EventStatus,
+    },
+    AddJoinTrigger {
+        // queue name and event id of each event
+        events: Vec<(String, u64)>,
+        condition: JoinCondition,
+        commands: Vec<Command>,
+    },
+    RemoveJoinTrigger {
+        id: u64,
+    },
+    GetJoinTrigger {
+        id: u64,
+    },
+    GetTransaction {
+        queue_name: String,
+        transaction_id: u64,
+    },
+    ListTransactions {
+        queue_name: String,
+    },
+    AbortTransaction {
+        queue_name: String,
+        transaction_id: u64,
+    },
+    GetEvent {
+        queue_name: String,
+        event_id: u64,
+    },
+    ListEvents {
+        queue_name: String,
+        status: Option<EventStatus>,
+        after_event_id: Option<u64>,
+        limit: u64,
+    },
+    ListQueues {},
+    DescribeQueue {
+        queue_name: String,
+    },
+    DeleteQueue {
+        queue_name: String,
+        refuse_if_running: bool,
+    },
+    PurgeQueue {
+        queue_name: String,
+        status: Option<EventStatus>,
+    },
+    PauseQueue {
+        queue_name: String,
+    },
+    ResumeQueue {
+        queue_name: String,
+    },
+    UpdateQueueConfig {
+        queue_name: String,
+        config: QueueConfig,
+    },
+    DeliverEvent {
+        queue_name: String,
+        event_id: u64,
+        worker_id: String,
+    },
+}
+
+impl Command {
+    // read-only commands leave the state untouched and are not written
+    // to the write-ahead log
+    pub fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            Command::ListDeadLetters { .. }
+                | Command::GetDeadLetter { .. }
+                | Command::ListSchedules { .. }
+                | Command::ListBindings { .. }
+                | Command::GetWorkflow { .. }
+                | Command::GetJoinTrigger { .. }
+                | Command::GetTransaction { .. }
+                | Command::ListTransactions { .. }
+                | Command::GetEvent { .. }
+                | Command::ListEvents { .. }
+                | Command::ListQueues { .. }
+                | Command::DescribeQueue { .. }
+        )
+    }
+
+    // internal commands are only written to the write-ahead log by the
+    // exchange itself and rejected when sent by clients, including as the
+    // commands of triggers
+    pub fn is_internal(&self) -> bool {
+        match self {
+            Command::DeliverEvent { .. }
+            | Command::FireSchedule { .. }
+            | Command::AdvanceWorkflow { .. } => true,
+            Command::AddCommandTrigger { command, .. } => command.is_internal(),
+            Command::AddJoinTrigger { commands, .. } => {
+                commands.iter().any(|command| command.is_internal())
+            }
+            _ => false,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum CommandResp {
     AddQueue {},
-    AddEvent { id: u64 },
-    AddEvents { ids: Vec<u64> },
+    AddEvent {
+        id: u64,
+        dead_lettered: bool,
+    },
+    AddEvents {
+        ids: Vec<u64>,
+        dead_lettered_ids: Vec<u64>,
+    },
     UpdateEventStatus {},
-    CreateTransaction { id: u64 },
+    CreateTransaction {
+        id: u64,
+    },
+    ClaimEvents {
+        events: Vec<Event>,
+    },
+    Heartbeat {},
+    AddCommandTrigger {
+        transaction_id: u64,
+    },
+    ListDeadLetters {
+        dead_letters: Vec<DeadLetter>,
+    },
+    GetDeadLetter {
+        dead_letter: Box<DeadLetter>,
+    },
+    ReplayDeadLetters {
+        event_ids: Vec<u64>,
+    },
+    PurgeDeadLetters {
+        purged: u64,
+    },
+    AddSchedule {
+        id: u64,
+        next_fire_time: Option<chrono::DateTime<chrono::Utc>>,
+    },
+    RemoveSchedule {},
+    ListSchedules {
+        schedules: Vec<Schedule>,
+    },
+    FireSchedule {
+        event_ids: Vec<u64>,
+    },
+    AddBinding {
+        id: u64,
+    },
+    RemoveBinding {},
+    ListBindings {
+        bindings: Vec<Binding>,
+    },
+    // the queue and id of every copy of the published event, and whether
+    // the copy was dead-lettered
+    Publish {
+        events: Vec<(String, u64, bool)>,
+    },
+    SubmitWorkflow {
+        id: u64,
+    },
+    GetWorkflow {
+        workflow: Box<Workflow>,
+    },
+    AdvanceWorkflow {
+        event_ids: Vec<u64>,
+    },
+    AddJoinTrigger {
+        id: u64,
+    },
+    RemoveJoinTrigger {},
+    GetJoinTrigger {
+        join_trigger: Box<JoinTrigger>,
+    },
+    GetTransaction {
+        transaction: Transaction,
+    },
+    ListTransactions {
+        transactions: Vec<Transaction>,
+    },
+    AbortTransaction {},
+    GetEvent {
+        event: Box<Event>,
+    },
+    ListEvents {
+        events: Vec<Event>,
+    },
+    ListQueues {
+        names: Vec<String>,
+    },
+    DescribeQueue {
+        description: Box<QueueDescription>,
+    },
+    DeleteQueue {},
+    PurgeQueue {
+        purged: u64,
+    },
+    PauseQueue {},
+    ResumeQueue {},
+    UpdateQueueConfig {},
+    DeliverEvent {},
+    Error {
+        code: ErrorCode,
+        error_msg: String,
+    },
+}
+
+impl CommandResp {
+    pub fn from_error(err: &anyhow::Error) -> CommandResp {
+        CommandResp::Error {
+            code: ErrorCode::from_error(err),
+            error_msg: err.to_string(),
+        }
+    }
 }
 
 impl TryFrom<CommandResp> for proto::exchange::CommandResp {
@@ -124,14 +598,20 @@ impl TryFrom<CommandResp> for proto::exchange::CommandResp {
                     proto::exchange::AddQueueResp {},
                 )),
             }),
-            CommandResp::AddEvent { id } => Ok(proto::exchange::CommandResp {
+            CommandResp::AddEvent { id, dead_lettered } => Ok(proto::exchange::CommandResp {
                 command_resp: Some(proto::exchange::command_resp::CommandResp::AddEventResp(
-                    proto::exchange::AddEventResp { id },
+                    proto::exchange::AddEventResp { id, dead_lettered },
                 )),
             }),
-            CommandResp::AddEvents { ids } => Ok(proto::exchange::CommandResp {
+            CommandResp::AddEvents {
+                ids,
+                dead_lettered_ids,
+            } => Ok(proto::exchange::CommandResp {
                 command_resp: Some(proto::exchange::command_resp::CommandResp::AddEventsResp(
-                    proto::exchange::AddEventsResp { ids },
+                    proto::exchange::AddEventsResp {
+                        ids,
+                        dead_lettered_ids,
+                    },
                 )),
             }),
             CommandResp::UpdateEventStatus {} => Ok(proto::exchange::CommandResp {
@@ -148,23 +628,726 @@ impl TryFrom<CommandResp> for proto::exchange::CommandResp {
                     ),
                 ),
             }),
+            CommandResp::ClaimEvents { events } => {
+                let mut proto_events: Vec<proto::exchange::Event> = Vec::new();
+                for event in events {
+                    proto_events.push(proto::exchange::Event::try_from(event)?);
+                }
+                Ok(proto::exchange::CommandResp {
+                    command_resp: Some(
+                        proto::exchange::command_resp::CommandResp::ClaimEventsResp(
+                            proto::exchange::ClaimEventsResp {
+                                events: proto_events,
+                            },
+                        ),
+                    ),
+                })
+            }
+            CommandResp::Heartbeat {} => Ok(proto::exchange::CommandResp {
+                command_resp: Some(proto::exchange::command_resp::CommandResp::HeartbeatResp(
+                    proto::exchange::HeartbeatResp {},
+                )),
+            }),
+            CommandResp::AddCommandTrigger { transaction_id } => Ok(proto::exchange::CommandResp {
+                command_resp: Some(
+                    proto::exchange::command_resp::CommandResp::AddCommandTriggerResp(
+                        proto::exchange::AddCommandTriggerResp { transaction_id },
+                    ),
+                ),
+            }),
+            CommandResp::ListDeadLetters { dead_letters } => {
+                let mut proto_dead_letters: Vec<proto::exchange::DeadLetter> = Vec::new();
+                for dead_letter in dead_letters {
+                    proto_dead_letters.push(proto::exchange::DeadLetter::try_from(dead_letter)?);
+                }
+                Ok(proto::exchange::CommandResp {
+                    command_resp: Some(
+                        proto::exchange::command_resp::CommandResp::ListDeadLettersResp(
+                            proto::exchange::ListDeadLettersResp {
+                                dead_letters: proto_dead_letters,
+                            },
+                        ),
+                    ),
+                })
+            }
+            CommandResp::GetDeadLetter { dead_letter } => Ok(proto::exchange::CommandResp {
+                command_resp: Some(
+                    proto::exchange::command_resp::CommandResp::GetDeadLetterResp(
+                        proto::exchange::GetDeadLetterResp {
+                            dead_letter: Some(proto::exchange::DeadLetter::try_from(*dead_letter)?),
+                        },
+                    ),
+                ),
+            }),
+            CommandResp::ReplayDeadLetters { event_ids } => Ok(proto::exchange::CommandResp {
+                command_resp: Some(
+                    proto::exchange::command_resp::CommandResp::ReplayDeadLettersResp(
+                        proto::exchange::ReplayDeadLettersResp { event_ids },
+                    ),
+                ),
+            }),
+            CommandResp::PurgeDeadLetters { purged } => Ok(proto::exchange::CommandResp {
+                command_resp: Some(
+                    proto::exchange::command_resp::CommandResp::PurgeDeadLettersResp(
+                        proto::exchange::PurgeDeadLettersResp { purged },
+                    ),
+                ),
+            }),
+            CommandResp::AddSchedule { id, next_fire_time } => Ok(proto::exchange::CommandResp {
+                command_resp: Some(proto::exchange::command_resp::CommandResp::AddScheduleResp(
+                    proto::exchange::AddScheduleResp {
+                        id,
+                        next_fire_time: next_fire_time
+                            .map(|next_fire_time| next_fire_time.timestamp_millis()),
+                    },
+                )),
+            }),
+            CommandResp::RemoveSchedule {} => Ok(proto::exchange::CommandResp {
+                command_resp: Some(
+                    proto::exchange::command_resp::CommandResp::RemoveScheduleResp(
+                        proto::exchange::RemoveScheduleResp {},
+                    ),
+                ),
+            }),
+            CommandResp::ListSchedules { schedules } => {
+                let mut proto_schedules: Vec<proto::exchange::Schedule> = Vec::new();
+                for schedule in schedules {
+                    proto_schedules.push(proto::exchange::Schedule::try_from(schedule)?);
+                }
+                Ok(proto::exchange::CommandResp {
+                    command_resp: Some(
+                        proto::exchange::command_resp::CommandResp::ListSchedulesResp(
+                            proto::exchange::ListSchedulesResp {
+                                schedules: proto_schedules,
+                            },
+                        ),
+                    ),
+                })
+            }
+            CommandResp::FireSchedule { event_ids } => Ok(proto::exchange::CommandResp {
+                command_resp: Some(
+                    proto::exchange::command_resp::CommandResp::FireScheduleResp(
+                        proto::exchange::FireScheduleResp { event_ids },
+                    ),
+                ),
+            }),
+            CommandResp::AddBinding { id } => Ok(proto::exchange::CommandResp {
+                command_resp: Some(proto::exchange::command_resp::CommandResp::AddBindingResp(
+                    proto::exchange::AddBindingResp { id },
+                )),
+            }),
+            CommandResp::RemoveBinding {} => Ok(proto::exchange::CommandResp {
+                command_resp: Some(
+                    proto::exchange::command_resp::CommandResp::RemoveBindingResp(
+                        proto::exchange::RemoveBindingResp {},
+                    ),
+                ),
+            }),
+            CommandResp::ListBindings { bindings } => {
+                let mut proto_bindings: Vec<proto::exchange::Binding> = Vec::new();
+                for binding in bindings {
+                    proto_bindings.push(proto::exchange::Binding::try_from(binding)?);
+                }
+                Ok(proto::exchange::CommandResp {
+                    command_resp: Some(
+                        proto::exchange::command_resp::CommandResp::ListBindingsResp(
+                            proto::exchange::ListBindingsResp {
+                                bindings: proto_bindings,
+                            },
+                        ),
+                    ),
+                })
+            }
+            CommandResp::Publish { events } => Ok(proto::exchange::CommandResp {
+                command_resp: Some(proto::exchange::command_resp::CommandResp::PublishResp(
+                    proto::exchange::PublishResp {
+                        events: events
+                            .into_iter()
+                            .map(
+                                |(queue_name, id, dead_lettered)| proto::exchange::RoutedEvent {
+                                    queue_name,
+                                    id,
+                                    dead_lettered,
+                                },
+                            )
+                            .collect(),
+                    },
+                )),
+            }),
+            CommandResp::SubmitWorkflow { id } => Ok(proto::exchange::CommandResp {
+                command_resp: Some(
+                    proto::exchange::command_resp::CommandResp::SubmitWorkflowResp(
+                        proto::exchange::SubmitWorkflowResp { id },
+                    ),
+                ),
+            }),
+            CommandResp::GetWorkflow { workflow } => Ok(proto::exchange::CommandResp {
+                command_resp: Some(proto::exchange::command_resp::CommandResp::GetWorkflowResp(
+                    proto::exchange::GetWorkflowResp {
+                        workflow: Some(proto::exchange::Workflow::try_from(*workflow)?),
+                    },
+                )),
+            }),
+            CommandResp::AdvanceWorkflow { event_ids } => Ok(proto::exchange::CommandResp {
+                command_resp: Some(
+                    proto::exchange::command_resp::CommandResp::AdvanceWorkflowResp(
+                        proto::exchange::AdvanceWorkflowResp { event_ids },
+                    ),
+                ),
+            }),
+            CommandResp::AddJoinTrigger { id } => Ok(proto::exchange::CommandResp {
+                command_resp: Some(
+                    proto::exchange::command_resp::CommandResp::AddJoinTriggerResp(
+                        proto::exchange::AddJoinTriggerResp { id },
+                    ),
+                ),
+            }),
+            CommandResp::RemoveJoinTrigger {} => Ok(proto::exchange::CommandResp {
+                command_resp: Some(
+                    proto::exchange::command_resp::CommandResp::RemoveJoinTriggerResp(
+                        proto::exchange::RemoveJoinTriggerResp {},
+                    ),
+                ),
+            }),
+            CommandResp::GetJoinTrigger { join_trigger } => Ok(proto::exchange::CommandResp {
+                command_resp: Some(
+                    proto::exchange::command_resp::CommandResp::GetJoinTriggerResp(
+                        proto::exchange::GetJoinTriggerResp {
+                            join_trigger: Some(proto::exchange::JoinTrigger::try_from(
+                                *join_trigger,
+                            )?),
+                        },
+                    ),
+                ),
+            }),
+            CommandResp::GetTransaction { transaction } => Ok(proto::exchange::CommandResp {
+                command_resp: Some(
+                    proto::exchange::command_resp::CommandResp::GetTransactionResp(
+                        proto::exchange::GetTransactionResp {
+                            transaction: Some(proto::exchange::Transaction::try_from(
+                                &transaction,
+                            )?),
+                        },
+                    ),
+                ),
+            }),
+            CommandResp::ListTransactions { transactions } => {
+                let mut proto_transactions: Vec<proto::exchange::Transaction> = Vec::new();
+                for transaction in transactions.iter() {
+                    proto_transactions.push(proto::exchange::Transaction::try_from(transaction)?);
+                }
+                Ok(proto::exchange::CommandResp {
+                    command_resp: Some(
+                        proto::exchange::command_resp::CommandResp::ListTransactionsResp(
+                            proto::exchange::ListTransactionsResp {
+                                transactions: proto_transactions,
+                            },
+                        ),
+                    ),
+                })
+            }
+            CommandResp::AbortTransaction {} => Ok(proto::exchange::CommandResp {
+                command_resp: Some(
+                    proto::exchange::command_resp::CommandResp::AbortTransactionResp(
+                        proto::exchange::AbortTransactionResp {},
+                    ),
+                ),
+            }),
+            CommandResp::GetEvent { event } => Ok(proto::exchange::CommandResp {
+                command_resp: Some(proto::exchange::command_resp::CommandResp::GetEventResp(
+                    proto::exchange::GetEventResp {
+                        event: Some(proto::exchange::Event::try_from(*event)?),
+                    },
+                )),
+            }),
+            CommandResp::ListEvents { events } => {
+                let mut proto_events: Vec<proto::exchange::Event> = Vec::new();
+                for event in events {
+                    proto_events.push(proto::exchange::Event::try_from(event)?);
+                }
+                Ok(proto::exchange::CommandResp {
+                    command_resp: Some(proto::exchange::command_resp::CommandResp::ListEventsResp(
+                        proto::exchange::ListEventsResp {
+                            events: proto_events,
+                        },
+                    )),
+                })
+            }
+            CommandResp::ListQueues { names } => Ok(proto::exchange::CommandResp {
+                command_resp: Some(proto::exchange::command_resp::CommandResp::ListQueuesResp(
+                    proto::exchange::ListQueuesResp { names },
+                )),
+            }),
+            CommandResp::DescribeQueue { description } => Ok(proto::exchange::CommandResp {
+                command_resp: Some(
+                    proto::exchange::command_resp::CommandResp::DescribeQueueResp(
+                        proto::exchange::DescribeQueueResp {
+                            description: Some(proto::exchange::QueueDescription::try_from(
+                                *description,
+                            )?),
+                        },
+                    ),
+                ),
+            }),
+            CommandResp::DeleteQueue {} => Ok(proto::exchange::CommandResp {
+                command_resp: Some(proto::exchange::command_resp::CommandResp::DeleteQueueResp(
+                    proto::exchange::DeleteQueueResp {},
+                )),
+            }),
+            CommandResp::PurgeQueue { purged } => Ok(proto::exchange::CommandResp {
+                command_resp: Some(proto::exchange::command_resp::CommandResp::PurgeQueueResp(
+                    proto::exchange::PurgeQueueResp { purged },
+                )),
+            }),
+            CommandResp::PauseQueue {} => Ok(proto::exchange::CommandResp {
+                command_resp: Some(proto::exchange::command_resp::CommandResp::PauseQueueResp(
+                    proto::exchange::PauseQueueResp {},
+                )),
+            }),
+            CommandResp::ResumeQueue {} => Ok(proto::exchange::CommandResp {
+                command_resp: Some(proto::exchange::command_resp::CommandResp::ResumeQueueResp(
+                    proto::exchange::ResumeQueueResp {},
+                )),
+            }),
+            CommandResp::UpdateQueueConfig {} => Ok(proto::exchange::CommandResp {
+                command_resp: Some(
+                    proto::exchange::command_resp::CommandResp::UpdateQueueConfigResp(
+                        proto::exchange::UpdateQueueConfigResp {},
+                    ),
+                ),
+            }),
+            CommandResp::DeliverEvent {} => Ok(proto::exchange::CommandResp {
+                command_resp: Some(
+                    proto::exchange::command_resp::CommandResp::DeliverEventResp(
+                        proto::exchange::DeliverEventResp {},
+                    ),
+                ),
+            }),
+            CommandResp::Error { code, error_msg } => Ok(proto::exchange::CommandResp {
+                command_resp: Some(proto::exchange::command_resp::CommandResp::ErrorResp(
+                    proto::exchange::ErrorResp {
+                        error_msg,
+                        code: proto::exchange::ErrorCode::try_from(code)? as i32,
+                    },
+                )),
+            }),
         }
     }
 }
 
+fn duration_to_ms(duration: Option<chrono::Duration>) -> anyhow::Result<Option<u64>> {
+    match duration {
+        Some(duration) => Ok(Some(u64::try_from(duration.num_milliseconds())?)),
+        None => Ok(None),
+    }
+}
+
+fn duration_from_ms(duration_ms: Option<u64>) -> anyhow::Result<Option<chrono::Duration>> {
+    match duration_ms {
+        Some(duration_ms) => Ok(Some(bounded_duration(duration_ms).ok_or(
+            anyhow::anyhow!(
+                "duration of {} ms exceeds {} ms",
+                duration_ms,
+                MAX_DURATION_MS
+            ),
+        )?)),
+        None => Ok(None),
+    }
+}
+
+impl TryFrom<Command> for proto::exchange::Command {
+    type Error = anyhow::Error;
+
+    fn try_from(value: Command) -> Result<Self, Self::Error> {
+        let command = match value {
+            Command::AddQueue {
+                name,
+                config,
+                dead_letter,
+                priority_aging,
+            } => proto::exchange::command::Command::AddQueue(proto::exchange::AddQueue {
+                name,
+                retry_policy: None,
+                dead_letter: match dead_letter {
+                    Some(dead_letter) => {
+                        Some(proto::exchange::DeadLetterConfig::try_from(dead_letter)?)
+                    }
+                    None => None,
+                },
+                priority_aging_ms: duration_to_ms(priority_aging)?,
+                config: Some(proto::exchange::QueueConfig::try_from(config)?),
+            }),
+            Command::AddEvent {
+                queue_name,
+                event,
+                delay,
+                dedup_key,
+            } => proto::exchange::command::Command::AddEvent(proto::exchange::AddEvent {
+                queue_name,
+                event: Some(proto::exchange::Event::try_from(event)?),
+                delay_ms: duration_to_ms(delay)?,
+                dedup_key,
+            }),
+            Command::AddEvents {
+                queue_name,
+                events,
+                delay,
+                dedup_key,
+            } => {
+                let mut proto_events: Vec<proto::exchange::Event> = Vec::new();
+                for event in events {
+                    proto_events.push(proto::exchange::Event::try_from(event)?);
+                }
+                proto::exchange::command::Command::AddEvents(proto::exchange::AddEvents {
+                    queue_name,
+                    events: proto_events,
+                    delay_ms: duration_to_ms(delay)?,
+                    dedup_key,
+                })
+            }
+            Command::UpdateEventStatus {
+                queue_name,
+                event_id,
+                status,
+            } => proto::exchange::command::Command::UpdateEventStatus(
+                proto::exchange::UpdateEventStatus {
+                    queue_name,
+                    event_id,
+                    status: Some(proto::exchange::Status::try_from(status)?),
+                },
+            ),
+            Command::CreateTransaction {
+                queue_name,
+                event_id,
+            } => proto::exchange::command::Command::CreateTransaction(
+                proto::exchange::CreateTransaction {
+                    queue_name,
+                    event_id,
+                },
+            ),
+            Command::ClaimEvents {
+                queue_name,
+                max,
+                worker_id,
+                selector,
+            } => proto::exchange::command::Command::ClaimEvents(proto::exchange::ClaimEvents {
+                queue_name,
+                max,
+                worker_id,
+                selector: if selector.is_empty() {
+                    None
+                } else {
+                    Some(proto::exchange::AttributeSelector::try_from(selector)?)
+                },
+            }),
+            Command::Heartbeat {
+                queue_name,
+                event_id,
+            } => proto::exchange::command::Command::Heartbeat(proto::exchange::Heartbeat {
+                queue_name,
+                event_id,
+            }),
+            Command::AddCommandTrigger {
+                queue_name,
+                event_id,
+                trigger,
+                command,
+            } => proto::exchange::command::Command::AddCommandTrigger(Box::new(
+                proto::exchange::AddCommandTrigger {
+                    queue_name,
+                    event_id,
+                    trigger: Some(proto::exchange::Trigger::try_from(trigger)?),
+                    command: Some(Box::new(proto::exchange::Command::try_from(*command)?)),
+                },
+            )),
+            Command::ListDeadLetters {
+                queue_name,
+                after_event_id,
+                limit,
+            } => proto::exchange::command::Command::ListDeadLetters(
+                proto::exchange::ListDeadLetters {
+                    queue_name,
+                    after_event_id,
+                    limit,
+                },
+            ),
+            Command::GetDeadLetter {
+                queue_name,
+                event_id,
+            } => proto::exchange::command::Command::GetDeadLetter(proto::exchange::GetDeadLetter {
+                queue_name,
+                event_id,
+            }),
+            Command::ReplayDeadLetters {
+                queue_name,
+                event_ids,
+            } => proto::exchange::command::Command::ReplayDeadLetters(
+                proto::exchange::ReplayDeadLetters {
+                    queue_name,
+                    event_ids,
+                },
+            ),
+            Command::PurgeDeadLetters {
+                queue_name,
+                event_ids,
+            } => proto::exchange::command::Command::PurgeDeadLetters(
+                proto::exchange::PurgeDeadLetters {
+                    queue_name,
+                    event_ids,
+                },
+            ),
+            Command::AddSchedule {
+                queue_name,
+                cron_expr,
+                timezone,
+                event_template,
+                missed_fire_policy,
+            } => proto::exchange::command::Command::AddSchedule(proto::exchange::AddSchedule {
+                queue_name,
+                cron_expr,
+                timezone,
+                event_template: Some(proto::exchange::Event::try_from(event_template)?),
+                missed_fire_policy: proto::exchange::MissedFirePolicy::try_from(missed_fire_policy)?
+                    as i32,
+            }),
+            Command::RemoveSchedule { id } => {
+                proto::exchange::command::Command::RemoveSchedule(proto::exchange::RemoveSchedule {
+                    id,
+                })
+            }
+            Command::ListSchedules { queue_name } => {
+                proto::exchange::command::Command::ListSchedules(proto::exchange::ListSchedules {
+                    queue_name,
+                })
+            }
+            Command::FireSchedule { id } => {
+                proto::exchange::command::Command::FireSchedule(proto::exchange::FireSchedule {
+                    id,
+                })
+            }
+            Command::AddBinding {
+                topic_pattern,
+                queue_name,
+                selector,
+            } => proto::exchange::command::Command::AddBinding(proto::exchange::AddBinding {
+                topic_pattern,
+                queue_name,
+                selector: if selector.is_empty() {
+                    None
+                } else {
+                    Some(proto::exchange::AttributeSelector::try_from(selector)?)
+                },
+            }),
+            Command::RemoveBinding { id } => {
+                proto::exchange::command::Command::RemoveBinding(proto::exchange::RemoveBinding {
+                    id,
+                })
+            }
+            Command::ListBindings { queue_name } => {
+                proto::exchange::command::Command::ListBindings(proto::exchange::ListBindings {
+                    queue_name,
+                })
+            }
+            Command::Publish { topic, event } => {
+                proto::exchange::command::Command::Publish(proto::exchange::Publish {
+                    topic,
+                    event: Some(proto::exchange::Event::try_from(event)?),
+                })
+            }
+            Command::SubmitWorkflow { name, nodes, edges } => {
+                let mut proto_nodes: Vec<proto::exchange::WorkflowNode> = Vec::new();
+                for node in nodes {
+                    proto_nodes.push(proto::exchange::WorkflowNode::try_from(node)?);
+                }
+                let mut proto_edges: Vec<proto::exchange::WorkflowEdge> = Vec::new();
+                for edge in edges {
+                    proto_edges.push(proto::exchange::WorkflowEdge::try_from(edge)?);
+                }
+                proto::exchange::command::Command::SubmitWorkflow(proto::exchange::SubmitWorkflow {
+                    name,
+                    nodes: proto_nodes,
+                    edges: proto_edges,
+                })
+            }
+            Command::GetWorkflow { id } => {
+                proto::exchange::command::Command::GetWorkflow(proto::exchange::GetWorkflow { id })
+            }
+            Command::AdvanceWorkflow {
+                workflow_id,
+                node,
+                status,
+            } => proto::exchange::command::Command::AdvanceWorkflow(
+                proto::exchange::AdvanceWorkflow {
+                    workflow_id,
+                    node,
+                    status: Some(proto::exchange::Status::try_from(status)?),
+                },
+            ),
+            Command::AddJoinTrigger {
+                events,
+                condition,
+                commands,
+            } => {
+                let mut proto_commands: Vec<proto::exchange::Command> = Vec::new();
+                for command in commands {
+                    proto_commands.push(proto::exchange::Command::try_from(command)?);
+                }
+                proto::exchange::command::Command::AddJoinTrigger(proto::exchange::AddJoinTrigger {
+                    events: events
+                        .into_iter()
+                        .map(|(queue_name, event_id)| proto::exchange::EventRef {
+                            queue_name,
+                            event_id,
+                        })
+                        .collect(),
+                    condition: Some(proto::exchange::JoinCondition::try_from(condition)?),
+                    commands: proto_commands,
+                })
+            }
+            Command::RemoveJoinTrigger { id } => {
+                proto::exchange::command::Command::RemoveJoinTrigger(
+                    proto::exchange::RemoveJoinTrigger { id },
+                )
+            }
+            Command::GetJoinTrigger { id } => {
+                proto::exchange::command::Command::GetJoinTrigger(proto::exchange::GetJoinTrigger {
+                    id,
+                })
+            }
+            Command::GetTransaction {
+                queue_name,
+                transaction_id,
+            } => {
+                proto::exchange::command::Command::GetTransaction(proto::exchange::GetTransaction {
+                    queue_name,
+                    transaction_id,
+                })
+            }
+            Command::ListTransactions { queue_name } => {
+                proto::exchange::command::Command::ListTransactions(
+                    proto::exchange::ListTransactions { queue_name },
+                )
+            }
+            Command::GetEvent {
+                queue_name,
+                event_id,
+            } => proto::exchange::command::Command::GetEvent(proto::exchange::GetEvent {
+                queue_name,
+                event_id,
+            }),
+            Command::ListEvents {
+                queue_name,
+                status,
+                after_event_id,
+                limit,
+            } => proto::exchange::command::Command::ListEvents(proto::exchange::ListEvents {
+                queue_name,
+                status: match status {
+                    Some(status) => Some(proto::exchange::Status::try_from(status)?),
+                    None => None,
+                },
+                after_event_id,
+                limit,
+            }),
+            Command::ListQueues {} => {
+                proto::exchange::command::Command::ListQueues(proto::exchange::ListQueues {})
+            }
+            Command::DescribeQueue { queue_name } => {
+                proto::exchange::command::Command::DescribeQueue(proto::exchange::DescribeQueue {
+                    queue_name,
+                })
+            }
+            Command::DeleteQueue {
+                queue_name,
+                refuse_if_running,
+            } => proto::exchange::command::Command::DeleteQueue(proto::exchange::DeleteQueue {
+                queue_name,
+                refuse_if_running,
+            }),
+            Command::PurgeQueue { queue_name, status } => {
+                proto::exchange::command::Command::PurgeQueue(proto::exchange::PurgeQueue {
+                    queue_name,
+                    status: match status {
+                        Some(status) => Some(proto::exchange::Status::try_from(status)?),
+                        None => None,
+                    },
+                })
+            }
+            Command::PauseQueue { queue_name } => {
+                proto::exchange::command::Command::PauseQueue(proto::exchange::PauseQueue {
+                    queue_name,
+                })
+            }
+            Command::ResumeQueue { queue_name } => {
+                proto::exchange::command::Command::ResumeQueue(proto::exchange::ResumeQueue {
+                    queue_name,
+                })
+            }
+            Command::UpdateQueueConfig { queue_name, config } => {
+                proto::exchange::command::Command::UpdateQueueConfig(
+                    proto::exchange::UpdateQueueConfig {
+                        queue_name,
+                        config: Some(proto::exchange::QueueConfig::try_from(config)?),
+                    },
+                )
+            }
+            Command::AbortTransaction {
+                queue_name,
+                transaction_id,
+            } => proto::exchange::command::Command::AbortTransaction(
+                proto::exchange::AbortTransaction {
+                    queue_name,
+                    transaction_id,
+                },
+            ),
+            Command::DeliverEvent {
+                queue_name,
+                event_id,
+                worker_id,
+            } => proto::exchange::command::Command::DeliverEvent(proto::exchange::DeliverEvent {
+                queue_name,
+                event_id,
+                worker_id,
+            }),
+        };
+        Ok(proto::exchange::Command {
+            command: Some(command),
+        })
+    }
+}
+
 impl TryFrom<proto::exchange::Command> for Command {
     type Error = anyhow::Error;
 
     fn try_from(value: proto::exchange::Command) -> Result<Self, Self::Error> {
         match value.command.ok_or(anyhow::anyhow!("received None"))? {
             proto::exchange::command::Command::AddQueue(obj) => {
-                Ok(Command::AddQueue { name: obj.name })
+                let mut config = match obj.config {
+                    Some(config) => QueueConfig::try_from(config)?,
+                    None => QueueConfig::default(),
+                };
+                if config.retry_policy().is_none()
+                    && let Some(retry_policy) = obj.retry_policy
+                {
+                    config.set_retry_policy(Some(RetryPolicy::try_from(retry_policy)?));
+                }
+                Ok(Command::AddQueue {
+                    name: obj.name,
+                    config,
+                    dead_letter: match obj.dead_letter {
+                        Some(dead_letter) => Some(DeadLetterConfig::try_from(dead_letter)?),
+                        None => None,
+                    },
+                    priority_aging: duration_from_ms(obj.priority_aging_ms)?,
+                })
             }
             proto::exchange::command::Command::AddEvent(obj) => {
                 let event = Event::try_from(obj.event.ok_or(anyhow::anyhow!("event was None"))?)?;
                 Ok(Command::AddEvent {
                     queue_name: obj.queue_name,
                     event,
+                    delay: duration_from_ms(obj.delay_ms)?,
+                    dedup_key: obj.dedup_key.filter(|dedup_key| !dedup_key.is_empty()),
                 })
             }
             proto::exchange::command::Command::AddEvents(obj) => {
@@ -175,6 +1358,8 @@ impl TryFrom<proto::exchange::Command> for Command {
                 Ok(Command::AddEvents {
                     queue_name: obj.queue_name,
                     events,
+                    delay: duration_from_ms(obj.delay_ms)?,
+                    dedup_key: obj.dedup_key.filter(|dedup_key| !dedup_key.is_empty()),
                 })
             }
             proto::exchange::command::Command::UpdateEventStatus(obj) => {
@@ -192,6 +1377,240 @@ impl TryFrom<proto::exchange::Command> for Command {
                     event_id: obj.event_id,
                 })
             }
+            proto::exchange::command::Command::ClaimEvents(obj) => Ok(Command::ClaimEvents {
+                queue_name: obj.queue_name,
+                max: obj.max,
+                worker_id: obj.worker_id,
+                selector: match obj.selector {
+                    Some(selector) => Selector::try_from(selector)?,
+                    None => Selector::default(),
+                },
+            }),
+            proto::exchange::command::Command::Heartbeat(obj) => Ok(Command::Heartbeat {
+                queue_name: obj.queue_name,
+                event_id: obj.event_id,
+            }),
+            proto::exchange::command::Command::AddCommandTrigger(obj) => {
+                let trigger = Trigger::new(
+                    obj.event_id,
+                    obj.trigger.ok_or(anyhow::anyhow!("trigger was None"))?,
+                )?;
+                let command =
+                    Command::try_from(*obj.command.ok_or(anyhow::anyhow!("command was None"))?)?;
+                Ok(Command::AddCommandTrigger {
+                    queue_name: obj.queue_name,
+                    event_id: obj.event_id,
+                    trigger,
+                    command: Box::new(command),
+                })
+            }
+            proto::exchange::command::Command::ListDeadLetters(obj) => {
+                Ok(Command::ListDeadLetters {
+                    queue_name: obj.queue_name,
+                    after_event_id: obj.after_event_id,
+                    limit: obj.limit,
+                })
+            }
+            proto::exchange::command::Command::GetDeadLetter(obj) => Ok(Command::GetDeadLetter {
+                queue_name: obj.queue_name,
+                event_id: obj.event_id,
+            }),
+            proto::exchange::command::Command::ReplayDeadLetters(obj) => {
+                Ok(Command::ReplayDeadLetters {
+                    queue_name: obj.queue_name,
+                    event_ids: obj.event_ids,
+                })
+            }
+            proto::exchange::command::Command::PurgeDeadLetters(obj) => {
+                Ok(Command::PurgeDeadLetters {
+                    queue_name: obj.queue_name,
+                    event_ids: obj.event_ids,
+                })
+            }
+            proto::exchange::command::Command::AddSchedule(obj) => {
+                let event_template = Event::try_from(
+                    obj.event_template
+                        .ok_or(anyhow::anyhow!("event template was None"))?,
+                )?;
+                let missed_fire_policy = MissedFirePolicy::try_from(
+                    proto::exchange::MissedFirePolicy::try_from(obj.missed_fire_policy)?,
+                )?;
+                Ok(Command::AddSchedule {
+                    queue_name: obj.queue_name,
+                    cron_expr: obj.cron_expr,
+                    timezone: obj.timezone,
+                    event_template,
+                    missed_fire_policy,
+                })
+            }
+            proto::exchange::command::Command::RemoveSchedule(obj) => {
+                Ok(Command::RemoveSchedule { id: obj.id })
+            }
+            proto::exchange::command::Command::ListSchedules(obj) => Ok(Command::ListSchedules {
+                queue_name: obj.queue_name,
+            }),
+            proto::exchange::command::Command::FireSchedule(obj) => {
+                Ok(Command::FireSchedule { id: obj.id })
+            }
+            proto::exchange::command::Command::AddBinding(obj) => Ok(Command::AddBinding {
+                topic_pattern: obj.topic_pattern,
+                queue_name: obj.queue_name,
+                selector: match obj.selector {
+                    Some(selector) => Selector::try_from(selector)?,
+                    None => Selector::default(),
+                },
+            }),
+            proto::exchange::command::Command::RemoveBinding(obj) => {
+                Ok(Command::RemoveBinding { id: obj.id })
+            }
+            proto::exchange::command::Command::ListBindings(obj) => Ok(Command::ListBindings {
+                queue_name: obj.queue_name,
+            }),
+            proto::exchange::command::Command::Publish(obj) => Ok(Command::Publish {
+                topic: obj.topic,
+                event: Event::try_from(obj.event.ok_or(anyhow::anyhow!("event was None"))?)?,
+            }),
+            proto::exchange::command::Command::SubmitWorkflow(obj) => {
+                let mut nodes: Vec<WorkflowNode> = Vec::new();
+                for node in obj.nodes {
+                    nodes.push(WorkflowNode::try_from(node)?);
+                }
+                let mut edges: Vec<WorkflowEdge> = Vec::new();
+                for edge in obj.edges {
+                    edges.push(WorkflowEdge::try_from(edge)?);
+                }
+                Ok(Command::SubmitWorkflow {
+                    name: obj.name,
+                    nodes,
+                    edges,
+                })
+            }
+            proto::exchange::command::Command::GetWorkflow(obj) => {
+                Ok(Command::GetWorkflow { id: obj.id })
+            }
+            proto::exchange::command::Command::AdvanceWorkflow(obj) => {
+                Ok(Command::AdvanceWorkflow {
+                    workflow_id: obj.workflow_id,
+                    node: obj.node,
+                    status: EventStatus::try_from(
+                        obj.status.ok_or(anyhow::anyhow!("status was None"))?,
+                    )?,
+                })
+            }
+            proto::exchange::command::Command::AddJoinTrigger(obj) => {
+                let mut commands: Vec<Command> = Vec::new();
+                for command in obj.commands {
+                    commands.push(Command::try_from(command)?);
+                }
+                Ok(Command::AddJoinTrigger {
+                    events: obj
+                        .events
+                        .into_iter()
+                        .map(|event| (event.queue_name, event.event_id))
+                        .collect(),
+                    condition: JoinCondition::try_from(
+                        obj.condition.ok_or(anyhow::anyhow!("condition was None"))?,
+                    )?,
+                    commands,
+                })
+            }
+            proto::exchange::command::Command::RemoveJoinTrigger(obj) => {
+                Ok(Command::RemoveJoinTrigger { id: obj.id })
+            }
+            proto::exchange::command::Command::GetJoinTrigger(obj) => {
+                Ok(Command::GetJoinTrigger { id: obj.id })
+            }
+            proto::exchange::command::Command::GetTransaction(obj) => Ok(Command::GetTransaction {
+                queue_name: obj.queue_name,
+                transaction_id: obj.transaction_id,
+            }),
+            proto::exchange::command::Command::ListTransactions(obj) => {
+                Ok(Command::ListTransactions {
+                    queue_name: obj.queue_name,
+                })
+            }
+            proto::exchange::command::Command::GetEvent(obj) => Ok(Command::GetEvent {
+                queue_name: obj.queue_name,
+                event_id: obj.event_id,
+            }),
+            proto::exchange::command::Command::ListEvents(obj) => Ok(Command::ListEvents {
+                queue_name: obj.queue_name,
+                status: match obj.status {
+                    Some(status) => Some(EventStatus::try_from(status)?),
+                    None => None,
+                },
+                after_event_id: obj.after_event_id,
+                limit: obj.limit,
+            }),
+            proto::exchange::command::Command::ListQueues(_) => Ok(Command::ListQueues {}),
+            proto::exchange::command::Command::DescribeQueue(obj) => Ok(Command::DescribeQueue {
+                queue_name: obj.queue_name,
+            }),
+            proto::exchange::command::Command::DeleteQueue(obj) => Ok(Command::DeleteQueue {
+                queue_name: obj.queue_name,
+                refuse_if_running: obj.refuse_if_running,
+            }),
+            proto::exchange::command::Command::PurgeQueue(obj) => Ok(Command::PurgeQueue {
+                queue_name: obj.queue_name,
+                status: match obj.status {
+                    Some(status) => Some(EventStatus::try_from(status)?),
+                    None => None,
+                },
+            }),
+            proto::exchange::command::Command::PauseQueue(obj) => Ok(Command::PauseQueue {
+                queue_name: obj.queue_name,
+            }),
+            proto::exchange::command::Command::ResumeQueue(obj) => Ok(Command::ResumeQueue {
+                queue_name: obj.queue_name,
+            }),
+            proto::exchange::command::Command::UpdateQueueConfig(obj) => {
+                Ok(Command::UpdateQueueConfig {
+                    queue_name: obj.queue_name,
+                    config: QueueConfig::try_from(
+                        obj.config.ok_or(anyhow::anyhow!("config was None"))?,
+                    )?,
+                })
+            }
+            proto::exchange::command::Command::AbortTransaction(obj) => {
+                Ok(Command::AbortTransaction {
+                    queue_name: obj.queue_name,
+                    transaction_id: obj.transaction_id,
+                })
+            }
+            proto::exchange::command::Command::DeliverEvent(obj) => Ok(Command::DeliverEvent {
+                queue_name: obj.queue_name,
+                event_id: obj.event_id,
+                worker_id: obj.worker_id,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_add_queue_retry_policy_fills_the_config() {
+        let command = Command::try_from(proto::exchange::Command {
+            command: Some(proto::exchange::command::Command::AddQueue(
+                proto::exchange::AddQueue {
+                    name: "test".to_string(),
+                    retry_policy: Some(proto::exchange::RetryPolicy {
+                        max_attempts: 3,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            )),
+        })
+        .unwrap();
+        match command {
+            Command::AddQueue { config, .. } => assert_eq!(
+                config.retry_policy().map(|policy| policy.max_attempts()),
+                Some(3)
+            ),
+            command => panic!("unexpected command: {:?}", command),
         }
     }
 }
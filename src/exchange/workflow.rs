@@ -0,0 +1,664 @@
+use anyhow::Result;
+use std::collections;
+use thiserror::Error;
+
+use crate::rpc::proto;
+
+use super::event::{Event, EventStatus};
+
+#[derive(Debug, Error)]
+pub enum WorkflowError {
+    #[error("invalid workflow: {0}")]
+    InvalidWorkflow(String),
+    #[error("workflow node not found: {0}")]
+    NodeNotFound(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorkflowStatus {
+    Running,
+    Complete,
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeStatus {
+    Pending,
+    Started,
+    Complete,
+    Failed,
+    Skipped,
+}
+
+#[derive(Debug, Clone)]
+pub struct WorkflowNode {
+    name: String,
+    queue_name: String,
+    event_template: Event,
+}
+
+impl WorkflowNode {
+    pub fn new(name: String, queue_name: String, event_template: Event) -> WorkflowNode {
+        WorkflowNode {
+            name,
+            queue_name,
+            event_template,
+        }
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn queue_name(&self) -> &String {
+        &self.queue_name
+    }
+
+    pub fn event_template(&self) -> &Event {
+        &self.event_template
+    }
+}
+
+// the to node is unblocked when the event of the from node finishes
+// with the given status
+#[derive(Debug, Clone)]
+pub struct WorkflowEdge {
+    from: String,
+    to: String,
+    status: EventStatus,
+}
+
+impl WorkflowEdge {
+    pub fn new(from: String, to: String, status: EventStatus) -> WorkflowEdge {
+        WorkflowEdge { from, to, status }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NodeProgress {
+    status: NodeStatus,
+    event_id: Option<u64>,
+    // final status of the node's event
+    outcome: Option<EventStatus>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Workflow {
+    id: u64,
+    name: String,
+    nodes: Vec<WorkflowNode>,
+    edges: Vec<WorkflowEdge>,
+
+    status: WorkflowStatus,
+    // indexed by node name
+    progress: collections::HashMap<String, NodeProgress>,
+    created_at: chrono::DateTime<chrono::Utc>,
+    finished_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl Workflow {
+    pub fn new(
+        id: u64,
+        name: String,
+        nodes: Vec<WorkflowNode>,
+        edges: Vec<WorkflowEdge>,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Workflow> {
+        Workflow::validate(&nodes, &edges)?;
+        let progress = nodes
+            .iter()
+            .map(|node| {
+                (
+                    node.name.clone(),
+                    NodeProgress {
+                        status: NodeStatus::Pending,
+                        event_id: None,
+                        outcome: None,
+                    },
+                )
+            })
+            .collect();
+        Ok(Workflow {
+            id,
+            name,
+            nodes,
+            edges,
+            status: WorkflowStatus::Running,
+            progress,
+            created_at: now,
+            finished_at: None,
+        })
+    }
+
+    // node names have to be unique and the edges have to form a DAG
+    // between known nodes
+    fn validate(nodes: &[WorkflowNode], edges: &[WorkflowEdge]) -> Result<()> {
+        if nodes.is_empty() {
+            return Err(WorkflowError::InvalidWorkflow("workflow has no nodes".to_string()).into());
+        }
+        let mut in_degrees: collections::HashMap<&String, usize> = collections::HashMap::new();
+        for node in nodes {
+            if node.name.is_empty() {
+                return Err(
+                    WorkflowError::InvalidWorkflow("node is missing a name".to_string()).into(),
+                );
+            }
+            if in_degrees.insert(&node.name, 0).is_some() {
+                return Err(WorkflowError::InvalidWorkflow(format!(
+                    "duplicate node {}",
+                    node.name
+                ))
+                .into());
+            }
+        }
+        for edge in edges {
+            for name in [&edge.from, &edge.to] {
+                if !in_degrees.contains_key(name) {
+                    return Err(WorkflowError::InvalidWorkflow(format!(
+                        "edge references unknown node {}",
+                        name
+                    ))
+                    .into());
+                }
+            }
+            if !matches!(
+                edge.status,
+                EventStatus::Complete
+                    | EventStatus::Errored
+                    | EventStatus::MissedHeartbeat
+                    | EventStatus::Timedout
+                    | EventStatus::Expired
+            ) {
+                return Err(WorkflowError::InvalidWorkflow(format!(
+                    "edge from {} to {} is not triggered by a final status",
+                    edge.from, edge.to
+                ))
+                .into());
+            }
+            if let Some(in_degree) = in_degrees.get_mut(&edge.to) {
+                *in_degree += 1;
+            }
+        }
+
+        // Kahn's algorithm, nodes left unvisited are part of a cycle
+        let mut ready: Vec<&String> = in_degrees
+            .iter()
+            .filter(|(_, in_degree)| **in_degree == 0)
+            .map(|(name, _)| *name)
+            .collect();
+        let mut visited: usize = 0;
+        while let Some(name) = ready.pop() {
+            visited += 1;
+            for edge in edges.iter().filter(|edge| edge.from == *name) {
+                if let Some(in_degree) = in_degrees.get_mut(&edge.to) {
+                    *in_degree -= 1;
+                    if *in_degree == 0 {
+                        ready.push(&edge.to);
+                    }
+                }
+            }
+        }
+        if visited < nodes.len() {
+            return Err(WorkflowError::InvalidWorkflow("edges contain a cycle".to_string()).into());
+        }
+        Ok(())
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn status(&self) -> &WorkflowStatus {
+        &self.status
+    }
+
+    pub fn finished_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.finished_at
+    }
+
+    pub fn nodes(&self) -> &Vec<WorkflowNode> {
+        &self.nodes
+    }
+
+    pub fn node(&self, name: &String) -> Result<&WorkflowNode> {
+        match self.nodes.iter().find(|node| node.name == *name) {
+            Some(node) => Ok(node),
+            None => Err(WorkflowError::NodeNotFound(name.clone()).into()),
+        }
+    }
+
+    // nodes without incoming edges, in definition order
+    pub fn root_nodes(&self) -> Vec<String> {
+        self.nodes
+            .iter()
+            .filter(|node| !self.edges.iter().any(|edge| edge.to == node.name))
+            .map(|node| node.name.clone())
+            .collect()
+    }
+
+    // whether a started node is waiting on an event of the queue to finish
+    pub fn waits_on_queue(&self, queue_name: &str) -> bool {
+        self.nodes.iter().any(|node| {
+            node.queue_name == queue_name
+                && self
+                    .progress
+                    .get(&node.name)
+                    .is_some_and(|progress| progress.status == NodeStatus::Started)
+        })
+    }
+
+    // whether a started node is waiting on the event to finish
+    pub fn waits_on_event(&self, queue_name: &str, event_id: u64) -> bool {
+        self.nodes.iter().any(|node| {
+            node.queue_name == queue_name
+                && self.progress.get(&node.name).is_some_and(|progress| {
+                    progress.status == NodeStatus::Started && progress.event_id == Some(event_id)
+                })
+        })
+    }
+
+    pub fn start_node(&mut self, name: &String, event_id: u64) -> Result<()> {
+        let progress = if let Some(progress) = self.progress.get_mut(name) {
+            progress
+        } else {
+            return Err(WorkflowError::NodeNotFound(name.clone()).into());
+        };
+        progress.status = NodeStatus::Started;
+        progress.event_id = Some(event_id);
+        Ok(())
+    }
+
+    pub fn node_pending(&self, name: &String) -> bool {
+        self.node_status(name) == Some(&NodeStatus::Pending)
+    }
+
+    // finishes a pending node whose event could not be added with the
+    // given status, returning the nodes it unblocks like finish_node
+    pub fn fail_node(
+        &mut self,
+        name: &String,
+        status: EventStatus,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<String>> {
+        match self.progress.get_mut(name) {
+            Some(progress) if progress.status == NodeStatus::Pending => {
+                progress.status = NodeStatus::Started;
+            }
+            Some(_) => return Ok(Vec::new()),
+            None => return Err(WorkflowError::NodeNotFound(name.clone()).into()),
+        }
+        self.finish_node(name, status, now)
+    }
+
+    // records the final status of a started node's event and returns the
+    // nodes it unblocks, in definition order; nodes which can no longer
+    // be unblocked are skipped
+    pub fn finish_node(
+        &mut self,
+        name: &String,
+        status: EventStatus,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<String>> {
+        let progress = if let Some(progress) = self.progress.get_mut(name) {
+            progress
+        } else {
+            return Err(WorkflowError::NodeNotFound(name.clone()).into());
+        };
+        if progress.status != NodeStatus::Started {
+            return Ok(Vec::new());
+        }
+        progress.status = if status == EventStatus::Complete {
+            NodeStatus::Complete
+        } else {
+            NodeStatus::Failed
+        };
+        progress.outcome = Some(status);
+
+        let mut ready: Vec<String> = Vec::new();
+        // skipping a node can resolve the nodes downstream of it
+        loop {
+            let mut changed = false;
+            for node in self.nodes.iter() {
+                if self.node_status(&node.name) != Some(&NodeStatus::Pending)
+                    || ready.contains(&node.name)
+                {
+                    continue;
+                }
+                match self.upstream_satisfied(&node.name) {
+                    Some(true) => ready.push(node.name.clone()),
+                    Some(false) => {
+                        if let Some(progress) = self.progress.get_mut(&node.name) {
+                            progress.status = NodeStatus::Skipped;
+                        }
+                    }
+                    None => continue,
+                }
+                changed = true;
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        if ready.is_empty() {
+            self.update_status(now);
+        }
+        Ok(ready)
+    }
+
+    fn node_status(&self, name: &String) -> Option<&NodeStatus> {
+        self.progress.get(name).map(|progress| &progress.status)
+    }
+
+    // None while an upstream node has not finished, otherwise whether
+    // every upstream node finished with a status one of its edges to the
+    // node matches
+    fn upstream_satisfied(&self, name: &String) -> Option<bool> {
+        let mut upstream: Vec<&String> = self
+            .edges
+            .iter()
+            .filter(|edge| edge.to == *name)
+            .map(|edge| &edge.from)
+            .collect();
+        upstream.sort();
+        upstream.dedup();
+
+        let mut satisfied = true;
+        for from in upstream {
+            let progress = self.progress.get(from)?;
+            match progress.status {
+                NodeStatus::Pending | NodeStatus::Started => return None,
+                NodeStatus::Skipped => satisfied = false,
+                NodeStatus::Complete | NodeStatus::Failed => {
+                    let matched = self.edges.iter().any(|edge| {
+                        edge.from == *from
+                            && edge.to == *name
+                            && progress.outcome.as_ref() == Some(&edge.status)
+                    });
+                    satisfied = satisfied && matched;
+                }
+            }
+        }
+        Some(satisfied)
+    }
+
+    // a finished workflow failed if a node failed without an outgoing
+    // edge for its status
+    fn update_status(&mut self, now: chrono::DateTime<chrono::Utc>) {
+        let finished = self
+            .progress
+            .values()
+            .all(|progress| !matches!(progress.status, NodeStatus::Pending | NodeStatus::Started));
+        if !finished || self.status != WorkflowStatus::Running {
+            return;
+        }
+
+        let unhandled = self.progress.iter().any(|(name, progress)| {
+            progress.status == NodeStatus::Failed
+                && !self.edges.iter().any(|edge| {
+                    edge.from == *name && progress.outcome.as_ref() == Some(&edge.status)
+                })
+        });
+        self.status = if unhandled {
+            WorkflowStatus::Failed
+        } else {
+            WorkflowStatus::Complete
+        };
+        self.finished_at = Some(now);
+    }
+}
+
+fn status_from_trigger(obj: proto::exchange::Trigger) -> Result<EventStatus> {
+    match obj.trigger_type {
+        Some(proto::exchange::trigger::TriggerType::OnEventComplete(_)) => {
+            Ok(EventStatus::Complete)
+        }
+        Some(proto::exchange::trigger::TriggerType::OnEventError(_)) => Ok(EventStatus::Errored),
+        Some(proto::exchange::trigger::TriggerType::OnEventMissedHeartbeat(_)) => {
+            Ok(EventStatus::MissedHeartbeat)
+        }
+        Some(proto::exchange::trigger::TriggerType::OnEventTimedout(_)) => {
+            Ok(EventStatus::Timedout)
+        }
+        Some(proto::exchange::trigger::TriggerType::OnEventExpired(_)) => Ok(EventStatus::Expired),
+        None => Err(anyhow::anyhow!("received None")),
+    }
+}
+
+fn status_to_trigger(status: EventStatus) -> Result<proto::exchange::Trigger> {
+    let trigger_type = match status {
+        EventStatus::Complete => proto::exchange::trigger::TriggerType::OnEventComplete(
+            proto::exchange::OnEventComplete {},
+        ),
+        EventStatus::Errored => {
+            proto::exchange::trigger::TriggerType::OnEventError(proto::exchange::OnEventError {})
+        }
+        EventStatus::MissedHeartbeat => {
+            proto::exchange::trigger::TriggerType::OnEventMissedHeartbeat(
+                proto::exchange::OnEventMissedHeartbeat {},
+            )
+        }
+        EventStatus::Timedout => proto::exchange::trigger::TriggerType::OnEventTimedout(
+            proto::exchange::OnEventTimedout {},
+        ),
+        EventStatus::Expired => proto::exchange::trigger::TriggerType::OnEventExpired(
+            proto::exchange::OnEventExpired {},
+        ),
+        EventStatus::Queued | EventStatus::Running => {
+            return Err(anyhow::anyhow!("no trigger for status {:?}", status));
+        }
+    };
+    Ok(proto::exchange::Trigger {
+        trigger_type: Some(trigger_type),
+    })
+}
+
+impl TryFrom<proto::exchange::WorkflowNode> for WorkflowNode {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: proto::exchange::WorkflowNode) -> Result<WorkflowNode, Self::Error> {
+        let event_template = Event::try_from(
+            obj.event_template
+                .ok_or(anyhow::anyhow!("event template was None"))?,
+        )?;
+        Ok(WorkflowNode {
+            name: obj.name,
+            queue_name: obj.queue_name,
+            event_template,
+        })
+    }
+}
+
+impl TryFrom<WorkflowNode> for proto::exchange::WorkflowNode {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: WorkflowNode) -> Result<proto::exchange::WorkflowNode, Self::Error> {
+        Ok(proto::exchange::WorkflowNode {
+            name: obj.name,
+            queue_name: obj.queue_name,
+            event_template: Some(proto::exchange::Event::try_from(obj.event_template)?),
+        })
+    }
+}
+
+impl TryFrom<proto::exchange::WorkflowEdge> for WorkflowEdge {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: proto::exchange::WorkflowEdge) -> Result<WorkflowEdge, Self::Error> {
+        Ok(WorkflowEdge {
+            from: obj.from,
+            to: obj.to,
+            status: status_from_trigger(obj.trigger.ok_or(anyhow::anyhow!("trigger was None"))?)?,
+        })
+    }
+}
+
+impl TryFrom<WorkflowEdge> for proto::exchange::WorkflowEdge {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: WorkflowEdge) -> Result<proto::exchange::WorkflowEdge, Self::Error> {
+        Ok(proto::exchange::WorkflowEdge {
+            from: obj.from,
+            to: obj.to,
+            trigger: Some(status_to_trigger(obj.status)?),
+        })
+    }
+}
+
+impl TryFrom<WorkflowStatus> for proto::exchange::WorkflowStatus {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: WorkflowStatus) -> Result<proto::exchange::WorkflowStatus, Self::Error> {
+        match obj {
+            WorkflowStatus::Running => Ok(proto::exchange::WorkflowStatus::Running),
+            WorkflowStatus::Complete => Ok(proto::exchange::WorkflowStatus::Complete),
+            WorkflowStatus::Failed => Ok(proto::exchange::WorkflowStatus::Failed),
+        }
+    }
+}
+
+impl TryFrom<proto::exchange::WorkflowStatus> for WorkflowStatus {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: proto::exchange::WorkflowStatus) -> Result<WorkflowStatus, Self::Error> {
+        match obj {
+            proto::exchange::WorkflowStatus::Running => Ok(WorkflowStatus::Running),
+            proto::exchange::WorkflowStatus::Complete => Ok(WorkflowStatus::Complete),
+            proto::exchange::WorkflowStatus::Failed => Ok(WorkflowStatus::Failed),
+            proto::exchange::WorkflowStatus::Unspecified => {
+                Err(anyhow::anyhow!("workflow status was unspecified"))
+            }
+        }
+    }
+}
+
+impl TryFrom<NodeStatus> for proto::exchange::WorkflowNodeStatus {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: NodeStatus) -> Result<proto::exchange::WorkflowNodeStatus, Self::Error> {
+        match obj {
+            NodeStatus::Pending => Ok(proto::exchange::WorkflowNodeStatus::Pending),
+            NodeStatus::Started => Ok(proto::exchange::WorkflowNodeStatus::Started),
+            NodeStatus::Complete => Ok(proto::exchange::WorkflowNodeStatus::Complete),
+            NodeStatus::Failed => Ok(proto::exchange::WorkflowNodeStatus::Failed),
+            NodeStatus::Skipped => Ok(proto::exchange::WorkflowNodeStatus::Skipped),
+        }
+    }
+}
+
+impl TryFrom<proto::exchange::WorkflowNodeStatus> for NodeStatus {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: proto::exchange::WorkflowNodeStatus) -> Result<NodeStatus, Self::Error> {
+        match obj {
+            proto::exchange::WorkflowNodeStatus::Pending => Ok(NodeStatus::Pending),
+            proto::exchange::WorkflowNodeStatus::Started => Ok(NodeStatus::Started),
+            proto::exchange::WorkflowNodeStatus::Complete => Ok(NodeStatus::Complete),
+            proto::exchange::WorkflowNodeStatus::Failed => Ok(NodeStatus::Failed),
+            proto::exchange::WorkflowNodeStatus::Skipped => Ok(NodeStatus::Skipped),
+            proto::exchange::WorkflowNodeStatus::Unspecified => {
+                Err(anyhow::anyhow!("workflow node status was unspecified"))
+            }
+        }
+    }
+}
+
+impl TryFrom<Workflow> for proto::exchange::Workflow {
+    type Error = anyhow::Error;
+
+    fn try_from(mut obj: Workflow) -> Result<proto::exchange::Workflow, Self::Error> {
+        let mut progress: Vec<proto::exchange::WorkflowNodeProgress> = Vec::new();
+        for node in obj.nodes.iter() {
+            let node_progress = if let Some(node_progress) = obj.progress.remove(&node.name) {
+                node_progress
+            } else {
+                continue;
+            };
+            progress.push(proto::exchange::WorkflowNodeProgress {
+                node: node.name.clone(),
+                status: proto::exchange::WorkflowNodeStatus::try_from(node_progress.status)? as i32,
+                event_id: node_progress.event_id,
+                outcome: match node_progress.outcome {
+                    Some(outcome) => Some(proto::exchange::Status::try_from(outcome)?),
+                    None => None,
+                },
+            });
+        }
+
+        let mut nodes: Vec<proto::exchange::WorkflowNode> = Vec::new();
+        for node in obj.nodes {
+            nodes.push(proto::exchange::WorkflowNode::try_from(node)?);
+        }
+        let mut edges: Vec<proto::exchange::WorkflowEdge> = Vec::new();
+        for edge in obj.edges {
+            edges.push(proto::exchange::WorkflowEdge::try_from(edge)?);
+        }
+
+        Ok(proto::exchange::Workflow {
+            id: obj.id,
+            name: obj.name,
+            nodes,
+            edges,
+            status: proto::exchange::WorkflowStatus::try_from(obj.status)? as i32,
+            progress,
+            created_at: obj.created_at.timestamp_millis(),
+            finished_at: obj
+                .finished_at
+                .map(|finished_at| finished_at.timestamp_millis()),
+        })
+    }
+}
+
+impl TryFrom<proto::exchange::Workflow> for Workflow {
+    type Error = anyhow::Error;
+
+    fn try_from(obj: proto::exchange::Workflow) -> Result<Workflow, Self::Error> {
+        let mut nodes: Vec<WorkflowNode> = Vec::new();
+        for node in obj.nodes {
+            nodes.push(WorkflowNode::try_from(node)?);
+        }
+        let mut edges: Vec<WorkflowEdge> = Vec::new();
+        for edge in obj.edges {
+            edges.push(WorkflowEdge::try_from(edge)?);
+        }
+
+        let mut progress: collections::HashMap<String, NodeProgress> = collections::HashMap::new();
+        for node_progress in obj.progress {
+            progress.insert(
+                node_progress.node,
+                NodeProgress {
+                    status: NodeStatus::try_from(proto::exchange::WorkflowNodeStatus::try_from(
+                        node_progress.status,
+                    )?)?,
+                    event_id: node_progress.event_id,
+                    outcome: match node_progress.outcome {
+                        Some(outcome) => Some(EventStatus::try_from(outcome)?),
+                        None => None,
+                    },
+                },
+            );
+        }
+
+        let finished_at = match obj.finished_at {
+            Some(finished_at) => Some(
+                chrono::DateTime::from_timestamp_millis(finished_at)
+                    .ok_or(anyhow::anyhow!("invalid finished at: {}", finished_at))?,
+            ),
+            None => None,
+        };
+
+        Ok(Workflow {
+            id: obj.id,
+            name: obj.name,
+            nodes,
+            edges,
+            status: WorkflowStatus::try_from(proto::exchange::WorkflowStatus::try_from(
+                obj.status,
+            )?)?,
+            progress,
+            created_at: chrono::DateTime::from_timestamp_millis(obj.created_at)
+                .ok_or(anyhow::anyhow!("invalid created at: {}", obj.created_at))?,
+            finished_at,
+        })
+    }
+}
@@ -1,3 +1,4 @@
 pub mod exchange;
 pub mod rpc;
+pub mod storage;
 pub mod worker;
@@ -9,10 +9,47 @@ pub struct ExecCommandResp {
     #[prost(message, optional, tag = "1")]
     pub command_resp: ::core::option::Option<CommandResp>,
 }
+/// the commands are applied all-or-nothing, if any command fails every
+/// change made by the batch is rolled back
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExecBatchReq {
+    #[prost(message, repeated, tag = "1")]
+    pub commands: ::prost::alloc::vec::Vec<Command>,
+}
+/// a batch which was not applied fails with the status of the error of
+/// the command which failed
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExecBatchResp {
+    /// one response per command
+    #[prost(message, repeated, tag = "1")]
+    pub command_resps: ::prost::alloc::vec::Vec<CommandResp>,
+    #[prost(bool, tag = "2")]
+    pub applied: bool,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SubscribeReq {
+    #[prost(string, tag = "1")]
+    pub queue_name: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub prefetch: u64,
+    #[prost(string, tag = "3")]
+    pub worker_id: ::prost::alloc::string::String,
+    /// only events matching the selector are pushed to the worker
+    #[prost(message, optional, tag = "4")]
+    pub selector: ::core::option::Option<AttributeSelector>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SubscribeResp {
+    #[prost(message, optional, tag = "1")]
+    pub event: ::core::option::Option<Event>,
+}
 /// commands //////////////////////////
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Command {
-    #[prost(oneof = "command::Command", tags = "1, 2, 3, 4, 5")]
+    #[prost(
+        oneof = "command::Command",
+        tags = "1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39"
+    )]
     pub command: ::core::option::Option<command::Command>,
 }
 /// Nested message and enum types in `Command`.
@@ -29,12 +66,94 @@ pub mod command {
         UpdateEventStatus(super::UpdateEventStatus),
         #[prost(message, tag = "5")]
         CreateTransaction(super::CreateTransaction),
+        #[prost(message, tag = "6")]
+        ClaimEvents(super::ClaimEvents),
+        #[prost(message, tag = "7")]
+        Heartbeat(super::Heartbeat),
+        #[prost(message, tag = "8")]
+        AddCommandTrigger(::prost::alloc::boxed::Box<super::AddCommandTrigger>),
+        #[prost(message, tag = "9")]
+        ListDeadLetters(super::ListDeadLetters),
+        #[prost(message, tag = "10")]
+        GetDeadLetter(super::GetDeadLetter),
+        #[prost(message, tag = "11")]
+        ReplayDeadLetters(super::ReplayDeadLetters),
+        #[prost(message, tag = "12")]
+        PurgeDeadLetters(super::PurgeDeadLetters),
+        #[prost(message, tag = "13")]
+        AddSchedule(super::AddSchedule),
+        #[prost(message, tag = "14")]
+        RemoveSchedule(super::RemoveSchedule),
+        #[prost(message, tag = "15")]
+        ListSchedules(super::ListSchedules),
+        #[prost(message, tag = "16")]
+        FireSchedule(super::FireSchedule),
+        #[prost(message, tag = "17")]
+        AddBinding(super::AddBinding),
+        #[prost(message, tag = "18")]
+        RemoveBinding(super::RemoveBinding),
+        #[prost(message, tag = "19")]
+        ListBindings(super::ListBindings),
+        #[prost(message, tag = "20")]
+        Publish(super::Publish),
+        #[prost(message, tag = "21")]
+        SubmitWorkflow(super::SubmitWorkflow),
+        #[prost(message, tag = "22")]
+        GetWorkflow(super::GetWorkflow),
+        #[prost(message, tag = "23")]
+        AdvanceWorkflow(super::AdvanceWorkflow),
+        #[prost(message, tag = "24")]
+        AddJoinTrigger(super::AddJoinTrigger),
+        #[prost(message, tag = "25")]
+        RemoveJoinTrigger(super::RemoveJoinTrigger),
+        #[prost(message, tag = "26")]
+        GetJoinTrigger(super::GetJoinTrigger),
+        #[prost(message, tag = "27")]
+        GetTransaction(super::GetTransaction),
+        #[prost(message, tag = "28")]
+        ListTransactions(super::ListTransactions),
+        #[prost(message, tag = "29")]
+        AbortTransaction(super::AbortTransaction),
+        #[prost(message, tag = "30")]
+        GetEvent(super::GetEvent),
+        #[prost(message, tag = "31")]
+        ListEvents(super::ListEvents),
+        #[prost(message, tag = "32")]
+        ListQueues(super::ListQueues),
+        #[prost(message, tag = "33")]
+        DescribeQueue(super::DescribeQueue),
+        #[prost(message, tag = "34")]
+        DeleteQueue(super::DeleteQueue),
+        #[prost(message, tag = "35")]
+        PurgeQueue(super::PurgeQueue),
+        #[prost(message, tag = "36")]
+        PauseQueue(super::PauseQueue),
+        #[prost(message, tag = "37")]
+        ResumeQueue(super::ResumeQueue),
+        #[prost(message, tag = "38")]
+        UpdateQueueConfig(super::UpdateQueueConfig),
+        #[prost(message, tag = "39")]
+        DeliverEvent(super::DeliverEvent),
     }
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct AddQueue {
     #[prost(string, tag = "1")]
     pub name: ::prost::alloc::string::String,
+    /// superseded by config.retry_policy, only used when the config does
+    /// not define a retry policy
+    #[prost(message, optional, tag = "2")]
+    pub retry_policy: ::core::option::Option<RetryPolicy>,
+    /// events ending in a failure status are kept in the queue's
+    /// dead-letter queue when set
+    #[prost(message, optional, tag = "3")]
+    pub dead_letter: ::core::option::Option<DeadLetterConfig>,
+    /// queued events gain one priority level for every interval in
+    /// milliseconds they wait, no aging when unset
+    #[prost(uint64, optional, tag = "4")]
+    pub priority_aging_ms: ::core::option::Option<u64>,
+    #[prost(message, optional, tag = "5")]
+    pub config: ::core::option::Option<QueueConfig>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct AddEvent {
@@ -42,6 +161,14 @@ pub struct AddEvent {
     pub queue_name: ::prost::alloc::string::String,
     #[prost(message, optional, tag = "2")]
     pub event: ::core::option::Option<Event>,
+    /// delay in milliseconds from when the command is applied before the
+    /// event is claimable, takes precedence over the event's not_before
+    #[prost(uint64, optional, tag = "3")]
+    pub delay_ms: ::core::option::Option<u64>,
+    /// a key seen again within the queue's dedup window adds nothing and
+    /// returns the id assigned the first time
+    #[prost(string, optional, tag = "4")]
+    pub dedup_key: ::core::option::Option<::prost::alloc::string::String>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct AddEvents {
@@ -49,6 +176,13 @@ pub struct AddEvents {
     pub queue_name: ::prost::alloc::string::String,
     #[prost(message, repeated, tag = "2")]
     pub events: ::prost::alloc::vec::Vec<Event>,
+    /// applied to every event, see AddEvent
+    #[prost(uint64, optional, tag = "3")]
+    pub delay_ms: ::core::option::Option<u64>,
+    /// identifies the whole batch, a repeated key returns the ids assigned
+    /// the first time, see AddEvent
+    #[prost(string, optional, tag = "4")]
+    pub dedup_key: ::core::option::Option<::prost::alloc::string::String>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct UpdateEventStatus {
@@ -59,6 +193,8 @@ pub struct UpdateEventStatus {
     #[prost(message, optional, tag = "3")]
     pub status: ::core::option::Option<Status>,
 }
+/// opens a transaction for a queued or running event, fails if the
+/// event already has one
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct CreateTransaction {
     #[prost(string, tag = "1")]
@@ -66,10 +202,312 @@ pub struct CreateTransaction {
     #[prost(uint64, tag = "2")]
     pub event_id: u64,
 }
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ClaimEvents {
+    #[prost(string, tag = "1")]
+    pub queue_name: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub max: u64,
+    #[prost(string, tag = "3")]
+    pub worker_id: ::prost::alloc::string::String,
+    /// only events matching the selector are claimed
+    #[prost(message, optional, tag = "4")]
+    pub selector: ::core::option::Option<AttributeSelector>,
+}
+/// keeps a running event alive, once a worker sent a heartbeat for an
+/// attempt it has to keep sending them within the heartbeat interval or
+/// the event is moved to MissedHeartbeat
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Heartbeat {
+    #[prost(string, tag = "1")]
+    pub queue_name: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub event_id: u64,
+}
+/// runs the command once the event's status changes to the one
+/// described by the trigger
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AddCommandTrigger {
+    #[prost(string, tag = "1")]
+    pub queue_name: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub event_id: u64,
+    #[prost(message, optional, tag = "3")]
+    pub trigger: ::core::option::Option<Trigger>,
+    #[prost(message, optional, boxed, tag = "4")]
+    pub command: ::core::option::Option<::prost::alloc::boxed::Box<Command>>,
+}
+/// dead-lettered events ordered by id, starting after the given id
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListDeadLetters {
+    #[prost(string, tag = "1")]
+    pub queue_name: ::prost::alloc::string::String,
+    #[prost(uint64, optional, tag = "2")]
+    pub after_event_id: ::core::option::Option<u64>,
+    /// 0 returns every dead-lettered event
+    #[prost(uint64, tag = "3")]
+    pub limit: u64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetDeadLetter {
+    #[prost(string, tag = "1")]
+    pub queue_name: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub event_id: u64,
+}
+/// moves dead-lettered events back to the queue, every event is replayed
+/// when no ids are given. Replayed events go through the queue's overflow
+/// policy like added ones, events left dead-lettered by the dead-letter
+/// policy are not part of the response.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ReplayDeadLetters {
+    #[prost(string, tag = "1")]
+    pub queue_name: ::prost::alloc::string::String,
+    #[prost(uint64, repeated, tag = "2")]
+    pub event_ids: ::prost::alloc::vec::Vec<u64>,
+}
+/// every dead-lettered event is purged when no ids are given
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PurgeDeadLetters {
+    #[prost(string, tag = "1")]
+    pub queue_name: ::prost::alloc::string::String,
+    #[prost(uint64, repeated, tag = "2")]
+    pub event_ids: ::prost::alloc::vec::Vec<u64>,
+}
+/// materializes an event from the template into the queue at every
+/// fire time of the cron expression, the template is validated against
+/// the queue when the schedule is added
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AddSchedule {
+    #[prost(string, tag = "1")]
+    pub queue_name: ::prost::alloc::string::String,
+    /// five fields, or six and seven with seconds and years
+    #[prost(string, tag = "2")]
+    pub cron_expr: ::prost::alloc::string::String,
+    /// IANA timezone name the expression is evaluated in, defaults to UTC
+    #[prost(string, tag = "3")]
+    pub timezone: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "4")]
+    pub event_template: ::core::option::Option<Event>,
+    #[prost(enumeration = "MissedFirePolicy", tag = "5")]
+    pub missed_fire_policy: i32,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct RemoveSchedule {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+}
+/// lists the schedules of the queue, or every schedule when empty
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListSchedules {
+    #[prost(string, tag = "1")]
+    pub queue_name: ::prost::alloc::string::String,
+}
+/// materializes the fire times of the schedule which are due, applied
+/// by the exchange as schedules come due, internal to the exchange and
+/// rejected when sent by clients, fire times refused by the queue are
+/// dropped and the schedule moves on to its next fire time
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct FireSchedule {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+}
+/// routes events published to topics matching the pattern to the queue
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AddBinding {
+    /// dot separated words, * matches exactly one word and # matches zero
+    /// or more words
+    #[prost(string, tag = "1")]
+    pub topic_pattern: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub queue_name: ::prost::alloc::string::String,
+    /// only events matching the selector are routed to the queue
+    #[prost(message, optional, tag = "3")]
+    pub selector: ::core::option::Option<AttributeSelector>,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct RemoveBinding {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+}
+/// lists the bindings of the queue, or every binding when empty
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListBindings {
+    #[prost(string, tag = "1")]
+    pub queue_name: ::prost::alloc::string::String,
+}
+/// copies the event into every queue with a binding matching the topic,
+/// once per queue even when several of its bindings match
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Publish {
+    #[prost(string, tag = "1")]
+    pub topic: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub event: ::core::option::Option<Event>,
+}
+/// starts a workflow instance, adding the events of the nodes without
+/// incoming edges, every other node's event is added once its upstream
+/// nodes finished with a status matching the edges, a node whose event is
+/// refused by its queue fails as Errored
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SubmitWorkflow {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "2")]
+    pub nodes: ::prost::alloc::vec::Vec<WorkflowNode>,
+    #[prost(message, repeated, tag = "3")]
+    pub edges: ::prost::alloc::vec::Vec<WorkflowEdge>,
+}
+/// finished workflows are removed once the exchange's workflow retention
+/// has passed
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct GetWorkflow {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+}
+/// records the final status of a node's event and starts the nodes it
+/// unblocks, applied by the triggers of the node events, internal to the
+/// exchange and rejected when sent by clients
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AdvanceWorkflow {
+    #[prost(uint64, tag = "1")]
+    pub workflow_id: u64,
+    #[prost(string, tag = "2")]
+    pub node: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "3")]
+    pub status: ::core::option::Option<Status>,
+}
+/// runs the commands once, when the final statuses of the events, which
+/// may be in different queues, satisfy the condition; the join trigger
+/// is dropped without running once the condition can no longer be met
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AddJoinTrigger {
+    #[prost(message, repeated, tag = "1")]
+    pub events: ::prost::alloc::vec::Vec<EventRef>,
+    #[prost(message, optional, tag = "2")]
+    pub condition: ::core::option::Option<JoinCondition>,
+    #[prost(message, repeated, tag = "3")]
+    pub commands: ::prost::alloc::vec::Vec<Command>,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct RemoveJoinTrigger {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct GetJoinTrigger {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetTransaction {
+    #[prost(string, tag = "1")]
+    pub queue_name: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub transaction_id: u64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListTransactions {
+    #[prost(string, tag = "1")]
+    pub queue_name: ::prost::alloc::string::String,
+}
+/// drops the transaction and its command triggers without running them,
+/// the event itself is left untouched
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AbortTransaction {
+    #[prost(string, tag = "1")]
+    pub queue_name: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub transaction_id: u64,
+}
+/// finished events are no longer kept by the queue, failed events can
+/// still be found among its dead letters
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetEvent {
+    #[prost(string, tag = "1")]
+    pub queue_name: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub event_id: u64,
+}
+/// lists the queued and running events of the queue ordered by id
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListEvents {
+    #[prost(string, tag = "1")]
+    pub queue_name: ::prost::alloc::string::String,
+    /// only events with the status are listed when set
+    #[prost(message, optional, tag = "2")]
+    pub status: ::core::option::Option<Status>,
+    #[prost(uint64, optional, tag = "3")]
+    pub after_event_id: ::core::option::Option<u64>,
+    /// 0 returns every matching event
+    #[prost(uint64, tag = "4")]
+    pub limit: u64,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct ListQueues {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DescribeQueue {
+    #[prost(string, tag = "1")]
+    pub queue_name: ::prost::alloc::string::String,
+}
+/// removes the queue with its events, subscribers, bindings and schedules,
+/// refused while workflows wait on any of its events
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DeleteQueue {
+    #[prost(string, tag = "1")]
+    pub queue_name: ::prost::alloc::string::String,
+    /// fails instead when the queue has running events
+    #[prost(bool, tag = "2")]
+    pub refuse_if_running: bool,
+}
+/// removes the queue's events without running their command triggers,
+/// refused while join triggers or workflows wait on any of the events
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PurgeQueue {
+    #[prost(string, tag = "1")]
+    pub queue_name: ::prost::alloc::string::String,
+    /// only events with the status are removed when set
+    #[prost(message, optional, tag = "2")]
+    pub status: ::core::option::Option<Status>,
+}
+/// a paused queue accepts new events but none can be claimed
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PauseQueue {
+    #[prost(string, tag = "1")]
+    pub queue_name: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ResumeQueue {
+    #[prost(string, tag = "1")]
+    pub queue_name: ::prost::alloc::string::String,
+}
+/// replaces the queue's config, events already in the queue are kept
+/// even if they exceed the new limits
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UpdateQueueConfig {
+    #[prost(string, tag = "1")]
+    pub queue_name: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub config: ::core::option::Option<QueueConfig>,
+}
+/// records an event pushed to a subscriber in the write-ahead log,
+/// internal to the exchange and rejected when sent by clients
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DeliverEvent {
+    #[prost(string, tag = "1")]
+    pub queue_name: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub event_id: u64,
+    #[prost(string, tag = "3")]
+    pub worker_id: ::prost::alloc::string::String,
+}
 /// command responses /////////////////
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct CommandResp {
-    #[prost(oneof = "command_resp::CommandResp", tags = "1, 2, 3, 4, 5, 6")]
+    #[prost(
+        oneof = "command_resp::CommandResp",
+        tags = "1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40"
+    )]
     pub command_resp: ::core::option::Option<command_resp::CommandResp>,
 }
 /// Nested message and enum types in `CommandResp`.
@@ -88,43 +526,504 @@ pub mod command_resp {
         CreateTransactionResp(super::CreateTransactionResp),
         #[prost(message, tag = "6")]
         ErrorResp(super::ErrorResp),
+        #[prost(message, tag = "7")]
+        ClaimEventsResp(super::ClaimEventsResp),
+        #[prost(message, tag = "8")]
+        HeartbeatResp(super::HeartbeatResp),
+        #[prost(message, tag = "9")]
+        AddCommandTriggerResp(super::AddCommandTriggerResp),
+        #[prost(message, tag = "10")]
+        ListDeadLettersResp(super::ListDeadLettersResp),
+        #[prost(message, tag = "11")]
+        GetDeadLetterResp(super::GetDeadLetterResp),
+        #[prost(message, tag = "12")]
+        ReplayDeadLettersResp(super::ReplayDeadLettersResp),
+        #[prost(message, tag = "13")]
+        PurgeDeadLettersResp(super::PurgeDeadLettersResp),
+        #[prost(message, tag = "14")]
+        AddScheduleResp(super::AddScheduleResp),
+        #[prost(message, tag = "15")]
+        RemoveScheduleResp(super::RemoveScheduleResp),
+        #[prost(message, tag = "16")]
+        ListSchedulesResp(super::ListSchedulesResp),
+        #[prost(message, tag = "17")]
+        FireScheduleResp(super::FireScheduleResp),
+        #[prost(message, tag = "18")]
+        AddBindingResp(super::AddBindingResp),
+        #[prost(message, tag = "19")]
+        RemoveBindingResp(super::RemoveBindingResp),
+        #[prost(message, tag = "20")]
+        ListBindingsResp(super::ListBindingsResp),
+        #[prost(message, tag = "21")]
+        PublishResp(super::PublishResp),
+        #[prost(message, tag = "22")]
+        SubmitWorkflowResp(super::SubmitWorkflowResp),
+        #[prost(message, tag = "23")]
+        GetWorkflowResp(super::GetWorkflowResp),
+        #[prost(message, tag = "24")]
+        AdvanceWorkflowResp(super::AdvanceWorkflowResp),
+        #[prost(message, tag = "25")]
+        AddJoinTriggerResp(super::AddJoinTriggerResp),
+        #[prost(message, tag = "26")]
+        RemoveJoinTriggerResp(super::RemoveJoinTriggerResp),
+        #[prost(message, tag = "27")]
+        GetJoinTriggerResp(super::GetJoinTriggerResp),
+        #[prost(message, tag = "28")]
+        GetTransactionResp(super::GetTransactionResp),
+        #[prost(message, tag = "29")]
+        ListTransactionsResp(super::ListTransactionsResp),
+        #[prost(message, tag = "30")]
+        AbortTransactionResp(super::AbortTransactionResp),
+        #[prost(message, tag = "31")]
+        GetEventResp(super::GetEventResp),
+        #[prost(message, tag = "32")]
+        ListEventsResp(super::ListEventsResp),
+        #[prost(message, tag = "33")]
+        ListQueuesResp(super::ListQueuesResp),
+        #[prost(message, tag = "34")]
+        DescribeQueueResp(super::DescribeQueueResp),
+        #[prost(message, tag = "35")]
+        DeleteQueueResp(super::DeleteQueueResp),
+        #[prost(message, tag = "36")]
+        PurgeQueueResp(super::PurgeQueueResp),
+        #[prost(message, tag = "37")]
+        PauseQueueResp(super::PauseQueueResp),
+        #[prost(message, tag = "38")]
+        ResumeQueueResp(super::ResumeQueueResp),
+        #[prost(message, tag = "39")]
+        UpdateQueueConfigResp(super::UpdateQueueConfigResp),
+        #[prost(message, tag = "40")]
+        DeliverEventResp(super::DeliverEventResp),
     }
 }
-#[derive(Clone, Copy, PartialEq, ::prost::Message)]
-pub struct AddQueueResp {}
-#[derive(Clone, Copy, PartialEq, ::prost::Message)]
-pub struct AddEventResp {
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct AddQueueResp {}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct AddEventResp {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+    /// the queue was full and its overflow policy added the event to the
+    /// dead letters
+    #[prost(bool, tag = "2")]
+    pub dead_lettered: bool,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AddEventsResp {
+    #[prost(uint64, repeated, tag = "1")]
+    pub ids: ::prost::alloc::vec::Vec<u64>,
+    /// ids of the events added to the dead letters as the queue was full
+    #[prost(uint64, repeated, tag = "2")]
+    pub dead_lettered_ids: ::prost::alloc::vec::Vec<u64>,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct UpdateEventStatusResp {}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct CreateTransactionResp {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ClaimEventsResp {
+    #[prost(message, repeated, tag = "1")]
+    pub events: ::prost::alloc::vec::Vec<Event>,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct HeartbeatResp {}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct AddCommandTriggerResp {
+    #[prost(uint64, tag = "1")]
+    pub transaction_id: u64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListDeadLettersResp {
+    #[prost(message, repeated, tag = "1")]
+    pub dead_letters: ::prost::alloc::vec::Vec<DeadLetter>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetDeadLetterResp {
+    #[prost(message, optional, tag = "1")]
+    pub dead_letter: ::core::option::Option<DeadLetter>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ReplayDeadLettersResp {
+    #[prost(uint64, repeated, tag = "1")]
+    pub event_ids: ::prost::alloc::vec::Vec<u64>,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct PurgeDeadLettersResp {
+    #[prost(uint64, tag = "1")]
+    pub purged: u64,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct AddScheduleResp {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+    /// unix timestamp in milliseconds
+    #[prost(int64, optional, tag = "2")]
+    pub next_fire_time: ::core::option::Option<i64>,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct RemoveScheduleResp {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListSchedulesResp {
+    #[prost(message, repeated, tag = "1")]
+    pub schedules: ::prost::alloc::vec::Vec<Schedule>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FireScheduleResp {
+    #[prost(uint64, repeated, tag = "1")]
+    pub event_ids: ::prost::alloc::vec::Vec<u64>,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct AddBindingResp {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct RemoveBindingResp {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListBindingsResp {
+    #[prost(message, repeated, tag = "1")]
+    pub bindings: ::prost::alloc::vec::Vec<Binding>,
+}
+/// empty when no binding matched the topic
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PublishResp {
+    #[prost(message, repeated, tag = "1")]
+    pub events: ::prost::alloc::vec::Vec<RoutedEvent>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RoutedEvent {
+    #[prost(string, tag = "1")]
+    pub queue_name: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub id: u64,
+    /// see AddEventResp
+    #[prost(bool, tag = "3")]
+    pub dead_lettered: bool,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct SubmitWorkflowResp {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetWorkflowResp {
+    #[prost(message, optional, tag = "1")]
+    pub workflow: ::core::option::Option<Workflow>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AdvanceWorkflowResp {
+    /// events of the nodes which were started
+    #[prost(uint64, repeated, tag = "1")]
+    pub event_ids: ::prost::alloc::vec::Vec<u64>,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct AddJoinTriggerResp {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct RemoveJoinTriggerResp {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetJoinTriggerResp {
+    #[prost(message, optional, tag = "1")]
+    pub join_trigger: ::core::option::Option<JoinTrigger>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetTransactionResp {
+    #[prost(message, optional, tag = "1")]
+    pub transaction: ::core::option::Option<Transaction>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListTransactionsResp {
+    #[prost(message, repeated, tag = "1")]
+    pub transactions: ::prost::alloc::vec::Vec<Transaction>,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct AbortTransactionResp {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetEventResp {
+    #[prost(message, optional, tag = "1")]
+    pub event: ::core::option::Option<Event>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListEventsResp {
+    #[prost(message, repeated, tag = "1")]
+    pub events: ::prost::alloc::vec::Vec<Event>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListQueuesResp {
+    /// ordered by name
+    #[prost(string, repeated, tag = "1")]
+    pub names: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DescribeQueueResp {
+    #[prost(message, optional, tag = "1")]
+    pub description: ::core::option::Option<QueueDescription>,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct DeleteQueueResp {}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct PurgeQueueResp {
+    #[prost(uint64, tag = "1")]
+    pub purged: u64,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct PauseQueueResp {}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct ResumeQueueResp {}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct UpdateQueueConfigResp {}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct DeliverEventResp {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ErrorResp {
+    #[prost(string, tag = "1")]
+    pub error_msg: ::prost::alloc::string::String,
+    #[prost(enumeration = "ErrorCode", tag = "2")]
+    pub code: i32,
+}
+/// shared entities //////////////////
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Event {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+    #[prost(bytes = "vec", tag = "2")]
+    pub data: ::prost::alloc::vec::Vec<u8>,
+    #[prost(message, repeated, tag = "3")]
+    pub attributes: ::prost::alloc::vec::Vec<Attribute>,
+    #[prost(message, optional, tag = "4")]
+    pub status: ::core::option::Option<Status>,
+    /// maximum time in milliseconds the event may be running before it
+    /// is moved to Timedout, has to be positive
+    #[prost(uint64, optional, tag = "5")]
+    pub time_to_run_ms: ::core::option::Option<u64>,
+    /// unix timestamp in milliseconds, set by the exchange while running
+    #[prost(int64, optional, tag = "6")]
+    pub deadline: ::core::option::Option<i64>,
+    /// overrides the retry policy of the queue
+    #[prost(message, optional, tag = "7")]
+    pub retry_policy: ::core::option::Option<RetryPolicy>,
+    /// attempt the event is on, starting at 1, set by the exchange and
+    /// ignored when adding events
+    #[prost(uint32, tag = "8")]
+    pub attempt: u32,
+    /// unix timestamp in milliseconds, the event is not claimable before it
+    #[prost(int64, optional, tag = "9")]
+    pub not_before: ::core::option::Option<i64>,
+    /// failed attempts, oldest first
+    #[prost(message, repeated, tag = "10")]
+    pub failures: ::prost::alloc::vec::Vec<Failure>,
+    /// higher priorities are claimed first, FIFO within a priority
+    #[prost(uint32, tag = "11")]
+    pub priority: u32,
+    /// time in milliseconds the event may stay queued before it is moved
+    /// to Expired
+    #[prost(uint64, optional, tag = "12")]
+    pub ttl_ms: ::core::option::Option<u64>,
+    /// unix timestamp in milliseconds, set by the exchange when the event
+    /// is queued with a ttl
+    #[prost(int64, optional, tag = "13")]
+    pub expires_at: ::core::option::Option<i64>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Failure {
+    #[prost(uint32, tag = "1")]
+    pub attempt: u32,
+    #[prost(message, optional, tag = "2")]
+    pub status: ::core::option::Option<Status>,
+    #[prost(string, optional, tag = "3")]
+    pub worker_id: ::core::option::Option<::prost::alloc::string::String>,
+    /// unix timestamp in milliseconds
+    #[prost(int64, tag = "4")]
+    pub time: i64,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct DeadLetterConfig {
+    /// oldest dead-lettered events are dropped past this count, 0 keeps
+    /// every event
+    #[prost(uint64, tag = "1")]
+    pub max_events: u64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DeadLetter {
+    #[prost(message, optional, tag = "1")]
+    pub event: ::core::option::Option<Event>,
+    /// unix timestamp in milliseconds
+    #[prost(int64, tag = "2")]
+    pub dead_lettered_at: i64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct QueueDescription {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    /// includes delayed events which are not claimable yet
+    #[prost(uint64, tag = "2")]
+    pub queued_events: u64,
+    #[prost(uint64, tag = "3")]
+    pub running_events: u64,
+    #[prost(uint64, tag = "4")]
+    pub dead_letters: u64,
+    #[prost(uint64, tag = "5")]
+    pub transactions: u64,
+    /// time in milliseconds the longest waiting claimable event has been
+    /// queued, unset when there is none
+    #[prost(uint64, optional, tag = "6")]
+    pub oldest_queued_age_ms: ::core::option::Option<u64>,
+    #[prost(bool, tag = "7")]
+    pub paused: bool,
+    #[prost(message, optional, tag = "8")]
+    pub config: ::core::option::Option<QueueConfig>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct QueueConfig {
+    /// includes delayed events, unlimited when unset
+    #[prost(uint64, optional, tag = "1")]
+    pub max_queued_events: ::core::option::Option<u64>,
+    #[prost(uint64, optional, tag = "2")]
+    pub max_payload_bytes: ::core::option::Option<u64>,
+    #[prost(uint64, optional, tag = "3")]
+    pub max_attributes: ::core::option::Option<u64>,
+    /// defaults for events which do not define their own
+    #[prost(message, optional, tag = "4")]
+    pub retry_policy: ::core::option::Option<RetryPolicy>,
+    #[prost(uint64, optional, tag = "5")]
+    pub time_to_run_ms: ::core::option::Option<u64>,
+    /// overrides the exchange's heartbeat interval for the queue's events,
+    /// when set every running event has to heartbeat, not only those whose
+    /// worker sent a heartbeat
+    #[prost(uint64, optional, tag = "6")]
+    pub heartbeat_interval_ms: ::core::option::Option<u64>,
+    #[prost(enumeration = "OverflowPolicy", tag = "7")]
+    pub overflow_policy: i32,
+    /// overrides the exchange's dedup window for the queue's dedup keys
+    #[prost(uint64, optional, tag = "8")]
+    pub dedup_window_ms: ::core::option::Option<u64>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Transaction {
     #[prost(uint64, tag = "1")]
     pub id: u64,
+    #[prost(uint64, tag = "2")]
+    pub event_id: u64,
+    #[prost(message, repeated, tag = "3")]
+    pub command_triggers: ::prost::alloc::vec::Vec<CommandTrigger>,
+    /// unix timestamp in milliseconds
+    #[prost(int64, optional, tag = "4")]
+    pub last_heartbeat_time: ::core::option::Option<i64>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct AddEventsResp {
-    #[prost(uint64, repeated, tag = "1")]
-    pub ids: ::prost::alloc::vec::Vec<u64>,
+pub struct CommandTrigger {
+    #[prost(message, optional, tag = "1")]
+    pub trigger: ::core::option::Option<Trigger>,
+    #[prost(message, repeated, tag = "2")]
+    pub commands: ::prost::alloc::vec::Vec<Command>,
 }
-#[derive(Clone, Copy, PartialEq, ::prost::Message)]
-pub struct UpdateEventStatusResp {}
-#[derive(Clone, Copy, PartialEq, ::prost::Message)]
-pub struct CreateTransactionResp {
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Schedule {
     #[prost(uint64, tag = "1")]
     pub id: u64,
+    #[prost(string, tag = "2")]
+    pub queue_name: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub cron_expr: ::prost::alloc::string::String,
+    #[prost(string, tag = "4")]
+    pub timezone: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "5")]
+    pub event_template: ::core::option::Option<Event>,
+    #[prost(enumeration = "MissedFirePolicy", tag = "6")]
+    pub missed_fire_policy: i32,
+    /// unix timestamps in milliseconds
+    #[prost(int64, optional, tag = "7")]
+    pub next_fire_time: ::core::option::Option<i64>,
+    #[prost(int64, optional, tag = "8")]
+    pub last_fire_time: ::core::option::Option<i64>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct ErrorResp {
+pub struct Binding {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+    #[prost(string, tag = "2")]
+    pub topic_pattern: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub queue_name: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "4")]
+    pub selector: ::core::option::Option<AttributeSelector>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WorkflowNode {
+    /// unique within the workflow
     #[prost(string, tag = "1")]
-    pub error_msg: ::prost::alloc::string::String,
+    pub name: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub queue_name: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "3")]
+    pub event_template: ::core::option::Option<Event>,
 }
-/// shared entities //////////////////
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct Event {
+pub struct WorkflowEdge {
+    #[prost(string, tag = "1")]
+    pub from: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub to: ::prost::alloc::string::String,
+    /// status of the from node's event which unblocks the to node, a node
+    /// with several incoming edges waits for every upstream node
+    #[prost(message, optional, tag = "3")]
+    pub trigger: ::core::option::Option<Trigger>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Workflow {
     #[prost(uint64, tag = "1")]
     pub id: u64,
-    #[prost(bytes = "vec", tag = "2")]
-    pub data: ::prost::alloc::vec::Vec<u8>,
+    #[prost(string, tag = "2")]
+    pub name: ::prost::alloc::string::String,
     #[prost(message, repeated, tag = "3")]
-    pub attributes: ::prost::alloc::vec::Vec<Attribute>,
+    pub nodes: ::prost::alloc::vec::Vec<WorkflowNode>,
+    #[prost(message, repeated, tag = "4")]
+    pub edges: ::prost::alloc::vec::Vec<WorkflowEdge>,
+    #[prost(enumeration = "WorkflowStatus", tag = "5")]
+    pub status: i32,
+    /// one entry per node, in the order of nodes
+    #[prost(message, repeated, tag = "6")]
+    pub progress: ::prost::alloc::vec::Vec<WorkflowNodeProgress>,
+    /// unix timestamps in milliseconds
+    #[prost(int64, tag = "7")]
+    pub created_at: i64,
+    #[prost(int64, optional, tag = "8")]
+    pub finished_at: ::core::option::Option<i64>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WorkflowNodeProgress {
+    #[prost(string, tag = "1")]
+    pub node: ::prost::alloc::string::String,
+    #[prost(enumeration = "WorkflowNodeStatus", tag = "2")]
+    pub status: i32,
+    /// set once the node is started
+    #[prost(uint64, optional, tag = "3")]
+    pub event_id: ::core::option::Option<u64>,
+    /// final status of the node's event
     #[prost(message, optional, tag = "4")]
-    pub status: ::core::option::Option<Status>,
+    pub outcome: ::core::option::Option<Status>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RetryPolicy {
+    /// total number of attempts, including the first one
+    #[prost(uint32, tag = "1")]
+    pub max_attempts: u32,
+    #[prost(uint64, tag = "2")]
+    pub initial_backoff_ms: u64,
+    #[prost(uint64, tag = "3")]
+    pub max_backoff_ms: u64,
+    #[prost(double, tag = "4")]
+    pub multiplier: f64,
+    /// fraction of the backoff randomly added or removed, from 0 to 1
+    #[prost(double, tag = "5")]
+    pub jitter: f64,
+    /// defaults to Errored, MissedHeartbeat and Timedout when empty
+    #[prost(message, repeated, tag = "6")]
+    pub retryable_statuses: ::prost::alloc::vec::Vec<Status>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Attribute {
@@ -133,9 +1032,45 @@ pub struct Attribute {
     #[prost(string, tag = "2")]
     pub value: ::prost::alloc::string::String,
 }
+/// matches events satisfying every matcher, an empty selector matches
+/// every event
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AttributeSelector {
+    #[prost(message, repeated, tag = "1")]
+    pub matchers: ::prost::alloc::vec::Vec<AttributeMatcher>,
+}
+/// satisfied when any attribute with the name matches
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AttributeMatcher {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(oneof = "attribute_matcher::MatchType", tags = "2, 3, 4, 5")]
+    pub match_type: ::core::option::Option<attribute_matcher::MatchType>,
+}
+/// Nested message and enum types in `AttributeMatcher`.
+pub mod attribute_matcher {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum MatchType {
+        #[prost(string, tag = "2")]
+        Equals(::prost::alloc::string::String),
+        #[prost(message, tag = "3")]
+        OneOf(super::AttributeValues),
+        #[prost(message, tag = "4")]
+        Exists(super::Exists),
+        #[prost(string, tag = "5")]
+        Prefix(::prost::alloc::string::String),
+    }
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AttributeValues {
+    #[prost(string, repeated, tag = "1")]
+    pub values: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct Exists {}
 #[derive(Clone, Copy, PartialEq, ::prost::Message)]
 pub struct Status {
-    #[prost(oneof = "status::DataType", tags = "1, 2, 3, 4, 5, 6")]
+    #[prost(oneof = "status::DataType", tags = "1, 2, 3, 4, 5, 6, 7")]
     pub data_type: ::core::option::Option<status::DataType>,
 }
 /// Nested message and enum types in `Status`.
@@ -154,8 +1089,98 @@ pub mod status {
         MissedHeartbeat(super::MissedHeartbeat),
         #[prost(message, tag = "6")]
         Timedout(super::Timedout),
+        #[prost(message, tag = "7")]
+        Expired(super::Expired),
+    }
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct Trigger {
+    #[prost(oneof = "trigger::TriggerType", tags = "1, 2, 3, 4, 5")]
+    pub trigger_type: ::core::option::Option<trigger::TriggerType>,
+}
+/// Nested message and enum types in `Trigger`.
+pub mod trigger {
+    #[derive(Clone, Copy, PartialEq, ::prost::Oneof)]
+    pub enum TriggerType {
+        #[prost(message, tag = "1")]
+        OnEventComplete(super::OnEventComplete),
+        #[prost(message, tag = "2")]
+        OnEventError(super::OnEventError),
+        #[prost(message, tag = "3")]
+        OnEventMissedHeartbeat(super::OnEventMissedHeartbeat),
+        #[prost(message, tag = "4")]
+        OnEventTimedout(super::OnEventTimedout),
+        #[prost(message, tag = "5")]
+        OnEventExpired(super::OnEventExpired),
+    }
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EventRef {
+    #[prost(string, tag = "1")]
+    pub queue_name: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub event_id: u64,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct JoinCondition {
+    #[prost(oneof = "join_condition::ConditionType", tags = "1, 2, 3")]
+    pub condition_type: ::core::option::Option<join_condition::ConditionType>,
+}
+/// Nested message and enum types in `JoinCondition`.
+pub mod join_condition {
+    #[derive(Clone, Copy, PartialEq, ::prost::Oneof)]
+    pub enum ConditionType {
+        #[prost(message, tag = "1")]
+        OnAllComplete(super::OnAllComplete),
+        #[prost(message, tag = "2")]
+        OnAnyError(super::OnAnyError),
+        #[prost(message, tag = "3")]
+        OnQuorum(super::OnQuorum),
     }
 }
+/// every event completed
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct OnAllComplete {}
+/// any event ended in Errored, MissedHeartbeat, Timedout or Expired
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct OnAnyError {}
+/// at least count events completed
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct OnQuorum {
+    #[prost(uint64, tag = "1")]
+    pub count: u64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct JoinTrigger {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+    #[prost(message, repeated, tag = "2")]
+    pub events: ::prost::alloc::vec::Vec<JoinEvent>,
+    #[prost(message, optional, tag = "3")]
+    pub condition: ::core::option::Option<JoinCondition>,
+    #[prost(message, repeated, tag = "4")]
+    pub commands: ::prost::alloc::vec::Vec<Command>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct JoinEvent {
+    #[prost(string, tag = "1")]
+    pub queue_name: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub event_id: u64,
+    /// final status of the event, unset while it has not finished
+    #[prost(message, optional, tag = "3")]
+    pub outcome: ::core::option::Option<Status>,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct OnEventComplete {}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct OnEventError {}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct OnEventMissedHeartbeat {}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct OnEventTimedout {}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct OnEventExpired {}
 #[derive(Clone, Copy, PartialEq, ::prost::Message)]
 pub struct Queued {}
 #[derive(Clone, Copy, PartialEq, ::prost::Message)]
@@ -168,6 +1193,249 @@ pub struct Errored {}
 pub struct MissedHeartbeat {}
 #[derive(Clone, Copy, PartialEq, ::prost::Message)]
 pub struct Timedout {}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct Expired {}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum ErrorCode {
+    Unspecified = 0,
+    Internal = 1,
+    InvalidCommand = 2,
+    QueueNotFound = 3,
+    QueueAlreadyExists = 4,
+    TransactionNotFound = 5,
+    EventNotFound = 6,
+    EventNotRunning = 7,
+    DeadLetterNotFound = 8,
+    ScheduleNotFound = 9,
+    InvalidSchedule = 10,
+    BindingNotFound = 11,
+    InvalidBinding = 12,
+    WorkflowNotFound = 13,
+    InvalidWorkflow = 14,
+    JoinTriggerNotFound = 15,
+    InvalidJoinTrigger = 16,
+    TransactionAlreadyExists = 17,
+    QueueHasRunningEvents = 18,
+    QueueHasDependentEvents = 19,
+    QueueFull = 20,
+    PayloadTooLarge = 21,
+    TooManyAttributes = 22,
+    InvalidQueueConfig = 23,
+}
+impl ErrorCode {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "ERROR_CODE_UNSPECIFIED",
+            Self::Internal => "ERROR_CODE_INTERNAL",
+            Self::InvalidCommand => "ERROR_CODE_INVALID_COMMAND",
+            Self::QueueNotFound => "ERROR_CODE_QUEUE_NOT_FOUND",
+            Self::QueueAlreadyExists => "ERROR_CODE_QUEUE_ALREADY_EXISTS",
+            Self::TransactionNotFound => "ERROR_CODE_TRANSACTION_NOT_FOUND",
+            Self::EventNotFound => "ERROR_CODE_EVENT_NOT_FOUND",
+            Self::EventNotRunning => "ERROR_CODE_EVENT_NOT_RUNNING",
+            Self::DeadLetterNotFound => "ERROR_CODE_DEAD_LETTER_NOT_FOUND",
+            Self::ScheduleNotFound => "ERROR_CODE_SCHEDULE_NOT_FOUND",
+            Self::InvalidSchedule => "ERROR_CODE_INVALID_SCHEDULE",
+            Self::BindingNotFound => "ERROR_CODE_BINDING_NOT_FOUND",
+            Self::InvalidBinding => "ERROR_CODE_INVALID_BINDING",
+            Self::WorkflowNotFound => "ERROR_CODE_WORKFLOW_NOT_FOUND",
+            Self::InvalidWorkflow => "ERROR_CODE_INVALID_WORKFLOW",
+            Self::JoinTriggerNotFound => "ERROR_CODE_JOIN_TRIGGER_NOT_FOUND",
+            Self::InvalidJoinTrigger => "ERROR_CODE_INVALID_JOIN_TRIGGER",
+            Self::TransactionAlreadyExists => "ERROR_CODE_TRANSACTION_ALREADY_EXISTS",
+            Self::QueueHasRunningEvents => "ERROR_CODE_QUEUE_HAS_RUNNING_EVENTS",
+            Self::QueueHasDependentEvents => "ERROR_CODE_QUEUE_HAS_DEPENDENT_EVENTS",
+            Self::QueueFull => "ERROR_CODE_QUEUE_FULL",
+            Self::PayloadTooLarge => "ERROR_CODE_PAYLOAD_TOO_LARGE",
+            Self::TooManyAttributes => "ERROR_CODE_TOO_MANY_ATTRIBUTES",
+            Self::InvalidQueueConfig => "ERROR_CODE_INVALID_QUEUE_CONFIG",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "ERROR_CODE_UNSPECIFIED" => Some(Self::Unspecified),
+            "ERROR_CODE_INTERNAL" => Some(Self::Internal),
+            "ERROR_CODE_INVALID_COMMAND" => Some(Self::InvalidCommand),
+            "ERROR_CODE_QUEUE_NOT_FOUND" => Some(Self::QueueNotFound),
+            "ERROR_CODE_QUEUE_ALREADY_EXISTS" => Some(Self::QueueAlreadyExists),
+            "ERROR_CODE_TRANSACTION_NOT_FOUND" => Some(Self::TransactionNotFound),
+            "ERROR_CODE_EVENT_NOT_FOUND" => Some(Self::EventNotFound),
+            "ERROR_CODE_EVENT_NOT_RUNNING" => Some(Self::EventNotRunning),
+            "ERROR_CODE_DEAD_LETTER_NOT_FOUND" => Some(Self::DeadLetterNotFound),
+            "ERROR_CODE_SCHEDULE_NOT_FOUND" => Some(Self::ScheduleNotFound),
+            "ERROR_CODE_INVALID_SCHEDULE" => Some(Self::InvalidSchedule),
+            "ERROR_CODE_BINDING_NOT_FOUND" => Some(Self::BindingNotFound),
+            "ERROR_CODE_INVALID_BINDING" => Some(Self::InvalidBinding),
+            "ERROR_CODE_WORKFLOW_NOT_FOUND" => Some(Self::WorkflowNotFound),
+            "ERROR_CODE_INVALID_WORKFLOW" => Some(Self::InvalidWorkflow),
+            "ERROR_CODE_JOIN_TRIGGER_NOT_FOUND" => Some(Self::JoinTriggerNotFound),
+            "ERROR_CODE_INVALID_JOIN_TRIGGER" => Some(Self::InvalidJoinTrigger),
+            "ERROR_CODE_TRANSACTION_ALREADY_EXISTS" => {
+                Some(Self::TransactionAlreadyExists)
+            }
+            "ERROR_CODE_QUEUE_HAS_RUNNING_EVENTS" => Some(Self::QueueHasRunningEvents),
+            "ERROR_CODE_QUEUE_HAS_DEPENDENT_EVENTS" => {
+                Some(Self::QueueHasDependentEvents)
+            }
+            "ERROR_CODE_QUEUE_FULL" => Some(Self::QueueFull),
+            "ERROR_CODE_PAYLOAD_TOO_LARGE" => Some(Self::PayloadTooLarge),
+            "ERROR_CODE_TOO_MANY_ATTRIBUTES" => Some(Self::TooManyAttributes),
+            "ERROR_CODE_INVALID_QUEUE_CONFIG" => Some(Self::InvalidQueueConfig),
+            _ => None,
+        }
+    }
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum WorkflowStatus {
+    Unspecified = 0,
+    Running = 1,
+    /// every node finished or was skipped, failures were handled by edges
+    Complete = 2,
+    /// a node failed without an edge handling its status
+    Failed = 3,
+}
+impl WorkflowStatus {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "WORKFLOW_STATUS_UNSPECIFIED",
+            Self::Running => "WORKFLOW_STATUS_RUNNING",
+            Self::Complete => "WORKFLOW_STATUS_COMPLETE",
+            Self::Failed => "WORKFLOW_STATUS_FAILED",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "WORKFLOW_STATUS_UNSPECIFIED" => Some(Self::Unspecified),
+            "WORKFLOW_STATUS_RUNNING" => Some(Self::Running),
+            "WORKFLOW_STATUS_COMPLETE" => Some(Self::Complete),
+            "WORKFLOW_STATUS_FAILED" => Some(Self::Failed),
+            _ => None,
+        }
+    }
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum WorkflowNodeStatus {
+    Unspecified = 0,
+    Pending = 1,
+    Started = 2,
+    Complete = 3,
+    Failed = 4,
+    /// an upstream node finished with a status none of its edges match
+    Skipped = 5,
+}
+impl WorkflowNodeStatus {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "WORKFLOW_NODE_STATUS_UNSPECIFIED",
+            Self::Pending => "WORKFLOW_NODE_STATUS_PENDING",
+            Self::Started => "WORKFLOW_NODE_STATUS_STARTED",
+            Self::Complete => "WORKFLOW_NODE_STATUS_COMPLETE",
+            Self::Failed => "WORKFLOW_NODE_STATUS_FAILED",
+            Self::Skipped => "WORKFLOW_NODE_STATUS_SKIPPED",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "WORKFLOW_NODE_STATUS_UNSPECIFIED" => Some(Self::Unspecified),
+            "WORKFLOW_NODE_STATUS_PENDING" => Some(Self::Pending),
+            "WORKFLOW_NODE_STATUS_STARTED" => Some(Self::Started),
+            "WORKFLOW_NODE_STATUS_COMPLETE" => Some(Self::Complete),
+            "WORKFLOW_NODE_STATUS_FAILED" => Some(Self::Failed),
+            "WORKFLOW_NODE_STATUS_SKIPPED" => Some(Self::Skipped),
+            _ => None,
+        }
+    }
+}
+/// what to do with an event added to a queue at its max_queued_events
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum OverflowPolicy {
+    /// defaults to reject
+    Unspecified = 0,
+    /// the event is refused with ERROR_CODE_QUEUE_FULL
+    Reject = 1,
+    /// the oldest queued events are moved to Expired to make room, running
+    /// their triggers, events which do not fit an empty queue are refused
+    DropOldest = 2,
+    /// the event is added to the queue's dead letters instead and reported
+    /// as dead-lettered, requires the queue to have a dead-letter config
+    DeadLetter = 3,
+}
+impl OverflowPolicy {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "OVERFLOW_POLICY_UNSPECIFIED",
+            Self::Reject => "OVERFLOW_POLICY_REJECT",
+            Self::DropOldest => "OVERFLOW_POLICY_DROP_OLDEST",
+            Self::DeadLetter => "OVERFLOW_POLICY_DEAD_LETTER",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "OVERFLOW_POLICY_UNSPECIFIED" => Some(Self::Unspecified),
+            "OVERFLOW_POLICY_REJECT" => Some(Self::Reject),
+            "OVERFLOW_POLICY_DROP_OLDEST" => Some(Self::DropOldest),
+            "OVERFLOW_POLICY_DEAD_LETTER" => Some(Self::DeadLetter),
+            _ => None,
+        }
+    }
+}
+/// what to do with fire times which passed while the exchange was down
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum MissedFirePolicy {
+    /// defaults to skip
+    Unspecified = 0,
+    /// only fire times less than a minute late are materialized
+    Skip = 1,
+    /// every missed fire time is materialized
+    CatchUp = 2,
+}
+impl MissedFirePolicy {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "MISSED_FIRE_POLICY_UNSPECIFIED",
+            Self::Skip => "MISSED_FIRE_POLICY_SKIP",
+            Self::CatchUp => "MISSED_FIRE_POLICY_CATCH_UP",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "MISSED_FIRE_POLICY_UNSPECIFIED" => Some(Self::Unspecified),
+            "MISSED_FIRE_POLICY_SKIP" => Some(Self::Skip),
+            "MISSED_FIRE_POLICY_CATCH_UP" => Some(Self::CatchUp),
+            _ => None,
+        }
+    }
+}
 /// Generated client implementations.
 pub mod exchange_client {
     #![allow(
@@ -283,6 +1551,51 @@ pub mod exchange_client {
                 .insert(GrpcMethod::new("exchange.Exchange", "ExecCommand"));
             self.inner.unary(req, path, codec).await
         }
+        pub async fn subscribe(
+            &mut self,
+            request: impl tonic::IntoRequest<super::SubscribeReq>,
+        ) -> std::result::Result<
+            tonic::Response<tonic::codec::Streaming<super::SubscribeResp>>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/exchange.Exchange/Subscribe",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("exchange.Exchange", "Subscribe"));
+            self.inner.server_streaming(req, path, codec).await
+        }
+        pub async fn exec_batch(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ExecBatchReq>,
+        ) -> std::result::Result<tonic::Response<super::ExecBatchResp>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/exchange.Exchange/ExecBatch",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("exchange.Exchange", "ExecBatch"));
+            self.inner.unary(req, path, codec).await
+        }
     }
 }
 /// Generated server implementations.
@@ -302,6 +1615,20 @@ pub mod exchange_server {
             &self,
             request: tonic::Request<super::ExecCommandReq>,
         ) -> std::result::Result<tonic::Response<super::ExecCommandResp>, tonic::Status>;
+        /// Server streaming response type for the Subscribe method.
+        type SubscribeStream: tonic::codegen::tokio_stream::Stream<
+                Item = std::result::Result<super::SubscribeResp, tonic::Status>,
+            >
+            + std::marker::Send
+            + 'static;
+        async fn subscribe(
+            &self,
+            request: tonic::Request<super::SubscribeReq>,
+        ) -> std::result::Result<tonic::Response<Self::SubscribeStream>, tonic::Status>;
+        async fn exec_batch(
+            &self,
+            request: tonic::Request<super::ExecBatchReq>,
+        ) -> std::result::Result<tonic::Response<super::ExecBatchResp>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct ExchangeServer<T> {
@@ -422,6 +1749,95 @@ pub mod exchange_server {
                     };
                     Box::pin(fut)
                 }
+                "/exchange.Exchange/Subscribe" => {
+                    #[allow(non_camel_case_types)]
+                    struct SubscribeSvc<T: Exchange>(pub Arc<T>);
+                    impl<
+                        T: Exchange,
+                    > tonic::server::ServerStreamingService<super::SubscribeReq>
+                    for SubscribeSvc<T> {
+                        type Response = super::SubscribeResp;
+                        type ResponseStream = T::SubscribeStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::SubscribeReq>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Exchange>::subscribe(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = SubscribeSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/exchange.Exchange/ExecBatch" => {
+                    #[allow(non_camel_case_types)]
+                    struct ExecBatchSvc<T: Exchange>(pub Arc<T>);
+                    impl<T: Exchange> tonic::server::UnaryService<super::ExecBatchReq>
+                    for ExecBatchSvc<T> {
+                        type Response = super::ExecBatchResp;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ExecBatchReq>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Exchange>::exec_batch(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = ExecBatchSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => {
                     Box::pin(async move {
                         let mut response = http::Response::new(
@@ -1 +1,2 @@
 pub mod exchange;
+pub mod storage;
@@ -0,0 +1,122 @@
+// This file is @generated by prost-build.
+/// write-ahead log ///////////////////
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WalEntry {
+    #[prost(uint64, tag = "1")]
+    pub sequence: u64,
+    /// unix timestamp in milliseconds
+    #[prost(int64, tag = "2")]
+    pub timestamp: i64,
+    #[prost(message, optional, tag = "3")]
+    pub command: ::core::option::Option<super::exchange::Command>,
+    /// set instead of command for batches which are applied all-or-nothing
+    #[prost(message, repeated, tag = "4")]
+    pub batch: ::prost::alloc::vec::Vec<super::exchange::Command>,
+}
+/// snapshots /////////////////////////
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Snapshot {
+    /// every write-ahead log entry with a lower sequence is covered
+    #[prost(uint64, tag = "1")]
+    pub sequence: u64,
+    /// unix timestamp in milliseconds
+    #[prost(int64, tag = "2")]
+    pub timestamp: i64,
+    #[prost(message, repeated, tag = "3")]
+    pub queues: ::prost::alloc::vec::Vec<QueueState>,
+    #[prost(message, repeated, tag = "4")]
+    pub schedules: ::prost::alloc::vec::Vec<super::exchange::Schedule>,
+    #[prost(uint64, tag = "5")]
+    pub schedule_idx: u64,
+    #[prost(message, repeated, tag = "6")]
+    pub bindings: ::prost::alloc::vec::Vec<super::exchange::Binding>,
+    #[prost(uint64, tag = "7")]
+    pub binding_idx: u64,
+    #[prost(message, repeated, tag = "8")]
+    pub workflows: ::prost::alloc::vec::Vec<super::exchange::Workflow>,
+    #[prost(uint64, tag = "9")]
+    pub workflow_idx: u64,
+    #[prost(message, repeated, tag = "10")]
+    pub join_triggers: ::prost::alloc::vec::Vec<super::exchange::JoinTrigger>,
+    #[prost(uint64, tag = "11")]
+    pub join_trigger_idx: u64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct QueueState {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "2")]
+    pub events: ::prost::alloc::vec::Vec<EventState>,
+    #[prost(uint64, tag = "3")]
+    pub event_idx: u64,
+    #[prost(message, repeated, tag = "4")]
+    pub transactions: ::prost::alloc::vec::Vec<TransactionState>,
+    #[prost(uint64, tag = "5")]
+    pub transaction_idx: u64,
+    /// superseded by config.retry_policy, only read from older snapshots
+    #[prost(message, optional, tag = "6")]
+    pub retry_policy: ::core::option::Option<super::exchange::RetryPolicy>,
+    #[prost(message, optional, tag = "7")]
+    pub dead_letter: ::core::option::Option<super::exchange::DeadLetterConfig>,
+    #[prost(message, repeated, tag = "8")]
+    pub dead_letters: ::prost::alloc::vec::Vec<DeadLetterState>,
+    #[prost(uint64, optional, tag = "9")]
+    pub priority_aging_ms: ::core::option::Option<u64>,
+    #[prost(bool, tag = "10")]
+    pub paused: bool,
+    #[prost(message, optional, tag = "11")]
+    pub config: ::core::option::Option<super::exchange::QueueConfig>,
+    #[prost(message, repeated, tag = "12")]
+    pub dedup_keys: ::prost::alloc::vec::Vec<DedupKeyState>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DedupKeyState {
+    #[prost(string, tag = "1")]
+    pub key: ::prost::alloc::string::String,
+    #[prost(uint64, repeated, tag = "2")]
+    pub event_ids: ::prost::alloc::vec::Vec<u64>,
+    /// unix timestamp in milliseconds
+    #[prost(int64, tag = "3")]
+    pub added_at: i64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DeadLetterState {
+    #[prost(message, optional, tag = "1")]
+    pub event: ::core::option::Option<EventState>,
+    /// unix timestamp in milliseconds
+    #[prost(int64, tag = "2")]
+    pub dead_lettered_at: i64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EventState {
+    #[prost(message, optional, tag = "1")]
+    pub event: ::core::option::Option<super::exchange::Event>,
+    #[prost(string, optional, tag = "2")]
+    pub worker_id: ::core::option::Option<::prost::alloc::string::String>,
+    /// unix timestamp in milliseconds
+    #[prost(int64, optional, tag = "3")]
+    pub queued_at: ::core::option::Option<i64>,
+    /// pushed to a subscriber, such events are queued again on recovery as
+    /// subscriptions do not survive a restart
+    #[prost(bool, tag = "4")]
+    pub delivered: bool,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TransactionState {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+    #[prost(uint64, tag = "2")]
+    pub event_id: u64,
+    #[prost(message, repeated, tag = "3")]
+    pub command_triggers: ::prost::alloc::vec::Vec<CommandTriggerState>,
+    /// unix timestamp in milliseconds
+    #[prost(int64, optional, tag = "4")]
+    pub last_heartbeat_time: ::core::option::Option<i64>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CommandTriggerState {
+    #[prost(message, optional, tag = "1")]
+    pub trigger: ::core::option::Option<super::exchange::Trigger>,
+    #[prost(message, repeated, tag = "2")]
+    pub commands: ::prost::alloc::vec::Vec<super::exchange::Command>,
+}
@@ -1,10 +1,18 @@
 use thiserror::Error;
 
-use crate::exchange::exchange::ExchangeReq;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::exchange::exchange::{self, ExchangeReq};
+use crate::exchange::selector::Selector;
+use crate::rpc::proto::exchange::command_resp;
 use crate::rpc::proto::exchange::exchange_server::Exchange;
-use crate::rpc::proto::exchange::{ExecCommandReq, ExecCommandResp};
+use crate::rpc::proto::exchange::{
+    CommandResp, ErrorCode, ErrorResp, Event, ExecBatchReq, ExecBatchResp, ExecCommandReq,
+    ExecCommandResp, SubscribeReq, SubscribeResp,
+};
 use tokio::sync::mpsc;
-use tokio_util::sync::CancellationToken;
+use tokio_stream::Stream;
 use tonic::{Request, Response, Status};
 
 #[derive(Error, Debug)]
@@ -17,6 +25,38 @@ pub enum ExchangeError {
     ExchangeResponseError(String),
     #[error("request missing command")]
     RequestMissingCommand,
+    #[error("subscribe error: {0}")]
+    SubscribeError(String),
+}
+
+fn error_status(err: &ErrorResp) -> Status {
+    let code = match ErrorCode::try_from(err.code) {
+        Ok(ErrorCode::InvalidCommand)
+        | Ok(ErrorCode::InvalidSchedule)
+        | Ok(ErrorCode::InvalidBinding)
+        | Ok(ErrorCode::InvalidWorkflow)
+        | Ok(ErrorCode::InvalidJoinTrigger)
+        | Ok(ErrorCode::PayloadTooLarge)
+        | Ok(ErrorCode::TooManyAttributes)
+        | Ok(ErrorCode::InvalidQueueConfig) => tonic::Code::InvalidArgument,
+        Ok(ErrorCode::QueueNotFound)
+        | Ok(ErrorCode::TransactionNotFound)
+        | Ok(ErrorCode::EventNotFound)
+        | Ok(ErrorCode::DeadLetterNotFound)
+        | Ok(ErrorCode::ScheduleNotFound)
+        | Ok(ErrorCode::BindingNotFound)
+        | Ok(ErrorCode::WorkflowNotFound)
+        | Ok(ErrorCode::JoinTriggerNotFound) => tonic::Code::NotFound,
+        Ok(ErrorCode::QueueAlreadyExists) | Ok(ErrorCode::TransactionAlreadyExists) => {
+            tonic::Code::AlreadyExists
+        }
+        Ok(ErrorCode::EventNotRunning)
+        | Ok(ErrorCode::QueueHasRunningEvents)
+        | Ok(ErrorCode::QueueHasDependentEvents) => tonic::Code::FailedPrecondition,
+        Ok(ErrorCode::QueueFull) => tonic::Code::ResourceExhausted,
+        Ok(ErrorCode::Internal) | Ok(ErrorCode::Unspecified) | Err(_) => tonic::Code::Internal,
+    };
+    Status::new(code, err.error_msg.clone())
 }
 
 #[derive(Debug)]
@@ -30,8 +70,156 @@ impl ExchangeImpl {
     }
 }
 
+// Stream of events pushed to a subscribed worker. Dropping the stream,
+// which happens when the client disconnects, unsubscribes the worker so
+// its unacknowledged events are handed to another subscriber.
+pub struct SubscriptionStream {
+    queue_name: String,
+    subscriber_id: u64,
+    events: mpsc::Receiver<Event>,
+    exchange_chan: mpsc::Sender<ExchangeReq>,
+}
+
+impl Stream for SubscriptionStream {
+    type Item = Result<SubscribeResp, Status>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.events.poll_recv(cx) {
+            Poll::Ready(Some(event)) => Poll::Ready(Some(Ok(SubscribeResp { event: Some(event) }))),
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for SubscriptionStream {
+    fn drop(&mut self) {
+        self.events.close();
+        let req = ExchangeReq::new_unsubscribe(self.queue_name.clone(), self.subscriber_id);
+        let exchange_chan = self.exchange_chan.clone();
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            handle.spawn(async move {
+                if exchange_chan.send(req).await.is_err() {
+                    tracing::error!("unable to send unsubscribe request to exchange");
+                }
+            });
+        }
+    }
+}
+
 #[tonic::async_trait]
 impl Exchange for ExchangeImpl {
+    type SubscribeStream = SubscriptionStream;
+
+    async fn exec_batch(
+        &self,
+        request: Request<ExecBatchReq>,
+    ) -> Result<Response<ExecBatchResp>, Status> {
+        let msg = request.into_inner();
+
+        let (req, recv) = ExchangeReq::new_batch(msg.commands);
+
+        if let Err(err) = self
+            .exchange_chan
+            .send_timeout(req, std::time::Duration::from_secs(10))
+            .await
+        {
+            return Err(Status::internal(
+                ExchangeError::UnableToSendRequestToInternalExchange(err.to_string()).to_string(),
+            ));
+        };
+
+        match tokio::time::timeout(std::time::Duration::from_secs(5), recv).await {
+            Ok(Ok(resp)) => {
+                if !resp.applied {
+                    return Err(match resp.command_resps.last() {
+                        Some(CommandResp {
+                            command_resp: Some(command_resp::CommandResp::ErrorResp(err)),
+                        }) => error_status(err),
+                        _ => Status::internal(
+                            ExchangeError::ExchangeResponseError(
+                                "batch not applied without an error".to_string(),
+                            )
+                            .to_string(),
+                        ),
+                    });
+                }
+                Ok(Response::new(ExecBatchResp {
+                    command_resps: resp.command_resps,
+                    applied: resp.applied,
+                }))
+            }
+            Ok(Err(err)) => Err(Status::internal(
+                ExchangeError::ExchangeResponseError(err.to_string()).to_string(),
+            )),
+            Err(_) => Err(Status::internal(
+                ExchangeError::ExchangeResponseError("timeout waiting for response".to_string())
+                    .to_string(),
+            )),
+        }
+    }
+
+    async fn subscribe(
+        &self,
+        request: Request<SubscribeReq>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let msg = request.into_inner();
+
+        let selector = match msg.selector {
+            Some(selector) => match Selector::try_from(selector) {
+                Ok(selector) => selector,
+                Err(err) => {
+                    return Err(Status::invalid_argument(
+                        ExchangeError::SubscribeError(err.to_string()).to_string(),
+                    ));
+                }
+            },
+            None => Selector::default(),
+        };
+
+        let (req, recv, events) = ExchangeReq::new_subscribe(
+            msg.queue_name.clone(),
+            msg.prefetch,
+            msg.worker_id,
+            selector,
+        );
+
+        if let Err(err) = self
+            .exchange_chan
+            .send_timeout(req, std::time::Duration::from_secs(10))
+            .await
+        {
+            return Err(Status::internal(
+                ExchangeError::UnableToSendRequestToInternalExchange(err.to_string()).to_string(),
+            ));
+        };
+
+        match tokio::time::timeout(std::time::Duration::from_secs(5), recv).await {
+            Ok(Ok(Ok(subscriber_id))) => Ok(Response::new(SubscriptionStream {
+                queue_name: msg.queue_name,
+                subscriber_id,
+                events,
+                exchange_chan: self.exchange_chan.clone(),
+            })),
+            Ok(Ok(Err(err))) => {
+                let code = exchange::ErrorCode::from_error(&err);
+                Err(error_status(&ErrorResp {
+                    error_msg: ExchangeError::SubscribeError(err.to_string()).to_string(),
+                    code: ErrorCode::try_from(code)
+                        .unwrap_or(ErrorCode::Internal)
+                        .into(),
+                }))
+            }
+            Ok(Err(err)) => Err(Status::internal(
+                ExchangeError::ExchangeResponseError(err.to_string()).to_string(),
+            )),
+            Err(_) => Err(Status::internal(
+                ExchangeError::ExchangeResponseError("timeout waiting for response".to_string())
+                    .to_string(),
+            )),
+        }
+    }
+
     async fn exec_command(
         &self,
         request: Request<ExecCommandReq>,
@@ -59,9 +247,16 @@ impl Exchange for ExchangeImpl {
         };
 
         match tokio::time::timeout(std::time::Duration::from_secs(5), recv).await {
-            Ok(Ok(resp)) => Ok(Response::new(ExecCommandResp {
-                command_resp: Some(resp.command_resp),
-            })),
+            Ok(Ok(resp)) => {
+                if let Some(command_resp::CommandResp::ErrorResp(err)) =
+                    &resp.command_resp.command_resp
+                {
+                    return Err(error_status(err));
+                }
+                Ok(Response::new(ExecCommandResp {
+                    command_resp: Some(resp.command_resp),
+                }))
+            }
             Ok(Err(err)) => Err(Status::internal(
                 ExchangeError::ExchangeResponseError(err.to_string()).to_string(),
             )),
@@ -72,3 +267,64 @@ impl Exchange for ExchangeImpl {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status_code(code: i32) -> tonic::Code {
+        error_status(&ErrorResp {
+            error_msg: "error".to_string(),
+            code,
+        })
+        .code()
+    }
+
+    #[test]
+    fn error_codes_map_to_status_codes() {
+        let cases = [
+            (ErrorCode::InvalidCommand, tonic::Code::InvalidArgument),
+            (ErrorCode::InvalidSchedule, tonic::Code::InvalidArgument),
+            (ErrorCode::PayloadTooLarge, tonic::Code::InvalidArgument),
+            (ErrorCode::InvalidQueueConfig, tonic::Code::InvalidArgument),
+            (ErrorCode::QueueNotFound, tonic::Code::NotFound),
+            (ErrorCode::EventNotFound, tonic::Code::NotFound),
+            (ErrorCode::WorkflowNotFound, tonic::Code::NotFound),
+            (ErrorCode::QueueAlreadyExists, tonic::Code::AlreadyExists),
+            (
+                ErrorCode::TransactionAlreadyExists,
+                tonic::Code::AlreadyExists,
+            ),
+            (ErrorCode::EventNotRunning, tonic::Code::FailedPrecondition),
+            (
+                ErrorCode::QueueHasRunningEvents,
+                tonic::Code::FailedPrecondition,
+            ),
+            (
+                ErrorCode::QueueHasDependentEvents,
+                tonic::Code::FailedPrecondition,
+            ),
+            (ErrorCode::QueueFull, tonic::Code::ResourceExhausted),
+            (ErrorCode::Internal, tonic::Code::Internal),
+            (ErrorCode::Unspecified, tonic::Code::Internal),
+        ];
+        for (code, status_code_want) in cases {
+            assert_eq!(status_code(code.into()), status_code_want, "{:?}", code);
+        }
+        // codes added by newer servers are not known to this one
+        assert_eq!(status_code(i32::MAX), tonic::Code::Internal);
+    }
+
+    #[test]
+    fn exchange_errors_keep_their_status_code() {
+        let err: anyhow::Error = exchange::ExchangeError::QueueNotFound("test".to_string()).into();
+        let status = error_status(&ErrorResp {
+            error_msg: err.to_string(),
+            code: ErrorCode::try_from(exchange::ErrorCode::from_error(&err))
+                .unwrap()
+                .into(),
+        });
+        assert_eq!(status.code(), tonic::Code::NotFound);
+        assert_eq!(status.message(), err.to_string());
+    }
+}
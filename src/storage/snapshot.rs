@@ -0,0 +1,185 @@
+use anyhow::Result;
+use prost::Message;
+use std::io::Write;
+use thiserror::Error;
+use tracing::warn;
+
+use crate::rpc::proto;
+
+// every snapshot file is written as
+// [magic: 4 bytes][version: u32][length: u64][crc32 of payload: u32][payload]
+const SNAPSHOT_MAGIC: &[u8; 4] = b"OSNP";
+const SNAPSHOT_VERSION: u32 = 1;
+const SNAPSHOT_HEADER_SIZE: usize = 20;
+
+const SNAPSHOT_FILE_PREFIX: &str = "snapshot-";
+const SNAPSHOT_FILE_EXTENSION: &str = "snap";
+
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    #[error("snapshot file too short: {0} bytes")]
+    FileTooShort(usize),
+    #[error("invalid snapshot magic")]
+    InvalidMagic,
+    #[error("unsupported snapshot version: {0}")]
+    UnsupportedVersion(u32),
+    #[error("snapshot length mismatch: expected {expected}, found {found}")]
+    LengthMismatch { expected: u64, found: u64 },
+    #[error("snapshot checksum mismatch")]
+    ChecksumMismatch,
+}
+
+#[derive(Debug, Clone)]
+pub struct SnapshotConfig {
+    pub dir: std::path::PathBuf,
+    // take a snapshot once this much time has passed since the last one
+    pub interval: Option<std::time::Duration>,
+    // take a snapshot once the write-ahead log grows past this size
+    pub max_wal_bytes: Option<u64>,
+    // number of snapshots kept on disk, older ones are removed. The log
+    // is only truncated up to the oldest retained snapshot so recovery
+    // can fall back to it if a newer snapshot is unreadable.
+    pub retain: usize,
+}
+
+impl SnapshotConfig {
+    pub fn new(dir: std::path::PathBuf) -> SnapshotConfig {
+        SnapshotConfig {
+            dir,
+            interval: Some(std::time::Duration::from_secs(300)),
+            max_wal_bytes: Some(64 * 1024 * 1024),
+            retain: 2,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SnapshotStore {
+    config: SnapshotConfig,
+}
+
+impl SnapshotStore {
+    pub fn new(config: SnapshotConfig) -> Result<SnapshotStore> {
+        std::fs::create_dir_all(&config.dir)?;
+        Ok(SnapshotStore { config })
+    }
+
+    pub fn config(&self) -> &SnapshotConfig {
+        &self.config
+    }
+
+    // writes the snapshot to a temporary file and renames it into place
+    // so a crash never leaves a partially written snapshot behind
+    pub fn write(&self, snapshot: &proto::storage::Snapshot) -> Result<std::path::PathBuf> {
+        let payload = snapshot.encode_to_vec();
+
+        let mut buf: Vec<u8> = Vec::with_capacity(SNAPSHOT_HEADER_SIZE + payload.len());
+        buf.extend_from_slice(SNAPSHOT_MAGIC);
+        buf.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+        buf.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+        buf.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+        buf.extend_from_slice(&payload);
+
+        let path = self.snapshot_path(snapshot.sequence);
+        let tmp_path = path.with_extension("tmp");
+        {
+            let mut file = std::fs::File::create(&tmp_path)?;
+            file.write_all(&buf)?;
+            file.sync_all()?;
+        }
+        std::fs::rename(&tmp_path, &path)?;
+        std::fs::File::open(&self.config.dir)?.sync_all()?;
+
+        Ok(path)
+    }
+
+    // loads the newest snapshot which passes validation, skipping over
+    // any which are corrupt
+    pub fn load_latest(&self) -> Result<Option<proto::storage::Snapshot>> {
+        for (_, path) in self.list()?.into_iter().rev() {
+            match SnapshotStore::read(&path) {
+                Ok(snapshot) => return Ok(Some(snapshot)),
+                Err(err) => {
+                    warn!(
+                        path = format!("{:?}", path),
+                        error = format!("{:?}", err),
+                        "skipping invalid snapshot"
+                    );
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn read(path: &std::path::Path) -> Result<proto::storage::Snapshot> {
+        let buf = std::fs::read(path)?;
+        if buf.len() < SNAPSHOT_HEADER_SIZE {
+            return Err(SnapshotError::FileTooShort(buf.len()).into());
+        }
+        if &buf[0..4] != SNAPSHOT_MAGIC {
+            return Err(SnapshotError::InvalidMagic.into());
+        }
+
+        let version = u32::from_le_bytes(buf[4..8].try_into()?);
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version).into());
+        }
+
+        let len = u64::from_le_bytes(buf[8..16].try_into()?);
+        let checksum = u32::from_le_bytes(buf[16..20].try_into()?);
+        let payload = &buf[SNAPSHOT_HEADER_SIZE..];
+        if payload.len() as u64 != len {
+            return Err(SnapshotError::LengthMismatch {
+                expected: len,
+                found: payload.len() as u64,
+            }
+            .into());
+        }
+        if crc32fast::hash(payload) != checksum {
+            return Err(SnapshotError::ChecksumMismatch.into());
+        }
+
+        Ok(proto::storage::Snapshot::decode(payload)?)
+    }
+
+    // removes all but the newest retained snapshots and returns the
+    // sequence of the oldest one kept
+    pub fn prune(&self) -> Result<Option<u64>> {
+        let snapshots = self.list()?;
+        let retain = self.config.retain.max(1);
+        let remove = snapshots.len().saturating_sub(retain);
+
+        for (_, path) in snapshots.iter().take(remove) {
+            std::fs::remove_file(path)?;
+        }
+        Ok(snapshots.get(remove).map(|(sequence, _)| *sequence))
+    }
+
+    fn snapshot_path(&self, sequence: u64) -> std::path::PathBuf {
+        self.config.dir.join(format!(
+            "{}{:020}.{}",
+            SNAPSHOT_FILE_PREFIX, sequence, SNAPSHOT_FILE_EXTENSION
+        ))
+    }
+
+    // snapshots sorted by the sequence they cover, oldest first
+    fn list(&self) -> Result<Vec<(u64, std::path::PathBuf)>> {
+        let mut snapshots: Vec<(u64, std::path::PathBuf)> = Vec::new();
+        for entry in std::fs::read_dir(&self.config.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(SNAPSHOT_FILE_EXTENSION) {
+                continue;
+            }
+            let sequence = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.strip_prefix(SNAPSHOT_FILE_PREFIX))
+                .and_then(|sequence| sequence.parse::<u64>().ok());
+            if let Some(sequence) = sequence {
+                snapshots.push((sequence, path));
+            }
+        }
+        snapshots.sort();
+        Ok(snapshots)
+    }
+}
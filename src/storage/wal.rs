@@ -0,0 +1,359 @@
+use anyhow::Result;
+use prost::Message;
+use std::io::{Read, Seek, Write};
+use thiserror::Error;
+use tracing::warn;
+
+use crate::rpc::proto;
+
+// every record is written as [length: u32][crc32 of payload: u32][payload]
+const RECORD_HEADER_SIZE: usize = 8;
+
+#[derive(Debug, Error)]
+pub enum WalError {
+    #[error("record too large: {0} bytes")]
+    RecordTooLarge(usize),
+    #[error("sequence out of order: expected {expected}, received {received}")]
+    SequenceOutOfOrder { expected: u64, received: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FsyncPolicy {
+    // fsync after every appended record
+    Always,
+    // fsync at most once per interval, bounding the window of lost writes
+    Interval(std::time::Duration),
+    // leave flushing to the operating system
+    Never,
+}
+
+#[derive(Debug, Clone)]
+pub struct WalConfig {
+    pub path: std::path::PathBuf,
+    pub fsync_policy: FsyncPolicy,
+}
+
+impl WalConfig {
+    pub fn new(path: std::path::PathBuf) -> WalConfig {
+        WalConfig {
+            path,
+            fsync_policy: FsyncPolicy::Always,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Wal {
+    config: WalConfig,
+    file: std::fs::File,
+
+    next_sequence: u64,
+    size_bytes: u64,
+    unsynced: bool,
+    last_sync: std::time::Instant,
+    // a failed write may have left part of a record past size_bytes,
+    // nothing is appended until it has been cut off
+    torn: bool,
+}
+
+impl Wal {
+    // opens the log, creating it if needed, and returns the valid entries
+    // it holds. A torn or corrupt tail left by a crash is truncated.
+    pub fn open(config: WalConfig) -> Result<(Wal, Vec<proto::storage::WalEntry>)> {
+        if let Some(parent) = config.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&config.path)?;
+
+        let mut buf: Vec<u8> = Vec::new();
+        file.read_to_end(&mut buf)?;
+
+        let (entries, valid_len) = Wal::decode_records(&buf);
+        if valid_len < buf.len() {
+            warn!(
+                path = format!("{:?}", config.path),
+                valid_bytes = valid_len,
+                total_bytes = buf.len(),
+                "truncating corrupt write-ahead log tail"
+            );
+            file.set_len(valid_len as u64)?;
+            file.sync_all()?;
+        }
+        file.seek(std::io::SeekFrom::Start(valid_len as u64))?;
+
+        let next_sequence = entries.last().map(|entry| entry.sequence + 1).unwrap_or(0);
+
+        Ok((
+            Wal {
+                config,
+                file,
+                next_sequence,
+                size_bytes: valid_len as u64,
+                unsynced: false,
+                last_sync: std::time::Instant::now(),
+                torn: false,
+            },
+            entries,
+        ))
+    }
+
+    fn decode_records(buf: &[u8]) -> (Vec<proto::storage::WalEntry>, usize) {
+        let mut entries: Vec<proto::storage::WalEntry> = Vec::new();
+        let mut offset = 0;
+        while offset + RECORD_HEADER_SIZE <= buf.len() {
+            let len = u32::from_le_bytes([
+                buf[offset],
+                buf[offset + 1],
+                buf[offset + 2],
+                buf[offset + 3],
+            ]) as usize;
+            let checksum = u32::from_le_bytes([
+                buf[offset + 4],
+                buf[offset + 5],
+                buf[offset + 6],
+                buf[offset + 7],
+            ]);
+
+            let start = offset + RECORD_HEADER_SIZE;
+            let end = start + len;
+            if end > buf.len() {
+                break;
+            }
+            let payload = &buf[start..end];
+            if crc32fast::hash(payload) != checksum {
+                break;
+            }
+            let entry = match proto::storage::WalEntry::decode(payload) {
+                Ok(entry) => entry,
+                Err(_) => break,
+            };
+
+            entries.push(entry);
+            offset = end;
+        }
+        (entries, offset)
+    }
+
+    pub fn next_sequence(&self) -> u64 {
+        self.next_sequence
+    }
+
+    // entries below the sequence are covered by a snapshot and may have
+    // been truncated from the log, new entries must continue after it
+    pub fn advance_sequence(&mut self, sequence: u64) {
+        self.next_sequence = self.next_sequence.max(sequence);
+    }
+
+    pub fn size_bytes(&self) -> u64 {
+        self.size_bytes
+    }
+
+    // drops every entry with a lower sequence by rewriting the remaining
+    // tail to a new file which then replaces the log
+    pub fn truncate_before(&mut self, sequence: u64) -> Result<()> {
+        self.sync()?;
+
+        let buf = std::fs::read(&self.config.path)?;
+        let (entries, _) = Wal::decode_records(&buf);
+
+        let mut tail: Vec<u8> = Vec::new();
+        for entry in entries.iter().filter(|entry| entry.sequence >= sequence) {
+            tail.extend_from_slice(&Wal::encode_record(entry)?);
+        }
+
+        let tmp_path = self.config.path.with_extension("tmp");
+        {
+            let mut file = std::fs::File::create(&tmp_path)?;
+            file.write_all(&tail)?;
+            file.sync_all()?;
+        }
+        std::fs::rename(&tmp_path, &self.config.path)?;
+        if let Some(parent) = self.config.path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            std::fs::File::open(parent)?.sync_all()?;
+        }
+
+        self.file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&self.config.path)?;
+        self.size_bytes = tail.len() as u64;
+        self.torn = false;
+        Ok(())
+    }
+
+    pub fn append(&mut self, command: proto::exchange::Command) -> Result<u64> {
+        let sequence = self.next_sequence;
+        let entry = proto::storage::WalEntry {
+            sequence,
+            timestamp: chrono::Utc::now().timestamp_millis(),
+            command: Some(command),
+            batch: Vec::new(),
+        };
+        self.write_entry(&entry, true)?;
+        Ok(sequence)
+    }
+
+    // appends a command whose loss in a crash is harmless without syncing
+    // it, it is synced along with the next synced record or sync_if_due
+    pub fn append_unsynced(&mut self, command: proto::exchange::Command) -> Result<u64> {
+        let sequence = self.next_sequence;
+        let entry = proto::storage::WalEntry {
+            sequence,
+            timestamp: chrono::Utc::now().timestamp_millis(),
+            command: Some(command),
+            batch: Vec::new(),
+        };
+        self.write_entry(&entry, false)?;
+        Ok(sequence)
+    }
+
+    pub fn append_batch(&mut self, commands: Vec<proto::exchange::Command>) -> Result<u64> {
+        let sequence = self.next_sequence;
+        let entry = proto::storage::WalEntry {
+            sequence,
+            timestamp: chrono::Utc::now().timestamp_millis(),
+            command: None,
+            batch: commands,
+        };
+        self.write_entry(&entry, true)?;
+        Ok(sequence)
+    }
+
+    fn write_entry(&mut self, entry: &proto::storage::WalEntry, sync: bool) -> Result<()> {
+        self.cut_torn_record()?;
+        if entry.sequence != self.next_sequence {
+            return Err(WalError::SequenceOutOfOrder {
+                expected: self.next_sequence,
+                received: entry.sequence,
+            }
+            .into());
+        }
+
+        let record = Wal::encode_record(entry)?;
+        if let Err(err) = self.file.write_all(&record) {
+            self.torn = true;
+            if let Err(cut_err) = self.cut_torn_record() {
+                warn!(
+                    path = format!("{:?}", self.config.path),
+                    error = format!("{:?}", cut_err),
+                    "unable to cut partially written record, refusing appends"
+                );
+            }
+            return Err(err.into());
+        }
+
+        self.next_sequence += 1;
+        self.size_bytes += record.len() as u64;
+        self.unsynced = true;
+        if sync && self.config.fsync_policy == FsyncPolicy::Always {
+            self.sync()?;
+        }
+        Ok(())
+    }
+
+    // truncates the log back to the end of the last complete record, as
+    // recovery stops at the first corrupt record and would drop every
+    // record appended after a partial one
+    fn cut_torn_record(&mut self) -> Result<()> {
+        if !self.torn {
+            return Ok(());
+        }
+        self.file.set_len(self.size_bytes)?;
+        self.file.seek(std::io::SeekFrom::Start(self.size_bytes))?;
+        self.torn = false;
+        Ok(())
+    }
+
+    fn encode_record(entry: &proto::storage::WalEntry) -> Result<Vec<u8>> {
+        let payload = entry.encode_to_vec();
+        let len =
+            u32::try_from(payload.len()).map_err(|_| WalError::RecordTooLarge(payload.len()))?;
+
+        let mut record: Vec<u8> = Vec::with_capacity(RECORD_HEADER_SIZE + payload.len());
+        record.extend_from_slice(&len.to_le_bytes());
+        record.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+        record.extend_from_slice(&payload);
+        Ok(record)
+    }
+
+    pub fn sync(&mut self) -> Result<()> {
+        if self.unsynced {
+            self.file.sync_data()?;
+            self.unsynced = false;
+        }
+        self.last_sync = std::time::Instant::now();
+        Ok(())
+    }
+
+    // syncs the log if the interval of the fsync policy has elapsed, or
+    // records appended without a sync are pending under the always policy
+    pub fn sync_if_due(&mut self) -> Result<()> {
+        match self.config.fsync_policy {
+            FsyncPolicy::Interval(interval) if self.last_sync.elapsed() >= interval => self.sync(),
+            FsyncPolicy::Always => self.sync(),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heartbeat(event_id: u64) -> proto::exchange::Command {
+        proto::exchange::Command {
+            command: Some(proto::exchange::command::Command::Heartbeat(
+                proto::exchange::Heartbeat {
+                    queue_name: "test".to_string(),
+                    event_id,
+                },
+            )),
+        }
+    }
+
+    fn test_path(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("orkidea-wal-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir.join("wal.log")
+    }
+
+    #[test]
+    fn appends_after_a_torn_record_are_recovered() {
+        let path = test_path("torn");
+        let (mut wal, entries) = Wal::open(WalConfig::new(path.clone())).unwrap();
+        assert!(entries.is_empty());
+        wal.append(heartbeat(0)).unwrap();
+
+        // what a write failing part way through leaves behind
+        wal.file.write_all(&[1, 2, 3]).unwrap();
+        wal.torn = true;
+        wal.append(heartbeat(1)).unwrap();
+        drop(wal);
+
+        let (wal, entries) = Wal::open(WalConfig::new(path.clone())).unwrap();
+        let sequences: Vec<u64> = entries.iter().map(|entry| entry.sequence).collect();
+        assert_eq!(sequences, vec![0, 1]);
+        assert_eq!(wal.next_sequence(), 2);
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn unsynced_appends_are_synced_by_the_next_sweep() {
+        let path = test_path("unsynced");
+        let (mut wal, _) = Wal::open(WalConfig::new(path.clone())).unwrap();
+        wal.append_unsynced(heartbeat(0)).unwrap();
+        assert!(wal.unsynced);
+        wal.sync_if_due().unwrap();
+        assert!(!wal.unsynced);
+
+        wal.append(heartbeat(1)).unwrap();
+        assert!(!wal.unsynced);
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+}
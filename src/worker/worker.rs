@@ -1,26 +1,27 @@
 use anyhow::Result;
 use tokio::{runtime::Runtime, sync::mpsc};
 use tokio_util::sync::CancellationToken;
-use tonic::{Request, Response, Status, transport::Server};
+use tonic::transport::Server;
 use tower_http::trace::{DefaultOnRequest, DefaultOnResponse, TraceLayer};
 use tracing::error;
-use tracing::{Level, info};
 
 use crate::{
-    exchange::exchange::{Exchange, ExchangeReq},
+    exchange::exchange::{Exchange, ExchangeConfig, ExchangeReq},
     rpc::{proto::exchange::exchange_server::ExchangeServer, server::exchange::ExchangeImpl},
 };
 
 pub struct Worker {
     runtime: Runtime,
     ct: CancellationToken,
+    exchange_config: ExchangeConfig,
 }
 
 impl Worker {
-    pub fn new() -> Result<Worker> {
+    pub fn new(exchange_config: ExchangeConfig) -> Result<Worker> {
         Ok(Worker {
             runtime: Runtime::new()?,
             ct: CancellationToken::new(),
+            exchange_config,
         })
     }
 
@@ -31,7 +32,8 @@ impl Worker {
         let ct = self.ct.child_token();
 
         // start the exchange
-        let mut exchange = Exchange::new();
+        let mut exchange = Exchange::new(self.exchange_config.clone());
+        exchange.recover()?;
         let exchange_handle = std::thread::spawn(move || {
             if let Err(err) = exchange.run(receiver) {
                 error!(error = format!("{:?}", err), "error running exchange");
@@ -59,7 +61,7 @@ impl Worker {
         });
 
         // wait for the exchange to shutdown
-        if let Err(_) = exchange_handle.join() {
+        if exchange_handle.join().is_err() {
             error!("error joining the exchange thread");
         };
 